@@ -562,6 +562,24 @@ impl GLTexture2D {
         );
     }
 
+    /// Replaces rows of an RGBA image that was loaded before, starting at row `y`.
+    ///
+    /// The buffer starts at the first row to replace. Whole rows are replaced, since
+    /// selecting a part of a row with `UNPACK_ROW_LENGTH` needs GLES 3.
+    pub unsafe fn load_rows_raw(&self, y: i32, width: i32, height: i32, buffer: *const u8) {
+        gl::TexSubImage2D(
+            gl::TEXTURE_2D,
+            0,
+            0,
+            y,
+            width,
+            height,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            buffer as *const GLvoid,
+        );
+    }
+
     /// Loads an RGBA image.
     pub unsafe fn load_image(&self, width: i32, height: i32, buffer: &[u8]) {
         self.load_image_raw(width, height, buffer as *const [u8] as *const u8);
@@ -944,6 +962,16 @@ pub unsafe fn enable_stencil(enabled: bool) {
     set_cap_enabled(gl::STENCIL_TEST, enabled);
 }
 
+/// Enables or disables scissor testing.
+pub unsafe fn enable_scissor_test(enabled: bool) {
+    set_cap_enabled(gl::SCISSOR_TEST, enabled);
+}
+
+/// Sets the scissor box in window coordinates, which start at the bottom left.
+pub unsafe fn scissor(x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+    gl::Scissor(x, y, width, height);
+}
+
 /// Sets the depth test function.
 pub unsafe fn set_depth_fn(function: GLPassFunction) {
     gl::DepthFunc(function.to_gl_const());
@@ -0,0 +1,371 @@
+//! The config file, with rules that decide which space a new window goes into and settings
+//! for decorations and outputs.
+//!
+//! The config is read from `$XDG_CONFIG_HOME/sfc/config` (or `~/.config/sfc/config`), with one
+//! rule or setting per line:
+//!
+//! ```text
+//! # browsers open every window in its own space
+//! app_id=firefox new-space
+//! app_id=org.gnome.* title=*Preferences* join-focused
+//! # terminals open next to whatever is in front
+//! app_id=*terminal* split
+//! ```
+//!
+//! A line `decorations all` draws title strips above all windows instead of only the ones in
+//! the Slide Over panel, and a line `scale 1.5` sets the output scale instead of deriving it
+//! from the screen’s pixel density. A line `outputs mirror` shows the same on all outputs
+//! instead of extending the layout onto them, and a line `primary DP-1` shows the tablet UI on
+//! that output instead of the built-in screen.
+//!
+//! A rule has any number of `app_id=` and `title=` patterns, where `*` matches anything, and
+//! ends with an action. The first rule whose patterns all match wins. Windows that don’t match
+//! any rule are grouped by app id, except for windows without an app id, which each get their
+//! own space.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Which windows get a title strip drawn by sfc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationPolicy {
+    /// Only windows in the Slide Over panel.
+    Floating,
+    /// All windows.
+    All,
+}
+
+impl Default for DecorationPolicy {
+    fn default() -> DecorationPolicy {
+        DecorationPolicy::Floating
+    }
+}
+
+/// How outputs other than the primary one are used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// They show the primary output’s content.
+    Mirror,
+    /// They extend the layout to the right and show the spaces that were moved to them.
+    Extend,
+}
+
+impl Default for OutputMode {
+    fn default() -> OutputMode {
+        OutputMode::Extend
+    }
+}
+
+/// The app id used for windows that don’t have one.
+pub const UNKNOWN_APP_ID: &str = "???";
+
+/// Where a new window should go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpaceRule {
+    /// Open the window in a new space.
+    NewSpace,
+    /// Open the window in the space of the app id, creating it if necessary.
+    GroupByAppId,
+    /// Open the window in the frontmost space.
+    JoinFocused,
+    /// Open the window in the frontmost space, in Split View next to its topmost window.
+    Split,
+    /// Open the window in the Slide Over panel.
+    SlideOver,
+}
+
+/// A pattern in which `*` matches any number of characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern(String);
+
+impl Pattern {
+    fn matches(&self, text: &str) -> bool {
+        let mut parts = self.0.split('*');
+        // there’s always at least one part
+        let first = parts.next().unwrap();
+        if !text.starts_with(first) {
+            return false;
+        }
+        let mut rest = &text[first.len()..];
+
+        let parts: Vec<_> = parts.collect();
+        let last = match parts.split_last() {
+            Some((last, middle)) => {
+                for part in middle {
+                    match rest.find(part) {
+                        Some(index) => rest = &rest[index + part.len()..],
+                        None => return false,
+                    }
+                }
+                last
+            }
+            // no wildcards
+            None => return rest.is_empty(),
+        };
+        rest.ends_with(last)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    app_ids: Vec<Pattern>,
+    titles: Vec<Pattern>,
+    action: SpaceRule,
+}
+
+impl Rule {
+    fn matches(&self, app_id: &str, title: &str) -> bool {
+        self.app_ids.iter().all(|pattern| pattern.matches(app_id))
+            && self.titles.iter().all(|pattern| pattern.matches(title))
+    }
+}
+
+/// An error in a line of the config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// The window grouping rules and the settings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    rules: Vec<Rule>,
+    pub decorations: DecorationPolicy,
+    /// The output scale, if set by the user.
+    pub scale: Option<f64>,
+    pub outputs: OutputMode,
+    /// The output that shows the tablet UI, if set by the user.
+    pub primary_output: Option<String>,
+}
+
+impl Config {
+    /// Returns the path of the user’s config file.
+    fn path() -> Option<PathBuf> {
+        let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_dir.join("sfc").join("config"))
+    }
+
+    /// Loads the user’s config, skipping invalid lines.
+    ///
+    /// Returns the default config if there is no config file.
+    pub fn load() -> Config {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Config::default(),
+        };
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(err) => {
+                debug!("Not loading config from {}: {}", path.display(), err);
+                return Config::default();
+            }
+        };
+
+        let (config, errors) = Config::parse(&source);
+        for err in errors {
+            warn!("Ignoring line in {}: {}", path.display(), err);
+        }
+        info!(
+            "Loaded {} rules from {}",
+            config.rules.len(),
+            path.display()
+        );
+        config
+    }
+
+    /// Parses a config, returning all valid rules and settings and errors for the invalid lines.
+    pub fn parse(source: &str) -> (Config, Vec<ParseError>) {
+        let mut rules = Vec::new();
+        let mut decorations = DecorationPolicy::default();
+        let mut scale = None;
+        let mut outputs = OutputMode::default();
+        let mut primary_output = None;
+        let mut errors = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with("decorations ") {
+                match line["decorations ".len()..].trim() {
+                    "floating" => decorations = DecorationPolicy::Floating,
+                    "all" => decorations = DecorationPolicy::All,
+                    policy => errors.push(ParseError {
+                        line: index + 1,
+                        message: format!("unknown decoration policy {:?}", policy),
+                    }),
+                }
+                continue;
+            }
+            if line.starts_with("outputs ") {
+                match line["outputs ".len()..].trim() {
+                    "mirror" => outputs = OutputMode::Mirror,
+                    "extend" => outputs = OutputMode::Extend,
+                    mode => errors.push(ParseError {
+                        line: index + 1,
+                        message: format!("unknown output mode {:?}", mode),
+                    }),
+                }
+                continue;
+            }
+            if line.starts_with("primary ") {
+                primary_output = Some(line["primary ".len()..].trim().into());
+                continue;
+            }
+            if line.starts_with("scale ") {
+                let value = line["scale ".len()..].trim();
+                match value.parse::<f64>() {
+                    Ok(value) if value > 0. => scale = Some(value),
+                    _ => errors.push(ParseError {
+                        line: index + 1,
+                        message: format!("invalid scale {:?}", value),
+                    }),
+                }
+                continue;
+            }
+            match Self::parse_rule(line) {
+                Ok(rule) => rules.push(rule),
+                Err(message) => errors.push(ParseError {
+                    line: index + 1,
+                    message,
+                }),
+            }
+        }
+
+        let config = Config {
+            rules,
+            decorations,
+            scale,
+            outputs,
+            primary_output,
+        };
+        (config, errors)
+    }
+
+    fn parse_rule(line: &str) -> Result<Rule, String> {
+        let mut app_ids = Vec::new();
+        let mut titles = Vec::new();
+        let mut action = None;
+
+        for word in line.split_whitespace() {
+            if action.is_some() {
+                return Err(format!("unexpected {:?} after the action", word));
+            }
+            if word.starts_with("app_id=") {
+                app_ids.push(Pattern(word["app_id=".len()..].into()));
+            } else if word.starts_with("title=") {
+                titles.push(Pattern(word["title=".len()..].into()));
+            } else {
+                action = Some(match word {
+                    "new-space" => SpaceRule::NewSpace,
+                    "group-by-app-id" => SpaceRule::GroupByAppId,
+                    "join-focused" => SpaceRule::JoinFocused,
+                    "split" => SpaceRule::Split,
+                    "slide-over" => SpaceRule::SlideOver,
+                    _ => return Err(format!("unknown action {:?}", word)),
+                });
+            }
+        }
+
+        match action {
+            Some(action) => Ok(Rule {
+                app_ids,
+                titles,
+                action,
+            }),
+            None => Err("missing action".into()),
+        }
+    }
+
+    /// Returns where a new window with the given app id and title should go.
+    pub fn space_rule(&self, app_id: &str, title: &str) -> SpaceRule {
+        match self.rules.iter().find(|rule| rule.matches(app_id, title)) {
+            Some(rule) => rule.action,
+            None if app_id == UNKNOWN_APP_ID => SpaceRule::NewSpace,
+            None => SpaceRule::GroupByAppId,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Pattern(pattern.into()).matches(text)
+    }
+
+    #[test]
+    fn patterns_match_with_wildcards() {
+        assert!(matches("firefox", "firefox"));
+        assert!(!matches("firefox", "firefox-esr"));
+        assert!(!matches("firefox", "fire"));
+
+        assert!(matches("org.gnome.*", "org.gnome.Nautilus"));
+        assert!(matches("org.gnome.*", "org.gnome."));
+        assert!(!matches("org.gnome.*", "org.kde.dolphin"));
+
+        assert!(matches("*terminal", "gnome-terminal"));
+        assert!(!matches("*terminal", "terminal-app"));
+        assert!(matches("*Picture*", "Picture in Picture"));
+        assert!(matches("a*b*c", "abc"));
+        assert!(!matches("a*a", "a"));
+
+        // ? isn’t a wildcard, so windows without an app id only match it literally
+        assert!(matches("???", UNKNOWN_APP_ID));
+        assert!(!matches("???", "abc"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn parsing_skips_comments_and_reports_invalid_lines() {
+        let (config, errors) = Config::parse(
+            "# comment\n\
+             \n\
+             \x20  # indented comment\n\
+             app_id=firefox new-space\n\
+             frobnicate all\n\
+             app_id=broken\n\
+             title=x split extra\n\
+             decorations some\n\
+             outputs mirror\n\
+             primary DP-1\n\
+             app_id=* title=*Preferences* join-focused\n",
+        );
+        let lines: Vec<_> = errors.iter().map(|err| err.line).collect();
+        assert_eq!(lines, [5, 6, 7, 8]);
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.decorations, DecorationPolicy::Floating);
+        assert_eq!(config.outputs, OutputMode::Mirror);
+        assert_eq!(config.primary_output, Some("DP-1".into()));
+
+        assert_eq!(config.space_rule("firefox", ""), SpaceRule::NewSpace);
+        assert_eq!(
+            config.space_rule("gedit", "Preferences"),
+            SpaceRule::JoinFocused
+        );
+        assert_eq!(config.space_rule("gedit", ""), SpaceRule::GroupByAppId);
+        assert_eq!(config.space_rule(UNKNOWN_APP_ID, ""), SpaceRule::NewSpace);
+    }
+
+    #[test]
+    fn scale_is_positive() {
+        let (config, errors) = Config::parse("scale 1.5\nscale zero\nscale -1\n");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(config.scale, Some(1.5));
+        assert_eq!(Config::parse("").0.scale, None);
+    }
+}
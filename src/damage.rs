@@ -0,0 +1,266 @@
+//! Damage tracking, so that outputs only draw a frame when something on them changed, and then
+//! only redraw the part that changed.
+//!
+//! Surface commits damage the area of the surface on the outputs that show it, while input and
+//! layout changes damage whole outputs. Animations that are still running ask for another frame
+//! with `request_frame`, or `request_frame_in` if they only move within a part of the screen, and
+//! the clock in the status bar is redrawn when the minute changes.
+
+use crate::utils::{
+    container_of, intersection, list_remove, listener, signal_add, union, with_server,
+};
+use cairo::Rectangle;
+use std::cell::Cell;
+use std::fmt;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use std::time::Duration;
+use wlroots::wlroots_sys::*;
+
+/// A frame that an animation asked for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameRequest {
+    /// Everything on the output is redrawn.
+    Whole,
+    /// Only the given area is redrawn, in the coordinates of the output’s renderer.
+    Area(Rectangle),
+}
+
+thread_local! {
+    static FRAME_REQUEST: Cell<Option<FrameRequest>> = Cell::new(None);
+}
+
+/// Asks for another frame that redraws the whole output, for animations that are still running.
+pub fn request_frame() {
+    FRAME_REQUEST.with(|request| request.set(Some(FrameRequest::Whole)));
+}
+
+/// Asks for another frame that only redraws the given area, which has to cover everywhere the
+/// animation can move to until the next frame.
+pub fn request_frame_in(area: Rectangle) {
+    FRAME_REQUEST.with(|request| {
+        request.set(Some(match request.get() {
+            Some(FrameRequest::Whole) => FrameRequest::Whole,
+            Some(FrameRequest::Area(requested)) => FrameRequest::Area(union(requested, area)),
+            None => FrameRequest::Area(area),
+        }))
+    });
+}
+
+/// Returns the frame that was requested since this was last called.
+pub fn take_frame_request() -> Option<FrameRequest> {
+    FRAME_REQUEST.with(|request| request.take())
+}
+
+/// What changed on an output since its last frame, in the output’s logical coordinates.
+///
+/// Output buffers are assumed to be swapped between two buffers, so every frame also redraws
+/// what the frame before it changed, which the other buffer is still missing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Damage {
+    current: Option<Rectangle>,
+    whole: bool,
+    /// What the last frame drew.
+    previous: Option<Rectangle>,
+}
+
+impl Damage {
+    /// Creates damage that covers the whole output, since nothing was drawn on it yet.
+    pub fn new() -> Damage {
+        Damage {
+            current: None,
+            whole: true,
+            previous: None,
+        }
+    }
+
+    pub fn add(&mut self, area: Rectangle) {
+        if area.width <= 0. || area.height <= 0. {
+            return;
+        }
+        self.current = Some(match self.current {
+            Some(current) => union(current, area),
+            None => area,
+        });
+    }
+
+    pub fn add_whole(&mut self) {
+        self.whole = true;
+    }
+
+    /// Returns true if the output doesn’t need to draw a frame.
+    pub fn is_empty(&self) -> bool {
+        !self.whole && self.current.is_none()
+    }
+
+    /// Returns the area that the next frame has to redraw, or `None` if it doesn’t have to be
+    /// drawn, and starts collecting damage for the frame after it.
+    pub fn take(&mut self, screen: Rectangle) -> Option<Rectangle> {
+        let current = if self.whole {
+            Some(screen)
+        } else {
+            self.current.and_then(|area| intersection(area, screen))
+        };
+        self.current = None;
+        self.whole = false;
+
+        let current = current?;
+        let area = match self.previous {
+            Some(previous) => union(current, previous),
+            None => current,
+        };
+        self.previous = Some(current);
+        intersection(area, screen)
+    }
+}
+
+/// Listens for new surfaces to track their commits, redraws the clock every minute, and hides
+/// the status bar and home bar again after they were revealed.
+#[repr(C)]
+pub struct DamageTracker {
+    new_surface: wl_listener,
+    clock_timer: *mut wl_event_source,
+    chrome_timer: *mut wl_event_source,
+}
+
+impl fmt::Debug for DamageTracker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DamageTracker")
+    }
+}
+
+impl DamageTracker {
+    /// Starts tracking the surfaces of the given compositor, and the clock with the event loop
+    /// of the given display.
+    pub unsafe fn new(
+        display: *mut wl_display,
+        compositor: *mut wlr_compositor,
+    ) -> Box<DamageTracker> {
+        let event_loop = wl_display_get_event_loop(display);
+        let mut this = Box::new(DamageTracker {
+            new_surface: listener(new_surface_notify),
+            clock_timer: wl_event_loop_add_timer(event_loop, clock_notify, ptr::null_mut()),
+            chrome_timer: wl_event_loop_add_timer(event_loop, chrome_notify, ptr::null_mut()),
+        });
+        signal_add(&mut (*compositor).events.new_surface, &mut this.new_surface);
+        this.schedule_clock();
+        this
+    }
+
+    /// Sets the clock timer to go off when the next minute starts.
+    pub fn schedule_clock(&self) {
+        let now = time::now();
+        let elapsed = now.tm_sec * 1000 + now.tm_nsec / 1_000_000;
+        unsafe { wl_event_source_timer_update(self.clock_timer, (60_000 - elapsed).max(1)) };
+    }
+
+    /// Sets the timer that hides the status bar and home bar to go off after the given duration,
+    /// replacing the previous one.
+    pub fn schedule_chrome_hide(&self, duration: Duration) {
+        let ms = duration.as_secs() as c_int * 1000 + duration.subsec_millis() as c_int;
+        unsafe { wl_event_source_timer_update(self.chrome_timer, ms.max(1)) };
+    }
+}
+
+unsafe extern "C" fn clock_notify(_: *mut c_void) -> c_int {
+    with_server("clock", |server| server.clock_ticked());
+    0
+}
+
+unsafe extern "C" fn chrome_notify(_: *mut c_void) -> c_int {
+    with_server("chrome", |server| server.hide_chrome());
+    0
+}
+
+/// Listeners for a single surface, freed when the surface is destroyed.
+#[repr(C)]
+struct SurfaceListeners {
+    // the order of these fields is used to get back to the struct from the listeners
+    commit: wl_listener,
+    destroy: wl_listener,
+    surface: *mut wlr_surface,
+    /// Where the surface was shown after its last commit, in layout coordinates.
+    bounds: Option<Rectangle>,
+}
+
+unsafe extern "C" fn new_surface_notify(_: *mut wl_listener, data: *mut c_void) {
+    let surface = data as *mut wlr_surface;
+    let listeners = Box::into_raw(Box::new(SurfaceListeners {
+        commit: listener(commit_notify),
+        destroy: listener(destroy_notify),
+        surface,
+        bounds: None,
+    }));
+    signal_add(&mut (*surface).events.commit, &mut (*listeners).commit);
+    signal_add(&mut (*surface).events.destroy, &mut (*listeners).destroy);
+}
+
+unsafe extern "C" fn commit_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let listeners = container_of::<SurfaceListeners>(listener, 0);
+    let (surface, previous) = ((*listeners).surface, (*listeners).bounds);
+    with_server("commit", |server| {
+        (*listeners).bounds = server.surface_committed(surface, previous);
+    });
+}
+
+unsafe extern "C" fn destroy_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let listeners = container_of::<SurfaceListeners>(listener, 1);
+    list_remove(&mut (*listeners).commit.link);
+    list_remove(&mut (*listeners).destroy.link);
+    if let Some(bounds) = (*listeners).bounds {
+        with_server("destroy", |server| server.outputs.damage(bounds));
+    }
+    drop(Box::from_raw(listeners));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn frames_redraw_what_the_previous_frame_changed() {
+        let screen = rect(0., 0., 100., 100.);
+        let mut damage = Damage::new();
+        assert_eq!(damage.take(screen), Some(screen));
+        assert!(damage.is_empty());
+        assert_eq!(damage.take(screen), None);
+
+        // the other buffer is still missing the whole first frame
+        damage.add(rect(10., 10., 10., 10.));
+        assert_eq!(damage.take(screen), Some(screen));
+
+        damage.add(rect(50., 50., 10., 10.));
+        assert_eq!(damage.take(screen), Some(rect(10., 10., 50., 50.)));
+
+        // damage outside of the screen is ignored
+        damage.add(rect(90., 90., 20., 20.));
+        assert_eq!(damage.take(screen), Some(rect(50., 50., 50., 50.)));
+        damage.add(rect(200., 0., 10., 10.));
+        assert_eq!(damage.take(screen), None);
+    }
+
+    #[test]
+    fn frame_requests_cover_every_animation() {
+        request_frame_in(rect(0., 0., 10., 10.));
+        request_frame_in(rect(20., 20., 10., 10.));
+        assert_eq!(
+            take_frame_request(),
+            Some(FrameRequest::Area(rect(0., 0., 30., 30.)))
+        );
+        assert_eq!(take_frame_request(), None);
+
+        request_frame_in(rect(0., 0., 10., 10.));
+        request_frame();
+        request_frame_in(rect(20., 20., 10., 10.));
+        assert_eq!(take_frame_request(), Some(FrameRequest::Whole));
+    }
+}
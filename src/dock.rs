@@ -27,9 +27,12 @@ impl Dock {
     }
 
     fn draw(&mut self, renderer: &Renderer) {
-        if self.cairo_tex.is_none() {
-            let (width, height) = renderer.dimensions();
-            let resolution = renderer.resolution();
+        let (width, height) = renderer.dimensions();
+        let resolution = renderer.resolution();
+        let current = self.cairo_tex.as_ref().map_or(false, |cairo_tex| {
+            cairo_tex.size() == (width, height) && cairo_tex.resolution() == resolution
+        });
+        if !current {
             self.cairo_tex = Some(CairoTex::new(width, height, resolution));
         }
 
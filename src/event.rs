@@ -8,8 +8,12 @@ use wlroots::wlr_axis_source;
 use wlroots::TouchId;
 use wlroots::wlr_tablet_tool_proximity_state::*;
 use wlroots::wlr_key_state::*;
+use wlroots::wlr_button_state::*;
+use wlroots::wlr_tablet_tool_tip_state::*;
+use crate::tablet::{TabletToolId, ToolAxes};
 use std::mem;
 
+#[derive(Clone, Copy)]
 pub enum Event {
     PointerDown {
         button: u32,
@@ -20,10 +24,15 @@ pub enum Event {
     PointerMotion {
         location: Vector2<f64>,
     },
+    /// Relative pointer motion; resolved to `PointerMotion` by the server.
+    PointerMotionRelative {
+        delta: Vector2<f64>,
+    },
     PointerAxis {
         source: wlr_axis_source,
         orientation: wlr_axis_orientation,
         delta: f64,
+        delta_discrete: i32,
     },
     TouchDown {
         id: TouchId,
@@ -40,30 +49,34 @@ pub enum Event {
         id: TouchId,
     },
     TabletProximityIn {
+        tool: TabletToolId,
         location: Vector2<f64>,
     },
     TabletProximityOut {
+        tool: TabletToolId,
         location: Vector2<f64>,
     },
     TabletTipDown {
+        tool: TabletToolId,
         location: Vector2<f64>,
     },
     TabletTipUp {
+        tool: TabletToolId,
         location: Vector2<f64>,
     },
     TabletButtonDown {
+        tool: TabletToolId,
         button: u32,
     },
     TabletButtonUp {
+        tool: TabletToolId,
         button: u32,
     },
     TabletAxis {
+        tool: TabletToolId,
+        /// Coordinates that didn’t change are only filled in by `TabletManager::track_location`.
         location: Vector2<f64>,
-        pressure: f64,
-        distance: f64,
-        tilt: (f64, f64),
-        slider: f64,
-        wheel_delta: f64,
+        axes: ToolAxes,
     },
     KeyDown {
         code: u32,
@@ -78,6 +91,7 @@ impl Event {
             | Event::PointerDown { .. }
             | Event::PointerUp { .. }
             | Event::PointerMotion { .. }
+            | Event::PointerMotionRelative { .. }
             | Event::PointerAxis { .. }
             | Event::TouchDown { .. }
             | Event::TouchMotion { .. }
@@ -97,6 +111,20 @@ impl Event {
 
     // TODO: deduplicate the following two
 
+    /// Returns the tablet tool for tablet events.
+    pub fn tablet_tool(&self) -> Option<TabletToolId> {
+        match self {
+            | Event::TabletProximityIn { tool, .. }
+            | Event::TabletProximityOut { tool, .. }
+            | Event::TabletTipDown { tool, .. }
+            | Event::TabletTipUp { tool, .. }
+            | Event::TabletButtonDown { tool, .. }
+            | Event::TabletButtonUp { tool, .. }
+            | Event::TabletAxis { tool, .. } => Some(*tool),
+            _ => None,
+        }
+    }
+
     pub fn location_mut(&mut self) -> Option<&mut Vector2<f64>> {
         match self {
             | Event::PointerMotion { location, .. }
@@ -109,6 +137,7 @@ impl Event {
             | Event::TabletAxis { location, .. } => Some(location),
             | Event::PointerDown { .. }
             | Event::PointerUp { .. }
+            | Event::PointerMotionRelative { .. }
             | Event::PointerAxis { .. }
             | Event::TouchUp { .. }
             | Event::TouchCancel { .. }
@@ -131,6 +160,7 @@ impl Event {
             | Event::TabletAxis { location, .. } => Some(*location),
             | Event::PointerDown { .. }
             | Event::PointerUp { .. }
+            | Event::PointerMotionRelative { .. }
             | Event::PointerAxis { .. }
             | Event::TouchUp { .. }
             | Event::TouchCancel { .. }
@@ -147,10 +177,42 @@ fn create_touchid(id: i32) -> TouchId {
     unsafe { mem::transmute::<i32, TouchId>(id) }
 }
 
+/// Reads the tablet and tool from a tablet tool event.
+macro_rules! tablet_tool_id {
+    ($event:expr) => {
+        unsafe {
+            let raw = $event.as_ptr();
+            TabletToolId {
+                tablet: (*raw).device,
+                tool: (*raw).tool,
+            }
+        }
+    };
+}
 
 impl<'a> From<RawEvent<'a>> for Event {
     fn from(event: RawEvent<'a>) -> Event {
         match event {
+            RawEvent::PointerButton(event) => match event.state() {
+                WLR_BUTTON_PRESSED => Event::PointerDown {
+                    button: event.button(),
+                },
+                WLR_BUTTON_RELEASED => Event::PointerUp {
+                    button: event.button(),
+                },
+            },
+            RawEvent::PointerMotion(event) => Event::PointerMotionRelative {
+                delta: event.delta().into(),
+            },
+            RawEvent::PointerAbsMotion(event) => Event::PointerMotion {
+                location: event.pos().into(),
+            },
+            RawEvent::PointerAxis(event) => Event::PointerAxis {
+                source: event.source(),
+                orientation: event.orientation(),
+                delta: event.delta(),
+                delta_discrete: event.delta_discrete(),
+            },
             RawEvent::TouchDown(event) => Event::TouchDown {
                 id: create_touchid(event.touch_id()),
                 location: event.location().into(),
@@ -162,21 +224,50 @@ impl<'a> From<RawEvent<'a>> for Event {
             RawEvent::TouchUp(event) => Event::TouchUp {
                 id: create_touchid(event.touch_id()),
             },
+            RawEvent::TouchCancel(event) => Event::TouchCancel {
+                id: create_touchid(event.touch_id()),
+            },
             RawEvent::TabletProximity(event) => match event.state() {
                 WLR_TABLET_TOOL_PROXIMITY_IN => Event::TabletProximityIn {
+                    tool: tablet_tool_id!(event),
                     location: event.position().into(),
                 },
                 WLR_TABLET_TOOL_PROXIMITY_OUT => Event::TabletProximityOut {
+                    tool: tablet_tool_id!(event),
+                    location: event.position().into(),
+                },
+            },
+            RawEvent::TabletTip(event) => match event.state() {
+                WLR_TABLET_TOOL_TIP_DOWN => Event::TabletTipDown {
+                    tool: tablet_tool_id!(event),
                     location: event.position().into(),
                 },
+                WLR_TABLET_TOOL_TIP_UP => Event::TabletTipUp {
+                    tool: tablet_tool_id!(event),
+                    location: event.position().into(),
+                },
+            },
+            RawEvent::TabletButton(event) => match event.state() {
+                WLR_BUTTON_PRESSED => Event::TabletButtonDown {
+                    tool: tablet_tool_id!(event),
+                    button: event.button(),
+                },
+                WLR_BUTTON_RELEASED => Event::TabletButtonUp {
+                    tool: tablet_tool_id!(event),
+                    button: event.button(),
+                },
             },
             RawEvent::TabletAxis(event) => Event::TabletAxis {
+                tool: tablet_tool_id!(event),
                 location: event.position().into(),
-                pressure: event.pressure(),
-                distance: event.distance(),
-                tilt: event.tilt(),
-                slider: event.slider(),
-                wheel_delta: event.wheel_delta(),
+                axes: ToolAxes {
+                    updated: unsafe { (*event.as_ptr()).updated_axes },
+                    pressure: event.pressure(),
+                    distance: event.distance(),
+                    tilt: event.tilt(),
+                    slider: event.slider(),
+                    wheel_delta: event.wheel_delta(),
+                },
             },
             RawEvent::Key(event) => match event.key_state() {
                 WLR_KEY_PRESSED => Event::KeyDown {
@@ -186,7 +277,6 @@ impl<'a> From<RawEvent<'a>> for Event {
                     code: event.keycode(),
                 }
             }
-            _ => unimplemented!(),
         }
     }
 }
@@ -7,6 +7,8 @@ use wlroots::tablet_tool_events::{
     AxisEvent as TabletAxisEvent, ButtonEvent as TabletButtonEvent, ProximityEvent, TipEvent,
 };
 use wlroots::touch_events::*;
+use wlroots::utils::current_time;
+use wlroots::wlroots_sys::wlr_input_device;
 use wlroots::*;
 
 #[derive(Debug)]
@@ -27,7 +29,7 @@ impl InputManagerHandler for InputManager {
     fn keyboard_added(
         &mut self,
         compositor_handle: CompositorHandle,
-        _: KeyboardHandle,
+        keyboard: KeyboardHandle,
     ) -> Option<Box<KeyboardHandler>> {
         info!("Keyboard added!");
 
@@ -35,6 +37,7 @@ impl InputManagerHandler for InputManager {
             use compositor_handle as compositor;
             let server: &mut Server = compositor.data.downcast_mut().unwrap();
             server.keyboard_added();
+            server.set_keyboard(keyboard);
         }
 
         Some(Box::new(SfKeyboardHandler {
@@ -77,22 +80,48 @@ impl InputManagerHandler for InputManager {
         Some(Box::new(SfTouchHandler))
     }
 
+    #[wlroots_dehandle(compositor, tool)]
     fn tablet_tool_added(
         &mut self,
-        _: CompositorHandle,
-        _: TabletToolHandle,
+        compositor_handle: CompositorHandle,
+        tool_handle: TabletToolHandle,
     ) -> Option<Box<TabletToolHandler>> {
         info!("Tablet tool added!");
-        Some(Box::new(SfTabletToolHandler))
+
+        let device = {
+            use tool_handle as tool;
+            tool.input_device().as_ptr()
+        };
+
+        {
+            use compositor_handle as compositor;
+            let server: &mut Server = compositor.data.downcast_mut().unwrap();
+            server.tablet_added(device);
+        }
+
+        Some(Box::new(SfTabletToolHandler { device }))
     }
 
+    #[wlroots_dehandle(compositor, pad)]
     fn tablet_pad_added(
         &mut self,
-        _: CompositorHandle,
-        _: TabletPadHandle,
+        compositor_handle: CompositorHandle,
+        pad_handle: TabletPadHandle,
     ) -> Option<Box<TabletPadHandler>> {
         info!("Tablet pad added!");
-        None
+
+        let device = {
+            use pad_handle as pad;
+            pad.input_device().as_ptr()
+        };
+
+        {
+            use compositor_handle as compositor;
+            let server: &mut Server = compositor.data.downcast_mut().unwrap();
+            server.tablet_pad_added(device);
+        }
+
+        Some(Box::new(SfTabletPadHandler { device }))
     }
 }
 
@@ -151,20 +180,31 @@ pub struct SfKeyboardHandler {
 }
 
 impl KeyboardHandler for SfKeyboardHandler {
-    #[wlroots_dehandle(keyboard)]
-    fn modifiers(&mut self, _: CompositorHandle, keyboard_handle: KeyboardHandle) {
-        use keyboard_handle as keyboard;
-        let mods = keyboard.get_modifiers();
-        let opt_pressed = mods.contains(KeyboardModifier::WLR_MODIFIER_ALT);
-        let ctrl_pressed = mods.contains(KeyboardModifier::WLR_MODIFIER_CTRL);
-        let shift_pressed = mods.contains(KeyboardModifier::WLR_MODIFIER_SHIFT);
+    #[wlroots_dehandle(compositor, keyboard)]
+    fn modifiers(&mut self, compositor_handle: CompositorHandle, keyboard_handle: KeyboardHandle) {
+        {
+            use keyboard_handle as keyboard;
+            let mods = keyboard.get_modifiers();
+            let opt_pressed = mods.contains(KeyboardModifier::WLR_MODIFIER_ALT);
+            let ctrl_pressed = mods.contains(KeyboardModifier::WLR_MODIFIER_CTRL);
+            let shift_pressed = mods.contains(KeyboardModifier::WLR_MODIFIER_SHIFT);
+
+            self.ctrl_alt_pressed = opt_pressed && ctrl_pressed;
+            self.ctrl_shift_opt_pressed = opt_pressed && ctrl_pressed && shift_pressed;
+        }
 
-        self.ctrl_alt_pressed = opt_pressed && ctrl_pressed;
-        self.ctrl_shift_opt_pressed = opt_pressed && ctrl_pressed && shift_pressed;
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        server.notify_modifiers(keyboard_handle);
     }
 
     #[wlroots_dehandle(compositor)]
-    fn on_key(&mut self, compositor_handle: CompositorHandle, _: KeyboardHandle, event: &KeyEvent) {
+    fn on_key(
+        &mut self,
+        compositor_handle: CompositorHandle,
+        keyboard: KeyboardHandle,
+        event: &KeyEvent,
+    ) {
         use compositor_handle as compositor;
 
         // F-keys
@@ -182,8 +222,44 @@ impl KeyboardHandler for SfKeyboardHandler {
         }
 
         let server: &mut Server = compositor.data.downcast_mut().unwrap();
+
+        // R toggles the rotation lock
+        if self.ctrl_alt_pressed && event.keycode() == 19 {
+            if event.key_state() == wlr_key_state::WLR_KEY_PRESSED {
+                server.toggle_rotation_lock();
+            }
+            return;
+        }
+
+        // O moves the focused space to the next output
+        if self.ctrl_alt_pressed && event.keycode() == 24 {
+            if event.key_state() == wlr_key_state::WLR_KEY_PRESSED {
+                server.move_space_to_next_output();
+            }
+            return;
+        }
+
+        // M switches between mirroring and extending
+        if self.ctrl_alt_pressed && event.keycode() == 50 {
+            if event.key_state() == wlr_key_state::WLR_KEY_PRESSED {
+                server.toggle_output_mode();
+            }
+            return;
+        }
+
+        server.set_keyboard(keyboard);
         server.handle_event(RawEvent::Key(event));
     }
+
+    #[wlroots_dehandle(compositor)]
+    fn destroyed(&mut self, compositor_handle: CompositorHandle, keyboard: KeyboardHandle) {
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        server.keyboard_removed();
+        if server.keyboard.as_ref() == Some(&keyboard) {
+            server.keyboard = None;
+        }
+    }
 }
 
 pub struct SfTouchHandler;
@@ -229,26 +305,86 @@ impl TouchHandler for SfTouchHandler {
     }
 }
 
-pub struct SfTabletToolHandler;
+pub struct SfTabletToolHandler {
+    device: *mut wlr_input_device,
+}
 
 impl TabletToolHandler for SfTabletToolHandler {
-    fn on_axis(&mut self, _: CompositorHandle, _: TabletToolHandle, _: &TabletAxisEvent) {
-        println!("axis");
+    #[wlroots_dehandle(compositor)]
+    fn on_axis(
+        &mut self,
+        compositor_handle: CompositorHandle,
+        _: TabletToolHandle,
+        event: &TabletAxisEvent,
+    ) {
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        server.handle_event(RawEvent::TabletAxis(event));
     }
 
-    fn on_proximity(&mut self, _: CompositorHandle, _: TabletToolHandle, _: &ProximityEvent) {
-        println!("proximity");
+    #[wlroots_dehandle(compositor)]
+    fn on_proximity(
+        &mut self,
+        compositor_handle: CompositorHandle,
+        _: TabletToolHandle,
+        event: &ProximityEvent,
+    ) {
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        server.handle_event(RawEvent::TabletProximity(event));
     }
 
-    fn on_tip(&mut self, _: CompositorHandle, _: TabletToolHandle, _: &TipEvent) {
-        println!("tip");
+    #[wlroots_dehandle(compositor)]
+    fn on_tip(&mut self, compositor_handle: CompositorHandle, _: TabletToolHandle, event: &TipEvent) {
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        server.handle_event(RawEvent::TabletTip(event));
     }
 
-    fn on_button(&mut self, _: CompositorHandle, _: TabletToolHandle, _: &TabletButtonEvent) {
-        println!("tablet button");
+    #[wlroots_dehandle(compositor)]
+    fn on_button(
+        &mut self,
+        compositor_handle: CompositorHandle,
+        _: TabletToolHandle,
+        event: &TabletButtonEvent,
+    ) {
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        server.handle_event(RawEvent::TabletButton(event));
     }
 
-    fn destroyed(&mut self, _: CompositorHandle, _: TabletToolHandle) {
-        println!("tablet destroyed");
+    #[wlroots_dehandle(compositor)]
+    fn destroyed(&mut self, compositor_handle: CompositorHandle, _: TabletToolHandle) {
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        server.tablet_device_removed(self.device);
+    }
+}
+
+pub struct SfTabletPadHandler {
+    device: *mut wlr_input_device,
+}
+
+impl TabletPadHandler for SfTabletPadHandler {
+    #[wlroots_dehandle(compositor)]
+    fn on_button(
+        &mut self,
+        compositor_handle: CompositorHandle,
+        _: TabletPadHandle,
+        event: &tablet_pad_events::ButtonEvent,
+    ) {
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        if let Some(ref mut tablet) = server.tablet {
+            let pressed = event.state() == WLR_BUTTON_PRESSED;
+            tablet.pad_button(self.device, event.button(), current_time(), pressed);
+        }
+    }
+
+    #[wlroots_dehandle(compositor)]
+    fn destroyed(&mut self, compositor_handle: CompositorHandle, _: TabletPadHandle) {
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        server.tablet_device_removed(self.device);
     }
 }
@@ -0,0 +1,432 @@
+//! Support for typing with an on-screen keyboard.
+//!
+//! Clients tell sfc about text fields with text-input-v3, which is relayed to the input method
+//! with input-method-v2, and the input method types into them either with text or with key
+//! presses through virtual-keyboard-v1. Without an input method, the built-in keyboard is used
+//! instead. None of these are wrapped by wlroots-rs yet, so this uses wlroots-sys and listens to
+//! the signals directly.
+
+use crate::utils::{container_of, list_remove, listener, signal_add, with_server};
+use std::fmt;
+use std::os::raw::c_void;
+use std::ptr;
+use wlroots::wlroots_sys::wlr_text_input_v3_features::*;
+use wlroots::wlroots_sys::*;
+
+/// Relays text input between clients and the input method.
+///
+/// This must stay at the same address, so it’s always boxed.
+#[repr(C)]
+pub struct InputMethodRelay {
+    // the order of these fields is used to get back to the struct from the listeners
+    new_text_input: wl_listener,
+    new_input_method: wl_listener,
+    new_virtual_keyboard: wl_listener,
+    seat: *mut wlr_seat,
+    text_inputs: Vec<*mut TextInput>,
+    /// The connected input method, or null.
+    input_method: *mut InputMethod,
+    /// The surface with keyboard focus, or null.
+    focus: *mut wlr_surface,
+    /// True while a text field is typed into, by the input method or the built-in keyboard.
+    active: bool,
+}
+
+impl fmt::Debug for InputMethodRelay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "InputMethodRelay({} text inputs, active: {})",
+            self.text_inputs.len(),
+            self.active
+        )
+    }
+}
+
+impl InputMethodRelay {
+    /// Creates the text input, input method and virtual keyboard globals on the given display.
+    pub unsafe fn new(display: *mut wl_display, seat: *mut wlr_seat) -> Box<InputMethodRelay> {
+        let text_input_manager = wlr_text_input_manager_v3_create(display);
+        let input_method_manager = wlr_input_method_manager_v2_create(display);
+        let virtual_keyboard_manager = wlr_virtual_keyboard_manager_v1_create(display);
+
+        let mut this = Box::new(InputMethodRelay {
+            new_text_input: listener(new_text_input_notify),
+            new_input_method: listener(new_input_method_notify),
+            new_virtual_keyboard: listener(new_virtual_keyboard_notify),
+            seat,
+            text_inputs: Vec::new(),
+            input_method: ptr::null_mut(),
+            focus: ptr::null_mut(),
+            active: false,
+        });
+        signal_add(
+            &mut (*text_input_manager).events.text_input,
+            &mut this.new_text_input,
+        );
+        signal_add(
+            &mut (*input_method_manager).events.input_method,
+            &mut this.new_input_method,
+        );
+        signal_add(
+            &mut (*virtual_keyboard_manager).events.new_virtual_keyboard,
+            &mut this.new_virtual_keyboard,
+        );
+        this
+    }
+
+    /// Returns true while a text field is typed into, which is when an on-screen keyboard is
+    /// shown.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Returns true if an input method is connected, which replaces the built-in keyboard.
+    pub fn has_input_method(&self) -> bool {
+        !self.input_method.is_null()
+    }
+
+    /// Returns the client of the input method, whose layer surfaces are the on-screen keyboard.
+    pub fn input_method_client(&self) -> Option<*mut wl_client> {
+        if self.input_method.is_null() {
+            return None;
+        }
+        Some(unsafe { wl_resource_get_client((*(*self.input_method).input_method).resource) })
+    }
+
+    /// Moves text input focus to the surface with keyboard focus.
+    pub fn set_focus(&mut self, surface: *mut wlr_surface) {
+        if surface == self.focus {
+            return;
+        }
+        unsafe {
+            if self.active_text_input().is_some() {
+                self.deactivate();
+            }
+            for text_input in &self.text_inputs {
+                let text_input = (**text_input).text_input;
+                if !(*text_input).focused_surface.is_null() {
+                    wlr_text_input_v3_send_leave(text_input);
+                }
+            }
+            self.focus = surface;
+            for text_input in &self.text_inputs {
+                self.enter((**text_input).text_input);
+            }
+        }
+    }
+
+    /// Sends a text input enter if it belongs to the client with keyboard focus.
+    unsafe fn enter(&self, text_input: *mut wlr_text_input_v3) {
+        if self.focus.is_null() || (*text_input).seat != self.seat {
+            return;
+        }
+        let focus_client = wl_resource_get_client((*self.focus).resource);
+        if wl_resource_get_client((*text_input).resource) == focus_client {
+            wlr_text_input_v3_send_enter(text_input, self.focus);
+        }
+    }
+
+    /// Returns the enabled text input of the surface with keyboard focus.
+    unsafe fn active_text_input(&self) -> Option<*mut wlr_text_input_v3> {
+        self.text_inputs
+            .iter()
+            .map(|text_input| (**text_input).text_input)
+            .find(|text_input| {
+                !(**text_input).focused_surface.is_null() && (**text_input).current_enabled
+            })
+    }
+
+    /// Hides the on-screen keyboard until a text field is enabled again.
+    pub fn deactivate(&mut self) {
+        if !self.active {
+            return;
+        }
+        self.active = false;
+        if !self.input_method.is_null() {
+            unsafe {
+                let input_method = (*self.input_method).input_method;
+                wlr_input_method_v2_send_deactivate(input_method);
+                wlr_input_method_v2_send_done(input_method);
+            }
+        }
+    }
+
+    /// Shows an on-screen keyboard for the active text input, and activates the input method if
+    /// one is connected.
+    unsafe fn activate(&mut self) {
+        if self.active_text_input().is_none() {
+            return;
+        }
+        self.active = true;
+        if self.input_method.is_null() {
+            // the built-in keyboard types with key presses and doesn’t need the state
+            return;
+        }
+        wlr_input_method_v2_send_activate((*self.input_method).input_method);
+        self.send_state();
+    }
+
+    /// Tells the input method about the text field that’s typed into.
+    unsafe fn send_state(&self) {
+        let text_input = match self.active_text_input() {
+            Some(text_input) => text_input,
+            None => return,
+        };
+        if !self.active || self.input_method.is_null() {
+            return;
+        }
+        let input_method = (*self.input_method).input_method;
+        let state = &(*text_input).current;
+
+        let features = (*text_input).active_features;
+        if features & WLR_TEXT_INPUT_V3_FEATURE_SURROUNDING_TEXT != 0 {
+            wlr_input_method_v2_send_surrounding_text(
+                input_method,
+                state.surrounding.text,
+                state.surrounding.cursor,
+                state.surrounding.anchor,
+            );
+        }
+        wlr_input_method_v2_send_text_change_cause(input_method, state.text_change_cause);
+        if features & WLR_TEXT_INPUT_V3_FEATURE_CONTENT_TYPE != 0 {
+            wlr_input_method_v2_send_content_type(
+                input_method,
+                state.content_type.hint,
+                state.content_type.purpose,
+            );
+        }
+        wlr_input_method_v2_send_done(input_method);
+    }
+}
+
+/// Shows or hides the on-screen keyboard, which changes the area of the space in front.
+fn keyboard_changed() {
+    with_server("text input", |server| server.configure_views());
+}
+
+/// Listeners for a single text input, freed when the text input is destroyed.
+#[repr(C)]
+struct TextInput {
+    // the order of these fields is used to get back to the struct from the listeners
+    enable: wl_listener,
+    commit: wl_listener,
+    disable: wl_listener,
+    destroy: wl_listener,
+    text_input: *mut wlr_text_input_v3,
+    relay: *mut InputMethodRelay,
+}
+
+unsafe extern "C" fn new_text_input_notify(relay_listener: *mut wl_listener, data: *mut c_void) {
+    // new_text_input is the first field
+    let relay = container_of::<InputMethodRelay>(relay_listener, 0);
+    let text_input = data as *mut wlr_text_input_v3;
+
+    let listeners = Box::into_raw(Box::new(TextInput {
+        enable: listener(text_input_enable_notify),
+        commit: listener(text_input_commit_notify),
+        disable: listener(text_input_disable_notify),
+        destroy: listener(text_input_destroy_notify),
+        text_input,
+        relay,
+    }));
+    let events = &mut (*text_input).events;
+    signal_add(&mut events.enable, &mut (*listeners).enable);
+    signal_add(&mut events.commit, &mut (*listeners).commit);
+    signal_add(&mut events.disable, &mut (*listeners).disable);
+    signal_add(&mut events.destroy, &mut (*listeners).destroy);
+
+    (*relay).text_inputs.push(listeners);
+    (*relay).enter(text_input);
+}
+
+unsafe extern "C" fn text_input_enable_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let relay = (*container_of::<TextInput>(listener, 0)).relay;
+    (*relay).activate();
+    keyboard_changed();
+}
+
+unsafe extern "C" fn text_input_commit_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let listeners = container_of::<TextInput>(listener, 1);
+    let relay = (*listeners).relay;
+    if (*relay).active_text_input() == Some((*listeners).text_input) {
+        (*relay).send_state();
+    }
+}
+
+unsafe extern "C" fn text_input_disable_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let relay = (*container_of::<TextInput>(listener, 2)).relay;
+    if (*relay).active_text_input().is_none() {
+        (*relay).deactivate();
+        keyboard_changed();
+    }
+}
+
+unsafe extern "C" fn text_input_destroy_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let listeners = container_of::<TextInput>(listener, 3);
+    let relay = (*listeners).relay;
+
+    list_remove(&mut (*listeners).enable.link);
+    list_remove(&mut (*listeners).commit.link);
+    list_remove(&mut (*listeners).disable.link);
+    list_remove(&mut (*listeners).destroy.link);
+    (*relay).text_inputs.retain(|x| *x != listeners);
+    drop(Box::from_raw(listeners));
+
+    if (*relay).active && (*relay).active_text_input().is_none() {
+        (*relay).deactivate();
+        keyboard_changed();
+    }
+}
+
+/// Listeners for the input method, freed when it’s destroyed.
+#[repr(C)]
+struct InputMethod {
+    // the order of these fields is used to get back to the struct from the listeners
+    commit: wl_listener,
+    destroy: wl_listener,
+    input_method: *mut wlr_input_method_v2,
+    relay: *mut InputMethodRelay,
+}
+
+unsafe extern "C" fn new_input_method_notify(relay_listener: *mut wl_listener, data: *mut c_void) {
+    // new_input_method directly follows new_text_input, which is the first field
+    let relay = container_of::<InputMethodRelay>(relay_listener, 1);
+    let input_method = data as *mut wlr_input_method_v2;
+
+    if !(*relay).input_method.is_null() {
+        info!("Another input method is already connected");
+        wlr_input_method_v2_send_unavailable(input_method);
+        return;
+    }
+
+    let listeners = Box::into_raw(Box::new(InputMethod {
+        commit: listener(input_method_commit_notify),
+        destroy: listener(input_method_destroy_notify),
+        input_method,
+        relay,
+    }));
+    signal_add(&mut (*input_method).events.commit, &mut (*listeners).commit);
+    signal_add(
+        &mut (*input_method).events.destroy,
+        &mut (*listeners).destroy,
+    );
+    (*relay).input_method = listeners;
+
+    // a text field may already be waiting for it
+    (*relay).activate();
+    keyboard_changed();
+}
+
+unsafe extern "C" fn input_method_commit_notify(listener: *mut wl_listener, _: *mut c_void) {
+    // commit is the first field
+    let listeners = container_of::<InputMethod>(listener, 0);
+    let relay = (*listeners).relay;
+    let text_input = match (*relay).active_text_input() {
+        Some(text_input) if (*relay).active => text_input,
+        _ => return,
+    };
+    let state = &(*(*listeners).input_method).current;
+
+    if !state.preedit.text.is_null() {
+        wlr_text_input_v3_send_preedit_string(
+            text_input,
+            state.preedit.text,
+            state.preedit.cursor_begin,
+            state.preedit.cursor_end,
+        );
+    }
+    if !state.commit_text.is_null() {
+        wlr_text_input_v3_send_commit_string(text_input, state.commit_text);
+    }
+    if state.delete.before_length != 0 || state.delete.after_length != 0 {
+        wlr_text_input_v3_send_delete_surrounding_text(
+            text_input,
+            state.delete.before_length,
+            state.delete.after_length,
+        );
+    }
+    wlr_text_input_v3_send_done(text_input);
+}
+
+unsafe extern "C" fn input_method_destroy_notify(listener: *mut wl_listener, _: *mut c_void) {
+    // destroy directly follows commit, which is the first field
+    let listeners = container_of::<InputMethod>(listener, 1);
+    let relay = (*listeners).relay;
+
+    list_remove(&mut (*listeners).commit.link);
+    list_remove(&mut (*listeners).destroy.link);
+    // the built-in keyboard takes over if a text field is still typed into
+    (*relay).input_method = ptr::null_mut();
+    drop(Box::from_raw(listeners));
+    keyboard_changed();
+}
+
+/// Listeners for a virtual keyboard, freed when it’s destroyed.
+#[repr(C)]
+struct VirtualKeyboard {
+    // the order of these fields is used to get back to the struct from the listeners
+    key: wl_listener,
+    modifiers: wl_listener,
+    destroy: wl_listener,
+    virtual_keyboard: *mut wlr_virtual_keyboard_v1,
+}
+
+unsafe extern "C" fn new_virtual_keyboard_notify(_: *mut wl_listener, data: *mut c_void) {
+    let virtual_keyboard = data as *mut wlr_virtual_keyboard_v1;
+    let keyboard = (*virtual_keyboard).input_device.__bindgen_anon_1.keyboard;
+
+    let listeners = Box::into_raw(Box::new(VirtualKeyboard {
+        key: listener(virtual_keyboard_key_notify),
+        modifiers: listener(virtual_keyboard_modifiers_notify),
+        destroy: listener(virtual_keyboard_destroy_notify),
+        virtual_keyboard,
+    }));
+    signal_add(&mut (*keyboard).events.key, &mut (*listeners).key);
+    signal_add(
+        &mut (*keyboard).events.modifiers,
+        &mut (*listeners).modifiers,
+    );
+    signal_add(
+        &mut (*virtual_keyboard).events.destroy,
+        &mut (*listeners).destroy,
+    );
+}
+
+/// Makes the virtual keyboard the seat’s active keyboard, so that its keymap is used.
+unsafe fn use_virtual_keyboard(virtual_keyboard: *mut wlr_virtual_keyboard_v1) {
+    let seat = (*virtual_keyboard).seat;
+    wlr_seat_set_keyboard(seat, &mut (*virtual_keyboard).input_device);
+
+    // the physical keyboard is made active again when it’s used next
+    with_server("virtual keyboard", |server| server.keyboard = None);
+}
+
+unsafe extern "C" fn virtual_keyboard_key_notify(listener: *mut wl_listener, data: *mut c_void) {
+    let virtual_keyboard = (*container_of::<VirtualKeyboard>(listener, 0)).virtual_keyboard;
+    let event = data as *mut wlr_event_keyboard_key;
+
+    use_virtual_keyboard(virtual_keyboard);
+    wlr_seat_keyboard_notify_key(
+        (*virtual_keyboard).seat,
+        (*event).time_msec,
+        (*event).keycode,
+        (*event).state as u32,
+    );
+}
+
+unsafe extern "C" fn virtual_keyboard_modifiers_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let virtual_keyboard = (*container_of::<VirtualKeyboard>(listener, 1)).virtual_keyboard;
+    let keyboard = (*virtual_keyboard).input_device.__bindgen_anon_1.keyboard;
+
+    use_virtual_keyboard(virtual_keyboard);
+    wlr_seat_keyboard_notify_modifiers((*virtual_keyboard).seat, &mut (*keyboard).modifiers);
+}
+
+unsafe extern "C" fn virtual_keyboard_destroy_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let listeners = container_of::<VirtualKeyboard>(listener, 2);
+    list_remove(&mut (*listeners).key.link);
+    list_remove(&mut (*listeners).modifiers.link);
+    list_remove(&mut (*listeners).destroy.link);
+    drop(Box::from_raw(listeners));
+}
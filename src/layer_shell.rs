@@ -0,0 +1,551 @@
+//! Support for the wlr-layer-shell protocol, which wallpapers, panels, notifications and lock
+//! screens use to place themselves around the views.
+//!
+//! wlroots-rs doesn’t wrap layer-shell yet, so this uses wlroots-sys and listens to the signals
+//! directly. Mapped layer surfaces are views in one floating space per layer, so they’re drawn
+//! and get input the same way as other views.
+
+use crate::event::Event;
+use crate::renderer::Renderer;
+use crate::server::Server;
+use crate::space::{Space, SpaceTransform};
+use crate::utils::{contains, container_of, list_remove, listener, signal_add, with_server};
+use crate::view::{View, ViewShell};
+use cairo::Rectangle;
+use cgmath::{Matrix4, Vector2};
+use std::fmt;
+use std::os::raw::c_void;
+use std::rc::Rc;
+use wlroots::wlroots_sys::zwlr_layer_shell_v1_layer::*;
+use wlroots::wlroots_sys::zwlr_layer_surface_v1_anchor::*;
+use wlroots::wlroots_sys::*;
+use wlroots::TouchId;
+
+/// The layers from bottom to top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    /// Below the views, usually a wallpaper.
+    Background,
+    /// Below the views, but above the background.
+    Bottom,
+    /// Above the views, hidden along with the status bar while a view is fullscreen.
+    Top,
+    /// Above everything, even the status bar.
+    Overlay,
+}
+
+impl Layer {
+    unsafe fn of(layer_surface: *mut wlr_layer_surface_v1) -> Layer {
+        match (*layer_surface).layer {
+            ZWLR_LAYER_SHELL_V1_LAYER_BACKGROUND => Layer::Background,
+            ZWLR_LAYER_SHELL_V1_LAYER_BOTTOM => Layer::Bottom,
+            ZWLR_LAYER_SHELL_V1_LAYER_TOP => Layer::Top,
+            _ => Layer::Overlay,
+        }
+    }
+}
+
+/// Where a layer surface wants to be, as last committed by the client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Placement {
+    anchor: u32,
+    exclusive_zone: i32,
+    /// Top, right, bottom and left margins.
+    margin: (u32, u32, u32, u32),
+    /// Zero to fill the bounds along that axis.
+    desired_size: (u32, u32),
+}
+
+impl Placement {
+    unsafe fn of(layer_surface: *mut wlr_layer_surface_v1) -> Placement {
+        Self::from_state(&(*layer_surface).current)
+    }
+
+    fn from_state(state: &wlr_layer_surface_v1_state) -> Placement {
+        Placement {
+            anchor: state.anchor,
+            exclusive_zone: state.exclusive_zone,
+            margin: (
+                state.margin.top,
+                state.margin.right,
+                state.margin.bottom,
+                state.margin.left,
+            ),
+            desired_size: (state.desired_width, state.desired_height),
+        }
+    }
+
+    fn anchored(&self, edge: u32) -> bool {
+        self.anchor & edge != 0
+    }
+
+    /// Returns the area of the surface within the given bounds.
+    fn place(&self, bounds: Rectangle) -> Rectangle {
+        let (top, right, bottom, left) = self.margin;
+        let (x, width) = place_axis(
+            bounds.x,
+            bounds.width,
+            self.desired_size.0 as f64,
+            (
+                self.anchored(ZWLR_LAYER_SURFACE_V1_ANCHOR_LEFT),
+                left as f64,
+            ),
+            (
+                self.anchored(ZWLR_LAYER_SURFACE_V1_ANCHOR_RIGHT),
+                right as f64,
+            ),
+        );
+        let (y, height) = place_axis(
+            bounds.y,
+            bounds.height,
+            self.desired_size.1 as f64,
+            (self.anchored(ZWLR_LAYER_SURFACE_V1_ANCHOR_TOP), top as f64),
+            (
+                self.anchored(ZWLR_LAYER_SURFACE_V1_ANCHOR_BOTTOM),
+                bottom as f64,
+            ),
+        );
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Removes the exclusive zone from the usable area.
+    ///
+    /// Only surfaces anchored to a single edge, or to an edge and the two adjacent ones, have
+    /// an exclusive zone.
+    fn reserve(&self, usable: &mut Rectangle) {
+        if self.exclusive_zone <= 0 {
+            return;
+        }
+        let (top, right, bottom, left) = self.margin;
+        let horizontal = ZWLR_LAYER_SURFACE_V1_ANCHOR_LEFT | ZWLR_LAYER_SURFACE_V1_ANCHOR_RIGHT;
+        let vertical = ZWLR_LAYER_SURFACE_V1_ANCHOR_TOP | ZWLR_LAYER_SURFACE_V1_ANCHOR_BOTTOM;
+        let zone = self.exclusive_zone as f64;
+
+        let is_edge =
+            |edge: u32, adjacent: u32| self.anchor == edge || self.anchor == edge | adjacent;
+        if is_edge(ZWLR_LAYER_SURFACE_V1_ANCHOR_TOP, horizontal) {
+            let zone = zone + top as f64;
+            usable.y += zone;
+            usable.height -= zone;
+        } else if is_edge(ZWLR_LAYER_SURFACE_V1_ANCHOR_BOTTOM, horizontal) {
+            usable.height -= zone + bottom as f64;
+        } else if is_edge(ZWLR_LAYER_SURFACE_V1_ANCHOR_LEFT, vertical) {
+            let zone = zone + left as f64;
+            usable.x += zone;
+            usable.width -= zone;
+        } else if is_edge(ZWLR_LAYER_SURFACE_V1_ANCHOR_RIGHT, vertical) {
+            usable.width -= zone + right as f64;
+        }
+        usable.width = usable.width.max(0.);
+        usable.height = usable.height.max(0.);
+    }
+}
+
+/// Places a surface along one axis and returns its start and length.
+///
+/// The anchors are whether the surface is anchored to either end of the bounds, with the margin
+/// to that end. A desired length of zero fills the bounds.
+fn place_axis(
+    start: f64,
+    length: f64,
+    desired: f64,
+    (anchored_start, margin_start): (bool, f64),
+    (anchored_end, margin_end): (bool, f64),
+) -> (f64, f64) {
+    if desired == 0. {
+        return (
+            start + margin_start,
+            (length - margin_start - margin_end).max(0.),
+        );
+    }
+    let position = match (anchored_start, anchored_end) {
+        (true, false) => start + margin_start,
+        (false, true) => start + length - desired - margin_end,
+        _ => start + (length - desired) / 2.,
+    };
+    (position, desired)
+}
+
+/// Returns the area a layer surface that isn’t mapped yet would get.
+///
+/// This reads the state that the client set but that wasn’t applied yet, since the surface
+/// is configured before its first commit is.
+pub unsafe fn initial_area(
+    layer_surface: *mut wlr_layer_surface_v1,
+    screen: Rectangle,
+    usable: Rectangle,
+) -> Rectangle {
+    let placement = Placement::from_state(&(*layer_surface).client_pending);
+    let bounds = if placement.exclusive_zone == -1 {
+        screen
+    } else {
+        usable
+    };
+    placement.place(bounds)
+}
+
+/// The view area and the content area of the renderer.
+type Areas = (Rectangle, Rectangle);
+
+/// The mapped layer surfaces.
+#[derive(Debug)]
+pub struct Layers {
+    /// One floating space per layer, from background to overlay.
+    spaces: Vec<Space>,
+    /// The layer each touch point went down on.
+    touches: Vec<(TouchId, Layer)>,
+    /// The layer the pointer is over, or was pressed on.
+    pointer_layer: Option<Layer>,
+    pointer_buttons: usize,
+    /// True while the top layer isn’t drawn, so that it doesn’t get input either.
+    top_hidden: bool,
+}
+
+impl Layers {
+    pub fn new() -> Layers {
+        Layers {
+            spaces: (0..4).map(|_| Space::new_floating()).collect(),
+            touches: Vec::new(),
+            pointer_layer: None,
+            pointer_buttons: 0,
+            top_hidden: false,
+        }
+    }
+
+    fn space(&self, layer: Layer) -> &Space {
+        &self.spaces[layer as usize]
+    }
+
+    fn space_mut(&mut self, layer: Layer) -> &mut Space {
+        &mut self.spaces[layer as usize]
+    }
+
+    pub fn add_view(&mut self, view: Rc<View>) {
+        let layer = match view.shell {
+            ViewShell::Layer(layer_surface) => unsafe { Layer::of(layer_surface) },
+            _ => return,
+        };
+        self.space_mut(layer).add_view(view);
+    }
+
+    pub fn remove_view_for_surface(&mut self, surface: &ViewShell) -> bool {
+        self.spaces
+            .iter_mut()
+            .any(|space| space.remove_view_for_surface(surface))
+    }
+
+    /// Returns the views of all layers from bottom to top.
+    pub fn views(&self) -> impl Iterator<Item = &Rc<View>> {
+        self.spaces.iter().flat_map(|space| space.views())
+    }
+
+    /// Closes the surfaces that are placed on an output that is going away.
+    pub unsafe fn close_on_output(&self, output: *mut wlr_output) {
+        for view in self.spaces.iter().flat_map(|space| space.views()) {
+            if let ViewShell::Layer(layer_surface) = view.shell {
+                if (*layer_surface).output == output {
+                    view.shell.close();
+                }
+            }
+        }
+    }
+
+    /// Returns the topmost surface in the top or overlay layer that wants keyboard input.
+    pub fn focus_target(&self) -> Option<Rc<View>> {
+        self.space(Layer::Overlay)
+            .top_view()
+            .or_else(|| self.space(Layer::Top).top_view())
+    }
+
+    /// Places all surfaces on the screen and returns the area that isn’t reserved by any of
+    /// them, along with the area of the on-screen keyboard if it’s shown.
+    ///
+    /// The on-screen keyboard is made of the surfaces of the input method client. It doesn’t
+    /// reserve any area, since it only shrinks the space in front.
+    pub fn arrange(
+        &self,
+        screen: Rectangle,
+        keyboard_client: Option<*mut wl_client>,
+    ) -> (Rectangle, Option<Rectangle>) {
+        let mut usable = screen;
+        let mut keyboard_area = None;
+
+        // surfaces with an exclusive zone go first so that the others can avoid them
+        for &exclusive in &[true, false] {
+            for space in self.spaces.iter().rev() {
+                for view in space.views() {
+                    let layer_surface = match view.shell {
+                        ViewShell::Layer(layer_surface) => layer_surface,
+                        _ => continue,
+                    };
+                    let placement = unsafe { Placement::of(layer_surface) };
+                    if (placement.exclusive_zone > 0) != exclusive {
+                        continue;
+                    }
+
+                    let bounds = if placement.exclusive_zone == -1 {
+                        screen
+                    } else {
+                        usable
+                    };
+                    let area = placement.place(bounds);
+                    view.configure(area);
+
+                    let client = unsafe { wl_resource_get_client((*layer_surface).resource) };
+                    if Some(client) == keyboard_client {
+                        keyboard_area = Some(area);
+                    } else {
+                        placement.reserve(&mut usable);
+                    }
+                }
+            }
+        }
+
+        (usable, keyboard_area)
+    }
+
+    /// Renders the surfaces of a layer.
+    ///
+    /// The top layer is hidden along with the status bar.
+    pub fn render(
+        &mut self,
+        layer: Layer,
+        matrix: Matrix4<f32>,
+        wlr_renderer: &mut wlroots::Renderer,
+        renderer: &Renderer,
+        chrome_hidden: bool,
+    ) {
+        if layer == Layer::Top {
+            self.top_hidden = chrome_hidden;
+            if chrome_hidden {
+                return;
+            }
+        }
+        self.space_mut(layer)
+            .render(matrix, SpaceTransform::identity(), wlr_renderer, renderer);
+    }
+
+    /// Returns the topmost layer with a surface at the given point.
+    ///
+    /// The background and bottom layers only get input in the content area where there are no
+    /// views, so that they don’t take it from the views, status bar or home bar.
+    fn layer_at(&self, location: Vector2<f64>, (view_area, content_area): Areas) -> Option<Layer> {
+        let mut layers = vec![Layer::Overlay];
+        if !self.top_hidden {
+            layers.push(Layer::Top);
+        }
+        if contains(content_area, location) && !contains(view_area, location) {
+            layers.push(Layer::Bottom);
+            layers.push(Layer::Background);
+        }
+
+        layers
+            .into_iter()
+            .find(|layer| self.space(*layer).contains_point(location))
+    }
+
+    /// Returns true if the pointer is over a layer surface, or was pressed on one.
+    pub fn pointer_inside(&self) -> bool {
+        self.pointer_layer.is_some()
+    }
+
+    /// Handles an event and returns true if it was meant for a layer surface.
+    ///
+    /// Touches and pointer presses go to the surface they started on until they end.
+    pub fn handle_event(&mut self, event: Event, server: &mut Server, areas: Areas) -> bool {
+        match event {
+            Event::TouchDown { id, location } => match self.layer_at(location, areas) {
+                Some(layer) => {
+                    self.touches.push((id, layer));
+                    self.space_mut(layer).handle_event(event, server);
+                    true
+                }
+                None => false,
+            },
+            Event::TouchMotion { id, .. } | Event::TouchUp { id } | Event::TouchCancel { id } => {
+                let pos = match self
+                    .touches
+                    .iter()
+                    .position(|(touch_id, _)| *touch_id == id)
+                {
+                    Some(pos) => pos,
+                    None => return false,
+                };
+                let layer = match event {
+                    Event::TouchMotion { .. } => self.touches[pos].1,
+                    _ => self.touches.remove(pos).1,
+                };
+                self.space_mut(layer).handle_event(event, server);
+                true
+            }
+            Event::PointerMotion { .. }
+            | Event::PointerDown { .. }
+            | Event::PointerUp { .. }
+            | Event::PointerAxis { .. } => self.handle_pointer_event(event, server, areas),
+            _ => false,
+        }
+    }
+
+    fn handle_pointer_event(&mut self, event: Event, server: &mut Server, areas: Areas) -> bool {
+        let layer = if self.pointer_buttons > 0 {
+            self.pointer_layer
+        } else {
+            self.layer_at(server.cursor_location(), areas)
+        };
+        if layer != self.pointer_layer {
+            // the other layer will send enter events once the pointer is back
+            if let Some(previous) = self.pointer_layer {
+                self.space_mut(previous).reset_pointer_focus();
+            }
+            self.pointer_layer = layer;
+        }
+        let layer = match layer {
+            Some(layer) => layer,
+            None => return false,
+        };
+
+        match event {
+            Event::PointerDown { .. } => self.pointer_buttons += 1,
+            Event::PointerUp { .. } => {
+                self.pointer_buttons = self.pointer_buttons.saturating_sub(1)
+            }
+            _ => (),
+        }
+        self.space_mut(layer).handle_event(event, server);
+        true
+    }
+}
+
+/// The layer shell global.
+///
+/// This must stay at the same address, so it’s always boxed.
+#[repr(C)]
+pub struct LayerShell {
+    // must be the first field, see new_surface_notify
+    new_surface: wl_listener,
+    shell: *mut wlr_layer_shell_v1,
+}
+
+impl fmt::Debug for LayerShell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LayerShell({:?})", self.shell)
+    }
+}
+
+impl LayerShell {
+    /// Creates the layer shell global on the given display.
+    pub unsafe fn new(display: *mut wl_display) -> Box<LayerShell> {
+        let shell = wlr_layer_shell_v1_create(display);
+        let mut this = Box::new(LayerShell {
+            new_surface: listener(new_surface_notify),
+            shell,
+        });
+        signal_add(&mut (*shell).events.new_surface, &mut this.new_surface);
+        this
+    }
+}
+
+/// Listeners for a single layer surface, freed when the surface is destroyed.
+#[repr(C)]
+struct SurfaceListeners {
+    // the order of these fields is used to get back to the struct from the listeners
+    map: wl_listener,
+    unmap: wl_listener,
+    destroy: wl_listener,
+    commit: wl_listener,
+    layer_surface: *mut wlr_layer_surface_v1,
+    /// The placement the surface was last arranged with.
+    placement: Option<Placement>,
+}
+
+unsafe extern "C" fn new_surface_notify(_: *mut wl_listener, data: *mut c_void) {
+    let layer_surface = data as *mut wlr_layer_surface_v1;
+    let listeners = Box::into_raw(Box::new(SurfaceListeners {
+        map: listener(map_notify),
+        unmap: listener(unmap_notify),
+        destroy: listener(destroy_notify),
+        commit: listener(commit_notify),
+        layer_surface,
+        placement: None,
+    }));
+    signal_add(&mut (*layer_surface).events.map, &mut (*listeners).map);
+    signal_add(&mut (*layer_surface).events.unmap, &mut (*listeners).unmap);
+    signal_add(
+        &mut (*layer_surface).events.destroy,
+        &mut (*listeners).destroy,
+    );
+    signal_add(
+        &mut (*(*layer_surface).surface).events.commit,
+        &mut (*listeners).commit,
+    );
+
+    // the surface can only be mapped after it was told its size
+    with_server("new_surface", |server| {
+        let output = server
+            .outputs
+            .primary()
+            .and_then(|output| output.handle.run(|output| output.as_ptr()).ok());
+        match (output, server.renderer()) {
+            (Some(output), Some(renderer)) => {
+                if (*layer_surface).output.is_null() {
+                    (*layer_surface).output = output;
+                }
+                let area = initial_area(
+                    layer_surface,
+                    renderer.screen_area(),
+                    renderer.usable_area(),
+                );
+                wlr_layer_surface_v1_configure(
+                    layer_surface,
+                    area.width as u32,
+                    area.height as u32,
+                );
+            }
+            _ => wlr_layer_surface_v1_close(layer_surface),
+        }
+    });
+}
+
+unsafe extern "C" fn map_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let listeners = container_of::<SurfaceListeners>(listener, 0);
+    let layer_surface = (*listeners).layer_surface;
+    (*listeners).placement = Some(Placement::of(layer_surface));
+
+    let view = Rc::new(View::new(ViewShell::Layer(layer_surface)));
+    with_server("map", |server| server.add_layer_view(view));
+}
+
+unsafe extern "C" fn unmap_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let layer_surface = (*container_of::<SurfaceListeners>(listener, 1)).layer_surface;
+    let shell = ViewShell::Layer(layer_surface);
+    with_server("unmap", |server| server.remove_view_for_surface(&shell));
+}
+
+unsafe extern "C" fn destroy_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let listeners = container_of::<SurfaceListeners>(listener, 2);
+    list_remove(&mut (*listeners).map.link);
+    list_remove(&mut (*listeners).unmap.link);
+    list_remove(&mut (*listeners).destroy.link);
+    list_remove(&mut (*listeners).commit.link);
+    drop(Box::from_raw(listeners));
+}
+
+unsafe extern "C" fn commit_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let listeners = container_of::<SurfaceListeners>(listener, 3);
+    let layer_surface = (*listeners).layer_surface;
+    if !(*layer_surface).mapped {
+        return;
+    }
+
+    // rearranging configures every surface, so it’s only done when something moved
+    let placement = Some(Placement::of(layer_surface));
+    if placement != (*listeners).placement {
+        (*listeners).placement = placement;
+        with_server("commit", |server| server.arrange_layers());
+    }
+}
@@ -4,27 +4,43 @@ extern crate log;
 use wlroots::extensions::server_decoration::ServerDecorationMode;
 use wlroots::{CompositorBuilder, Seat};
 
+pub mod config;
 pub mod dock;
+pub mod damage;
 pub mod event;
 pub mod input_manager;
+pub mod input_method;
+pub mod layer_shell;
+pub mod osk;
 pub mod output_handler;
+pub mod output_layout;
 pub mod output_manager;
+pub mod overview;
 pub mod renderer;
+pub mod rotation;
 pub mod seat;
 pub mod seat_manager;
 pub mod server;
 pub mod shell;
+pub mod slide_over;
 pub mod space;
+pub mod split;
 pub mod spring;
 pub mod status;
+pub mod switcher;
+pub mod tablet;
+pub mod unmanaged;
 pub mod utils;
 pub mod view;
+pub mod xdg_decoration;
+pub mod xwayland;
 
+use self::config::Config;
 use self::input_manager::InputManager;
 use self::output_manager::OutputManager;
 use self::seat_manager::SeatManager;
 use self::server::Server;
-use self::shell::XdgV6ShellManager;
+use self::shell::{XdgShellManager, XdgV6ShellManager};
 
 fn main() {
     fern::Dispatch::new()
@@ -43,14 +59,22 @@ fn main() {
         .expect("Failed to init logger");
     info!("Starting...");
 
+    let mut server = Server::new();
+    server.config = Config::load();
+    let output_mode = server.config.outputs;
+    server.set_output_mode(output_mode);
+    let primary_output = server.config.primary_output.clone();
+    server.outputs.set_preferred_primary(primary_output);
+
     let mut compositor = CompositorBuilder::new()
         .gles2(true)
         .data_device(true)
         .server_decoration_manager(true)
         .input_manager(Box::new(InputManager::new()))
         .output_manager(Box::new(OutputManager::new()))
+        .xdg_shell_manager(Box::new(XdgShellManager::new()))
         .xdg_shell_v6_manager(Box::new(XdgV6ShellManager::new()))
-        .build_auto(Server::new());
+        .build_auto(server);
 
     compositor
         .server_decoration_manager
@@ -63,9 +87,16 @@ fn main() {
         "seat0".into(),
         Box::new(SeatManager::new()),
     );
+    let wlr_compositor = compositor.compositor;
     {
         let server: &mut Server = compositor.data.downcast_mut().unwrap();
         server.seat = seat;
+        server.init_xdg_decoration();
+        server.init_layer_shell();
+        server.init_input_method();
+        server.init_rotation();
+        server.init_damage_tracking(wlr_compositor);
+        server.init_xwayland(wlr_compositor);
     }
     compositor.run();
 }
@@ -0,0 +1,620 @@
+//! The built-in on-screen keyboard, which is shown when a text field is focused and no input
+//! method is connected.
+//!
+//! Keys are typed through a keyboard device that sfc creates itself, so clients get a keymap
+//! even when no hardware keyboard is attached.
+
+use crate::damage;
+use crate::event::Event;
+use crate::renderer::{CairoTex, HOME_BAR_REGION_HEIGHT};
+use crate::spring::{RealTimeSpring, Spring};
+use crate::utils::{contains, rounded_rect};
+use cairo::Rectangle;
+use cgmath::{Matrix4, Vector2};
+use std::ffi::CString;
+use std::fmt;
+use std::mem;
+use std::ptr;
+use std::time::{Duration, Instant};
+use wlroots::utils::current_time;
+use wlroots::wlroots_sys::wlr_input_device_type::*;
+use wlroots::wlroots_sys::wlr_key_state::*;
+use wlroots::wlroots_sys::xkb_context_flags::*;
+use wlroots::wlroots_sys::xkb_keymap_compile_flags::*;
+use wlroots::wlroots_sys::*;
+use wlroots::TouchId;
+
+/// Height of the keyboard panel.
+const KEYBOARD_HEIGHT: f64 = 216.;
+
+/// Space around each key.
+const KEY_GAP: f64 = 3.;
+
+/// How long a key needs to be held before it starts repeating.
+const REPEAT_DELAY: Duration = Duration::from_millis(500);
+
+/// Time between repeats while a key is held.
+const REPEAT_INTERVAL: Duration = Duration::from_millis(60);
+
+/// Width of a row in key units; a normal key is one unit wide.
+const ROW_UNITS: f64 = 10.;
+
+/// Evdev key codes.
+const KEY_BACKSPACE: u32 = 14;
+const KEY_ENTER: u32 = 28;
+const KEY_LEFTSHIFT: u32 = 42;
+const KEY_SPACE: u32 = 57;
+
+/// A key press to type into the focused text field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPress {
+    /// The evdev key code.
+    pub code: u32,
+    /// True if shift needs to be held.
+    pub shift: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyLayer {
+    Letters,
+    Symbols,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    /// A key that types a character, holding shift if `shift` is true.
+    Char {
+        label: &'static str,
+        code: u32,
+        shift: bool,
+    },
+    Shift,
+    Backspace,
+    Enter,
+    Space,
+    /// Switches between the letter layer and the number and symbol layer.
+    SwitchLayer,
+}
+
+impl Key {
+    fn repeats(self) -> bool {
+        match self {
+            Key::Char { .. } | Key::Backspace | Key::Space => true,
+            _ => false,
+        }
+    }
+}
+
+fn char_key(label: &'static str, code: u32) -> Key {
+    Key::Char {
+        label,
+        code,
+        shift: false,
+    }
+}
+
+fn shifted_key(label: &'static str, code: u32) -> Key {
+    Key::Char {
+        label,
+        code,
+        shift: true,
+    }
+}
+
+/// Returns the rows of a layer as keys and their widths in key units.
+fn layout(layer: KeyLayer) -> Vec<Vec<(Key, f64)>> {
+    let chars = |keys: &[(&'static str, u32)]| -> Vec<(Key, f64)> {
+        keys.iter()
+            .map(|&(label, code)| (char_key(label, code), 1.))
+            .collect()
+    };
+
+    match layer {
+        KeyLayer::Letters => {
+            let top = chars(&[
+                ("q", 16),
+                ("w", 17),
+                ("e", 18),
+                ("r", 19),
+                ("t", 20),
+                ("y", 21),
+                ("u", 22),
+                ("i", 23),
+                ("o", 24),
+                ("p", 25),
+            ]);
+            let middle = chars(&[
+                ("a", 30),
+                ("s", 31),
+                ("d", 32),
+                ("f", 33),
+                ("g", 34),
+                ("h", 35),
+                ("j", 36),
+                ("k", 37),
+                ("l", 38),
+            ]);
+            let mut bottom = vec![(Key::Shift, 1.5)];
+            bottom.extend(chars(&[
+                ("z", 44),
+                ("x", 45),
+                ("c", 46),
+                ("v", 47),
+                ("b", 48),
+                ("n", 49),
+                ("m", 50),
+            ]));
+            bottom.push((Key::Backspace, 1.5));
+            let space_row = vec![
+                (Key::SwitchLayer, 2.),
+                (char_key(",", 51), 1.),
+                (Key::Space, 4.),
+                (char_key(".", 52), 1.),
+                (Key::Enter, 2.),
+            ];
+            vec![top, middle, bottom, space_row]
+        }
+        KeyLayer::Symbols => {
+            let top = chars(&[
+                ("1", 2),
+                ("2", 3),
+                ("3", 4),
+                ("4", 5),
+                ("5", 6),
+                ("6", 7),
+                ("7", 8),
+                ("8", 9),
+                ("9", 10),
+                ("0", 11),
+            ]);
+            let middle = vec![
+                (char_key("-", 12), 1.),
+                (char_key("/", 53), 1.),
+                (shifted_key(":", 39), 1.),
+                (char_key(";", 39), 1.),
+                (shifted_key("(", 10), 1.),
+                (shifted_key(")", 11), 1.),
+                (shifted_key("$", 5), 1.),
+                (shifted_key("&", 8), 1.),
+                (shifted_key("@", 3), 1.),
+                (shifted_key("\"", 40), 1.),
+            ];
+            let bottom = vec![
+                (char_key(".", 52), 1.4),
+                (char_key(",", 51), 1.4),
+                (shifted_key("?", 53), 1.4),
+                (shifted_key("!", 2), 1.4),
+                (char_key("'", 40), 1.4),
+                (Key::Backspace, 1.5),
+            ];
+            let space_row = vec![
+                (Key::SwitchLayer, 2.),
+                (char_key("=", 13), 1.),
+                (Key::Space, 4.),
+                (shifted_key("+", 13), 1.),
+                (Key::Enter, 2.),
+            ];
+            vec![top, middle, bottom, space_row]
+        }
+    }
+}
+
+/// A key that a touch point is holding down.
+#[derive(Debug)]
+struct HeldKey {
+    id: TouchId,
+    index: usize,
+    since: Instant,
+    /// When the key was last repeated, if it has been.
+    repeated: Option<Instant>,
+}
+
+/// The keyboard panel, which slides in above the home bar.
+#[derive(Debug)]
+pub struct OnScreenKeyboard {
+    inner: CairoTex,
+    /// Where the panel is while it’s shown.
+    area: Rectangle,
+    /// The keys and their bounds relative to the panel.
+    keys: Vec<(Key, Rectangle)>,
+    layer: KeyLayer,
+    shifted: bool,
+    held: Option<HeldKey>,
+    shown: bool,
+    /// How far the panel is pushed down off the screen.
+    offset: RealTimeSpring,
+    needs_redraw: bool,
+}
+
+impl OnScreenKeyboard {
+    /// Creates a hidden keyboard panel for a screen of the given size.
+    pub fn new(screen_width: f64, screen_height: f64, resolution: f64) -> OnScreenKeyboard {
+        let area = Rectangle {
+            x: 0.,
+            y: screen_height - HOME_BAR_REGION_HEIGHT - KEYBOARD_HEIGHT,
+            width: screen_width,
+            height: KEYBOARD_HEIGHT,
+        };
+        let mut offset = RealTimeSpring::new(Spring::new(1., 0.3));
+        offset.spring.value = Self::hidden_offset();
+        offset.spring.target = Self::hidden_offset();
+
+        let mut keyboard = OnScreenKeyboard {
+            inner: CairoTex::new(area.width, area.height, resolution),
+            area,
+            keys: Vec::new(),
+            layer: KeyLayer::Letters,
+            shifted: false,
+            held: None,
+            shown: false,
+            offset,
+            needs_redraw: true,
+        };
+        keyboard.lay_out();
+        keyboard
+    }
+
+    /// How far the panel needs to be pushed down to be below the home bar and off screen.
+    fn hidden_offset() -> f64 {
+        KEYBOARD_HEIGHT + HOME_BAR_REGION_HEIGHT
+    }
+
+    /// Returns where the panel is while it’s shown.
+    pub fn area(&self) -> Rectangle {
+        self.area
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.shown
+    }
+
+    /// Slides the panel in or out.
+    pub fn set_shown(&mut self, shown: bool) {
+        if shown == self.shown {
+            return;
+        }
+        self.shown = shown;
+        self.offset.spring.target = if shown { 0. } else { Self::hidden_offset() };
+        if !shown {
+            self.held = None;
+            self.shifted = false;
+            self.set_layer(KeyLayer::Letters);
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Returns true if the point is on the panel while it’s shown.
+    pub fn contains(&self, point: Vector2<f64>) -> bool {
+        self.shown && contains(self.area, point)
+    }
+
+    fn set_layer(&mut self, layer: KeyLayer) {
+        if layer != self.layer {
+            self.layer = layer;
+            self.lay_out();
+        }
+    }
+
+    /// Places the keys of the current layer, with each row centered.
+    fn lay_out(&mut self) {
+        let rows = layout(self.layer);
+        let unit = self.area.width / ROW_UNITS;
+        let row_height = self.area.height / rows.len() as f64;
+
+        self.keys.clear();
+        for (i, row) in rows.into_iter().enumerate() {
+            let row_width: f64 = row.iter().map(|(_, width)| width * unit).sum();
+            let mut x = (self.area.width - row_width) / 2.;
+            for (key, width) in row {
+                let bounds = Rectangle {
+                    x,
+                    y: i as f64 * row_height,
+                    width: width * unit,
+                    height: row_height,
+                };
+                self.keys.push((key, bounds));
+                x += width * unit;
+            }
+        }
+        self.needs_redraw = true;
+    }
+
+    fn key_at(&self, point: Vector2<f64>) -> Option<usize> {
+        let local = Vector2::new(point.x - self.area.x, point.y - self.area.y);
+        self.keys
+            .iter()
+            .position(|(_, bounds)| contains(*bounds, local))
+    }
+
+    /// Handles a touch event for a touch point on the panel and returns the key to type, if any.
+    ///
+    /// Keys are typed when they’re released, so that the finger can slide to a different key.
+    pub fn handle_event(&mut self, event: Event) -> Option<KeyPress> {
+        match event {
+            Event::TouchDown { id, location } => {
+                if self.held.is_some() {
+                    // a second finger types the key that the first one is holding
+                    let key = self.release();
+                    self.press(id, location);
+                    return key;
+                }
+                self.press(id, location);
+                None
+            }
+            Event::TouchMotion { id, location } => {
+                let index = self.key_at(location);
+                if let (Some(held), Some(index)) = (self.held.as_mut(), index) {
+                    if held.id == id && held.index != index {
+                        held.index = index;
+                        held.since = Instant::now();
+                        held.repeated = None;
+                        self.needs_redraw = true;
+                    }
+                }
+                None
+            }
+            Event::TouchUp { id } if self.is_holding(id) => self.release(),
+            Event::TouchCancel { id } if self.is_holding(id) => {
+                self.held = None;
+                self.needs_redraw = true;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn is_holding(&self, id: TouchId) -> bool {
+        self.held.as_ref().map_or(false, |held| held.id == id)
+    }
+
+    fn press(&mut self, id: TouchId, location: Vector2<f64>) {
+        if let Some(index) = self.key_at(location) {
+            self.held = Some(HeldKey {
+                id,
+                index,
+                since: Instant::now(),
+                repeated: None,
+            });
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Releases the held key and types it unless it was already typed by repeating.
+    fn release(&mut self) -> Option<KeyPress> {
+        let held = self.held.take()?;
+        self.needs_redraw = true;
+        if held.repeated.is_some() {
+            return None;
+        }
+        let key = self.keys[held.index].0;
+        self.activate(key)
+    }
+
+    fn activate(&mut self, key: Key) -> Option<KeyPress> {
+        let code = match key {
+            Key::Char { code, shift, .. } => {
+                let shift = shift || self.shifted;
+                // shift only applies to the next letter
+                if self.shifted {
+                    self.shifted = false;
+                    self.needs_redraw = true;
+                }
+                return Some(KeyPress { code, shift });
+            }
+            Key::Shift => {
+                self.shifted = !self.shifted;
+                self.needs_redraw = true;
+                return None;
+            }
+            Key::SwitchLayer => {
+                self.shifted = false;
+                self.set_layer(match self.layer {
+                    KeyLayer::Letters => KeyLayer::Symbols,
+                    KeyLayer::Symbols => KeyLayer::Letters,
+                });
+                return None;
+            }
+            Key::Backspace => KEY_BACKSPACE,
+            Key::Enter => KEY_ENTER,
+            Key::Space => KEY_SPACE,
+        };
+        Some(KeyPress { code, shift: false })
+    }
+
+    /// Returns the held key if it’s due to be repeated.
+    pub fn update(&mut self) -> Option<KeyPress> {
+        let (key, bounds, due) = {
+            let held = self.held.as_ref()?;
+            let (key, bounds) = self.keys[held.index];
+            let due = match held.repeated {
+                Some(repeated) => repeated.elapsed() >= REPEAT_INTERVAL,
+                None => held.since.elapsed() >= REPEAT_DELAY,
+            };
+            (key, bounds, due)
+        };
+        if !key.repeats() {
+            return None;
+        }
+        // frames keep coming until the key is released, so that it repeats in time, but only the
+        // key is drawn again
+        damage::request_frame_in(Rectangle {
+            x: self.area.x + bounds.x,
+            y: self.area.y + self.offset.spring.value + bounds.y,
+            ..bounds
+        });
+        if !due {
+            return None;
+        }
+        self.held.as_mut().unwrap().repeated = Some(Instant::now());
+        self.activate(key)
+    }
+
+    fn key_label(&self, key: Key) -> String {
+        match key {
+            Key::Char { label, .. } if self.shifted => label.to_uppercase(),
+            Key::Char { label, .. } => label.into(),
+            Key::Shift => "⇧".into(),
+            Key::Backspace => "⌫".into(),
+            Key::Enter => "⏎".into(),
+            Key::Space => String::new(),
+            Key::SwitchLayer => match self.layer {
+                KeyLayer::Letters => "123".into(),
+                KeyLayer::Symbols => "ABC".into(),
+            },
+        }
+    }
+
+    fn draw(&self) {
+        let ctx = self.inner.context();
+        let (width, height) = self.inner.size();
+
+        self.inner.clear();
+
+        ctx.set_source_rgba(0.1, 0.1, 0.1, 0.95);
+        ctx.rectangle(0., 0., width, height);
+        ctx.fill();
+
+        ctx.set_font_size(16.);
+        let font_extents = ctx.font_extents();
+        let held = self.held.as_ref().map(|held| held.index);
+
+        for (index, &(key, bounds)) in self.keys.iter().enumerate() {
+            let is_modifier = match key {
+                Key::Char { .. } | Key::Space => false,
+                Key::Shift => !self.shifted,
+                _ => true,
+            };
+            if held == Some(index) {
+                ctx.set_source_rgba(0.6, 0.6, 0.6, 1.);
+            } else if is_modifier {
+                ctx.set_source_rgba(0.25, 0.25, 0.25, 1.);
+            } else {
+                ctx.set_source_rgba(0.4, 0.4, 0.4, 1.);
+            }
+            rounded_rect(
+                ctx,
+                bounds.x + KEY_GAP,
+                bounds.y + KEY_GAP,
+                bounds.width - 2. * KEY_GAP,
+                bounds.height - 2. * KEY_GAP,
+                5.,
+            );
+            ctx.fill();
+
+            let label = self.key_label(key);
+            let text_extents = ctx.text_extents(&label);
+            ctx.set_source_rgba(1., 1., 1., 1.);
+            ctx.move_to(
+                bounds.x + (bounds.width - text_extents.width) / 2.,
+                bounds.y + bounds.height / 2. - font_extents.descent + font_extents.height / 2.,
+            );
+            ctx.show_text(&label);
+        }
+
+        self.inner.commit();
+    }
+
+    pub fn render(&mut self, matrix: Matrix4<f32>) {
+        // the panel only slides between its place and the bottom edge
+        self.offset.update_in(Rectangle {
+            height: self.area.height + Self::hidden_offset(),
+            ..self.area
+        });
+        if self.offset.spring.value >= Self::hidden_offset() {
+            return;
+        }
+
+        if self.needs_redraw {
+            self.draw();
+            self.needs_redraw = false;
+        }
+        let y = self.area.y + self.offset.spring.value;
+        self.inner.render(matrix, self.area.x, y, 1.);
+    }
+}
+
+/// Callbacks for the on-screen keyboard device, which has no LEDs and is owned by sfc, so that
+/// wlroots doesn’t free it when it’s destroyed.
+static KEYBOARD_IMPL: wlr_keyboard_impl = wlr_keyboard_impl {
+    destroy: Some(keyboard_destroy),
+    led_update: Some(keyboard_led_update),
+};
+
+unsafe extern "C" fn keyboard_destroy(_: *mut wlr_keyboard) {}
+
+unsafe extern "C" fn keyboard_led_update(_: *mut wlr_keyboard, _: u32) {}
+
+/// The keyboard device that the on-screen keyboard types with.
+///
+/// This must stay at the same address, so it’s always boxed.
+pub struct OskDevice {
+    device: wlr_input_device,
+    keyboard: wlr_keyboard,
+    seat: *mut wlr_seat,
+}
+
+impl fmt::Debug for OskDevice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OskDevice")
+    }
+}
+
+impl OskDevice {
+    /// Creates a keyboard device with the default keymap.
+    pub unsafe fn new(seat: *mut wlr_seat) -> Box<OskDevice> {
+        let mut this = Box::new(OskDevice {
+            device: mem::zeroed(),
+            keyboard: mem::zeroed(),
+            seat,
+        });
+        let name = CString::new("sfc on-screen keyboard").unwrap();
+        wlr_input_device_init(
+            &mut this.device,
+            WLR_INPUT_DEVICE_KEYBOARD,
+            ptr::null(),
+            name.as_ptr(),
+            0,
+            0,
+        );
+        wlr_keyboard_init(&mut this.keyboard, &KEYBOARD_IMPL);
+        this.device.__bindgen_anon_1.keyboard = &mut this.keyboard;
+
+        let context = xkb_context_new(XKB_CONTEXT_NO_FLAGS);
+        let keymap = xkb_keymap_new_from_names(context, ptr::null(), XKB_KEYMAP_COMPILE_NO_FLAGS);
+        wlr_keyboard_set_keymap(&mut this.keyboard, keymap);
+        xkb_keymap_unref(keymap);
+        xkb_context_unref(context);
+
+        this
+    }
+
+    /// Types a key into the surface with keyboard focus, holding shift around it if needed.
+    pub fn type_key(&mut self, key: KeyPress) {
+        unsafe {
+            // the device needs to be the seat’s keyboard so that clients get its keymap
+            wlr_seat_set_keyboard(self.seat, &mut self.device);
+            if key.shift {
+                self.send_key(KEY_LEFTSHIFT, WLR_KEY_PRESSED);
+            }
+            self.send_key(key.code, WLR_KEY_PRESSED);
+            self.send_key(key.code, WLR_KEY_RELEASED);
+            if key.shift {
+                self.send_key(KEY_LEFTSHIFT, WLR_KEY_RELEASED);
+            }
+        }
+    }
+
+    unsafe fn send_key(&mut self, code: u32, state: wlr_key_state::Type) {
+        let time = current_time();
+        let time_msec = (time.as_secs() * 1000) as u32 + time.subsec_millis();
+        let mut event = wlr_event_keyboard_key {
+            time_msec,
+            keycode: code,
+            update_state: true,
+            state,
+        };
+        wlr_keyboard_notify_key(&mut self.keyboard, &mut event);
+        wlr_seat_keyboard_notify_modifiers(self.seat, &mut self.keyboard.modifiers);
+        wlr_seat_keyboard_notify_key(self.seat, time_msec, code, state as u32);
+    }
+}
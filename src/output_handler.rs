@@ -1,6 +1,12 @@
-use crate::renderer::{init_box, Renderer as SfRenderer};
+use crate::damage::{self, FrameRequest};
+use crate::output_layout::OutputRole;
+use crate::renderer::{
+    fit_projection, init_box, rotate_projection, scissor_box, Renderer as SfRenderer,
+};
 use crate::server::Server;
-use cgmath::Matrix4;
+use crate::utils::contains_rect;
+use cgmath::{Matrix4, Vector2};
+use wlroots::wlroots_sys::wlr_output_set_transform;
 use wlroots::*;
 
 pub struct SfOutputHandler;
@@ -17,31 +23,171 @@ impl OutputHandler for SfOutputHandler {
         use compositor_handle as compositor;
         use output_handle as output;
 
-        // TODO: handle on_scale_change
-        output.set_scale(2.);
-
         let server: &mut Server = compositor.data.downcast_mut().unwrap();
-        let renderer = compositor.renderer.as_mut().expect("no renderer");
-        let mut wlr_renderer = renderer.render(output, None);
+        let role = match server.outputs.role(&output_handle) {
+            Some(role) => role,
+            None => return,
+        };
+
+        // the transform needs to be set before rendering starts, and only the tablet’s own
+        // screen has an accelerometer
+        if role == OutputRole::Primary {
+            if let Some(orientation) = server.rotation.take_pending() {
+                unsafe { wlr_output_set_transform(output.as_ptr(), orientation.transform()) };
+            }
+        }
 
-        let (width, height) = wlr_renderer.output.effective_resolution();
-        let resolution = wlr_renderer.output.scale();
+        let (width, height) = output.effective_resolution();
+        let (mut width, mut height) = (width as f64, height as f64);
+        let mut resolution = output.scale() as f64;
+        let transform = output.get_transform();
+        let buffer_size = unsafe { ((*output.as_ptr()).width, (*output.as_ptr()).height) };
 
-        if server.renderer.is_none() {
-            let renderer = SfRenderer::new(width as f64, height as f64, resolution as f64);
-            server.renderer = Some(renderer);
-            unsafe { init_box() };
+        // mirrors draw the primary output’s content as large as it fits, centered, at the
+        // primary output’s resolution so that they share its textures
+        let mut fit = (Vector2::new(0., 0.), 1.);
+        if role == OutputRole::Mirror {
+            let primary = server
+                .renderer()
+                .map(|primary| (primary.dimensions(), primary.resolution()))
+                .filter(|&((width, height), _)| width > 0. && height > 0.);
+            let ((primary_width, primary_height), primary_resolution) = match primary {
+                Some(primary) => primary,
+                None => {
+                    let renderer = compositor.renderer.as_mut().expect("no renderer");
+                    renderer.render(output, None).clear([0., 0., 0., 1.]);
+                    return;
+                }
+            };
+            let scale = (width / primary_width).min(height / primary_height);
+            let offset = Vector2::new(
+                width - primary_width * scale,
+                height - primary_height * scale,
+            ) * resolution
+                / 2.;
+            fit = (offset, scale * resolution / primary_resolution);
+            resolution = primary_resolution;
+            width = primary_width;
+            height = primary_height;
         }
-        let matrix = wlr_renderer.output.transform_matrix();
+
+        // textures are drawn for a size and resolution, so everything is rebuilt if they change
+        let has_renderer = server.outputs.has_renderer();
+        let sf_output = server.outputs.get_mut(&output_handle).unwrap();
+        let size_changed = match sf_output.renderer {
+            Some(ref renderer) => {
+                renderer.dimensions() != (width, height)
+                    || renderer.resolution() != resolution
+                    || renderer.transform() != transform
+                    || renderer.role() != role
+            }
+            None => true,
+        };
+        if size_changed {
+            if !has_renderer {
+                unsafe { init_box() };
+            }
+            let renderer = SfRenderer::new(width, height, resolution, transform, role);
+            sf_output.renderer = Some(renderer);
+            sf_output.damage.add_whole();
+            server.outputs.arrange();
+            server.arrange_layers();
+        }
+
+        // the content is turned back towards the previous orientation while rotating
+        let projection = if role == OutputRole::Primary {
+            let center = Vector2::new(width, height) * resolution / 2.;
+            rotate_projection(output.transform_matrix(), server.rotation.angle(), center)
+        } else {
+            fit_projection(output.transform_matrix(), fit.0, fit.1)
+        };
+
+        // nothing is drawn if nothing changed, and otherwise only what changed is drawn again
+        let sf_output = server.outputs.get_mut(&output_handle).unwrap();
+        let screen = sf_output.renderer.as_ref().unwrap().screen_area();
+        let damaged = match sf_output.damage.take(screen) {
+            Some(damaged) => damaged,
+            None => return,
+        };
+        // mirrors also clear the borders around the content when everything is drawn
+        let (x, y, scissor_width, scissor_height) = if contains_rect(damaged, screen) {
+            (0, 0, buffer_size.0, buffer_size.1)
+        } else {
+            scissor_box(projection, resolution, damaged, buffer_size)
+        };
+
+        let resolution = resolution as f32;
+        // scaled from logical coordinates to output pixels
         let matrix = Matrix4::from([
-            [matrix[0] * resolution, matrix[3], matrix[6], 0.],
-            [matrix[1], matrix[4] * resolution, matrix[7], 0.],
+            [
+                projection[0] * resolution,
+                projection[3] * resolution,
+                projection[6],
+                0.,
+            ],
+            [
+                projection[1] * resolution,
+                projection[4] * resolution,
+                projection[7],
+                0.,
+            ],
             [0., 0., 1., 0.],
-            [matrix[2], matrix[5], matrix[8], 1.],
+            [projection[2], projection[5], projection[8], 1.],
         ]);
 
-        let mut renderer = server.renderer.take().unwrap();
-        renderer.render(matrix, &mut wlr_renderer, server);
-        server.renderer = Some(renderer);
+        let sf_output = server.outputs.get_mut(&output_handle).unwrap();
+        let mut renderer = sf_output.renderer.take().unwrap();
+        let front_space = sf_output.front_space();
+        let output_x = sf_output.x;
+        renderer.set_projection(projection);
+
+        let mut wlr_renderer = compositor
+            .renderer
+            .as_mut()
+            .expect("no renderer")
+            .render(output, None);
+        unsafe {
+            gl::enable_scissor_test(true);
+            gl::scissor(x, y, scissor_width, scissor_height);
+        }
+        match role {
+            OutputRole::Extended => {
+                let cursor = server.cursor_location();
+                let cursor = server
+                    .outputs
+                    .extended_output_at(cursor)
+                    .filter(|output| output.handle == output_handle)
+                    .map(|_| cursor - Vector2::new(output_x, 0.));
+                renderer.render_extended(matrix, &mut wlr_renderer, server, front_space, cursor);
+            }
+            _ => renderer.render(matrix, &mut wlr_renderer, server),
+        }
+        unsafe { gl::enable_scissor_test(false) };
+
+        // animations that are still running are drawn again on the next frame
+        match damage::take_frame_request() {
+            Some(FrameRequest::Whole) => server.outputs.damage_whole(),
+            Some(FrameRequest::Area(area)) => server.outputs.damage_on(&output_handle, area),
+            None => (),
+        }
+        if let Some(sf_output) = server.outputs.get_mut(&output_handle) {
+            sf_output.renderer = Some(renderer);
+        }
+    }
+
+    #[wlroots_dehandle(compositor)]
+    fn destroyed(&mut self, compositor_handle: CompositorHandle, output_handle: OutputHandle) {
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        server.output_removed(&output_handle);
+    }
+
+    #[wlroots_dehandle(compositor)]
+    fn on_mode_change(&mut self, compositor_handle: CompositorHandle, output_handle: OutputHandle) {
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        // the pixel density depends on the mode
+        server.update_output_scale(&output_handle);
+        server.outputs.damage_whole();
     }
 }
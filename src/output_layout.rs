@@ -0,0 +1,430 @@
+//! The outputs and where they are.
+//!
+//! The primary output is the tablet’s own screen, or the one named by a `primary` line in the
+//! config, and is picked again whenever outputs are added or removed. It shows the home bar, the
+//! overview, the Slide Over panel and layer surfaces, and gets touch input. Other outputs either
+//! mirror it, or extend the layout to its right and show the spaces that were moved to them.
+
+use crate::config::OutputMode;
+use crate::damage::Damage;
+use crate::renderer::Renderer;
+use crate::server::SpaceID;
+use crate::utils::intersection;
+use cairo::Rectangle;
+use cgmath::Vector2;
+use std::cmp::Reverse;
+use std::mem;
+use wlroots::wlroots_sys::wlr_output_schedule_frame;
+use wlroots::OutputHandle;
+
+/// Name prefixes of the built-in panels of tablets and laptops.
+const BUILT_IN_PREFIXES: [&str; 3] = ["eDP", "DSI", "LVDS"];
+
+/// What an output shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputRole {
+    /// The tablet UI.
+    Primary,
+    /// The primary output’s content, scaled to fit.
+    Mirror,
+    /// The spaces that were moved to the output.
+    Extended,
+}
+
+/// An output and the state needed to draw it.
+#[derive(Debug)]
+pub struct SfOutput {
+    pub handle: OutputHandle,
+    pub name: String,
+    /// Created when the output draws its first frame, and again when its size, scale, transform
+    /// or role changes.
+    pub renderer: Option<Renderer>,
+    /// The left edge of the output in the layout.
+    pub x: f64,
+    /// The spaces shown on the output while it’s extended, back to front.
+    pub space_order: Vec<SpaceID>,
+    /// What changed since the last frame, in the coordinates of the renderer.
+    pub damage: Damage,
+}
+
+impl SfOutput {
+    /// Returns the output’s area in the layout, which is empty until it has a renderer.
+    pub fn area(&self) -> Rectangle {
+        let (width, height) = self
+            .renderer
+            .as_ref()
+            .map_or((0., 0.), |renderer| renderer.dimensions());
+        Rectangle {
+            x: self.x,
+            y: 0.,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the space in front, if the output has any spaces.
+    pub fn front_space(&self) -> Option<SpaceID> {
+        self.space_order.last().cloned()
+    }
+
+    /// Asks for a frame, which fails while the output is drawing one.
+    fn schedule_frame(&self) {
+        let _ = self
+            .handle
+            .run(|output| unsafe { wlr_output_schedule_frame(output.as_ptr()) });
+    }
+}
+
+/// All outputs, with the primary one first.
+#[derive(Debug, Default)]
+pub struct OutputLayout {
+    outputs: Vec<SfOutput>,
+    mode: OutputMode,
+    /// The output that should be primary whenever it’s connected.
+    preferred_primary: Option<String>,
+}
+
+impl OutputLayout {
+    pub fn new() -> OutputLayout {
+        OutputLayout::default()
+    }
+
+    pub fn mode(&self) -> OutputMode {
+        self.mode
+    }
+
+    /// Sets how the other outputs are used and returns the spaces that have to move to the
+    /// primary output because they can’t be shown anymore.
+    pub fn set_mode(&mut self, mode: OutputMode) -> Vec<SpaceID> {
+        self.mode = mode;
+        let mut orphaned = Vec::new();
+        if mode == OutputMode::Mirror {
+            for output in &mut self.outputs {
+                orphaned.append(&mut output.space_order);
+            }
+        }
+        self.arrange();
+        orphaned
+    }
+
+    /// Sets the output that should be primary, which takes effect when outputs are added or
+    /// removed next.
+    pub fn set_preferred_primary(&mut self, name: Option<String>) {
+        self.preferred_primary = name;
+    }
+
+    /// Adds an output and returns the spaces that have to move to the primary output, if the
+    /// new output takes its place.
+    pub fn add(&mut self, handle: OutputHandle, name: String) -> Vec<SpaceID> {
+        self.outputs.push(SfOutput {
+            handle,
+            name,
+            renderer: None,
+            x: 0.,
+            space_order: Vec::new(),
+            damage: Damage::new(),
+        });
+        let orphaned = self.pick_primary();
+        self.arrange();
+        orphaned
+    }
+
+    /// Removes an output and returns the spaces that have to move to the primary output.
+    ///
+    /// If the primary output is removed, the best remaining one takes its place.
+    pub fn remove(&mut self, handle: &OutputHandle) -> Vec<SpaceID> {
+        let index = match self
+            .outputs
+            .iter()
+            .position(|output| &output.handle == handle)
+        {
+            Some(index) => index,
+            None => return Vec::new(),
+        };
+        let mut orphaned = self.outputs.remove(index).space_order;
+        orphaned.append(&mut self.pick_primary());
+        self.arrange();
+        orphaned
+    }
+
+    /// Returns how well an output fits being the primary one: the preferred output first, then
+    /// built-in panels, then any other.
+    fn primary_rank(&self, name: &str) -> u8 {
+        if self
+            .preferred_primary
+            .as_ref()
+            .map_or(false, |preferred| preferred == name)
+        {
+            2
+        } else if BUILT_IN_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Moves the best fitting output to the front, keeping the current primary one on ties,
+    /// and returns the spaces that the new primary output showed while it was extended.
+    fn pick_primary(&mut self) -> Vec<SpaceID> {
+        let best = (0..self.outputs.len())
+            .max_by_key(|&index| (self.primary_rank(&self.outputs[index].name), Reverse(index)));
+        if let Some(index) = best.filter(|&index| index != 0) {
+            let output = self.outputs.remove(index);
+            self.outputs.insert(0, output);
+        }
+        self.outputs.first_mut().map_or_else(Vec::new, |primary| {
+            mem::replace(&mut primary.space_order, Vec::new())
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SfOutput> {
+        self.outputs.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut SfOutput> {
+        self.outputs.iter_mut()
+    }
+
+    pub fn primary(&self) -> Option<&SfOutput> {
+        self.outputs.first()
+    }
+
+    pub fn primary_mut(&mut self) -> Option<&mut SfOutput> {
+        self.outputs.first_mut()
+    }
+
+    pub fn get(&self, handle: &OutputHandle) -> Option<&SfOutput> {
+        self.outputs.iter().find(|output| &output.handle == handle)
+    }
+
+    pub fn get_mut(&mut self, handle: &OutputHandle) -> Option<&mut SfOutput> {
+        self.outputs
+            .iter_mut()
+            .find(|output| &output.handle == handle)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&SfOutput> {
+        self.outputs.iter().find(|output| output.name == name)
+    }
+
+    pub fn by_name_mut(&mut self, name: &str) -> Option<&mut SfOutput> {
+        self.outputs.iter_mut().find(|output| output.name == name)
+    }
+
+    /// Returns what the output shows, or `None` if it isn’t in the layout.
+    pub fn role(&self, handle: &OutputHandle) -> Option<OutputRole> {
+        let index = self
+            .outputs
+            .iter()
+            .position(|output| &output.handle == handle)?;
+        Some(if index == 0 {
+            OutputRole::Primary
+        } else if self.mode == OutputMode::Mirror {
+            OutputRole::Mirror
+        } else {
+            OutputRole::Extended
+        })
+    }
+
+    /// Returns true if any output has a renderer yet.
+    pub fn has_renderer(&self) -> bool {
+        self.outputs.iter().any(|output| output.renderer.is_some())
+    }
+
+    /// Places extended outputs next to each other, to the right of the primary output.
+    pub fn arrange(&mut self) {
+        let mut x = 0.;
+        for output in &mut self.outputs {
+            output.x = x;
+            if self.mode == OutputMode::Extend {
+                x += output.area().width;
+            }
+        }
+    }
+
+    /// Returns the area that the cursor can move in.
+    pub fn bounds(&self) -> Rectangle {
+        let outputs = match self.mode {
+            OutputMode::Mirror => &self.outputs[..self.outputs.len().min(1)],
+            OutputMode::Extend => &self.outputs[..],
+        };
+        outputs.iter().map(SfOutput::area).fold(
+            Rectangle {
+                x: 0.,
+                y: 0.,
+                width: 0.,
+                height: 0.,
+            },
+            |bounds, area| Rectangle {
+                width: bounds.width.max(area.x + area.width),
+                height: bounds.height.max(area.height),
+                ..bounds
+            },
+        )
+    }
+
+    /// Clamps a point to the layout.
+    pub fn clamp_location(&self, location: Vector2<f64>) -> Vector2<f64> {
+        let bounds = self.bounds();
+        Vector2::new(
+            location.x.max(0.).min(bounds.width),
+            location.y.max(0.).min(bounds.height),
+        )
+    }
+
+    /// Returns the extended output at a point in the layout, if it isn’t on the primary output.
+    ///
+    /// Points on the right and bottom edges belong to the output, since the cursor is clamped to
+    /// them.
+    pub fn extended_output_at(&self, point: Vector2<f64>) -> Option<&SfOutput> {
+        if self.mode != OutputMode::Extend {
+            return None;
+        }
+        self.outputs.iter().skip(1).find(|output| {
+            let area = output.area();
+            point.x >= area.x && point.x <= area.x + area.width && point.y <= area.height
+        })
+    }
+
+    /// Damages an area of the layout on the outputs that show it and schedules frames for them.
+    ///
+    /// Mirrors show the primary output’s area, in its coordinates.
+    pub fn damage(&mut self, area: Rectangle) {
+        let primary = match self.outputs.first() {
+            Some(primary) => primary.area(),
+            None => return,
+        };
+        let mode = self.mode;
+        for (index, output) in self.outputs.iter_mut().enumerate() {
+            let shown = if index == 0 || mode == OutputMode::Mirror {
+                primary
+            } else {
+                output.area()
+            };
+            if let Some(area) = intersection(area, shown) {
+                output.damage.add(Rectangle {
+                    x: area.x - shown.x,
+                    y: area.y - shown.y,
+                    ..area
+                });
+                output.schedule_frame();
+            }
+        }
+    }
+
+    /// Damages an area in the coordinates of an output’s renderer, on that output and the
+    /// outputs that show the same content.
+    pub fn damage_on(&mut self, handle: &OutputHandle, area: Rectangle) {
+        let index = match self
+            .outputs
+            .iter()
+            .position(|output| &output.handle == handle)
+        {
+            Some(index) => index,
+            None => return,
+        };
+        let shown = if index == 0 || self.mode == OutputMode::Mirror {
+            self.outputs[0].area()
+        } else {
+            self.outputs[index].area()
+        };
+        self.damage(Rectangle {
+            x: area.x + shown.x,
+            y: area.y + shown.y,
+            ..area
+        });
+    }
+
+    /// Damages all outputs entirely and schedules frames for them.
+    pub fn damage_whole(&mut self) {
+        for output in &mut self.outputs {
+            output.damage.add_whole();
+            output.schedule_frame();
+        }
+    }
+
+    /// Returns the extended output that shows a space, or `None` if the primary output does.
+    pub fn output_of_space(&self, id: SpaceID) -> Option<&SfOutput> {
+        self.outputs
+            .iter()
+            .skip(1)
+            .find(|output| output.space_order.contains(&id))
+    }
+
+    /// Forgets a space that was removed.
+    pub fn remove_space(&mut self, id: SpaceID) {
+        for output in &mut self.outputs {
+            output.space_order.retain(|x| *x != id);
+        }
+    }
+
+    /// Returns the name of the output after the given one, or of the first extended output if
+    /// no name is given, wrapping around to the primary output which is returned as `None`.
+    pub fn next_output(&self, name: Option<&str>) -> Option<String> {
+        if self.mode != OutputMode::Extend {
+            return None;
+        }
+        let index = match name {
+            Some(name) => self.outputs.iter().position(|output| output.name == name)?,
+            None => 0,
+        };
+        self.outputs
+            .get(index + 1)
+            .map(|output| output.name.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaces_return_to_the_primary_output() {
+        let mut layout = OutputLayout::new();
+        layout.add(OutputHandle::default(), "eDP-1".into());
+        layout.add(OutputHandle::default(), "DP-1".into());
+        layout.add(OutputHandle::default(), "DP-2".into());
+        assert_eq!(layout.next_output(None), Some("DP-1".into()));
+        assert_eq!(layout.next_output(Some("DP-1")), Some("DP-2".into()));
+        assert_eq!(layout.next_output(Some("DP-2")), None);
+
+        layout.by_name_mut("DP-1").unwrap().space_order = vec![1, 2];
+        layout.by_name_mut("DP-2").unwrap().space_order = vec![3];
+        assert_eq!(layout.output_of_space(3).unwrap().name, "DP-2");
+        layout.remove_space(2);
+        assert_eq!(layout.by_name("DP-1").unwrap().front_space(), Some(1));
+
+        assert_eq!(layout.set_mode(OutputMode::Mirror), vec![1, 3]);
+        assert_eq!(layout.next_output(None), None);
+        assert!(layout.output_of_space(1).is_none());
+    }
+
+    #[test]
+    fn built_in_or_preferred_output_is_primary() {
+        let mut layout = OutputLayout::new();
+        // the dock’s screen is often found first
+        layout.add(OutputHandle::default(), "DP-1".into());
+        layout.by_name_mut("DP-1").unwrap().space_order = vec![1];
+        assert_eq!(layout.primary().unwrap().name, "DP-1");
+        assert_eq!(layout.add(OutputHandle::default(), "eDP-1".into()), vec![1]);
+        assert_eq!(layout.primary().unwrap().name, "eDP-1");
+        layout.add(OutputHandle::default(), "DP-2".into());
+        assert_eq!(layout.primary().unwrap().name, "eDP-1");
+
+        layout.set_preferred_primary(Some("DP-2".into()));
+        layout.by_name_mut("DP-2").unwrap().space_order = vec![2];
+        assert_eq!(
+            layout.add(OutputHandle::default(), "HDMI-A-1".into()),
+            vec![2]
+        );
+        assert_eq!(layout.primary().unwrap().name, "DP-2");
+        assert_eq!(layout.next_output(None), Some("eDP-1".into()));
+    }
+}
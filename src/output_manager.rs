@@ -1,6 +1,21 @@
 use crate::output_handler::SfOutputHandler;
+use crate::server::Server;
 use wlroots::*;
 
+/// Pixel density that outputs are scaled to, which is a bit more than the usual 96 DPI since
+/// tablets are held closer than monitors.
+const TARGET_DPI: f64 = 128.;
+
+/// Returns the scale for a screen with the given width in pixels and millimeters, rounded to
+/// quarters, or 1 if the physical size is unknown.
+pub fn scale_for_dpi(width: i32, phys_width: i32) -> f64 {
+    if width <= 0 || phys_width <= 0 {
+        return 1.;
+    }
+    let dpi = f64::from(width) / (f64::from(phys_width) / 25.4);
+    ((dpi / TARGET_DPI * 4.).round() / 4.).max(1.)
+}
+
 #[derive(Debug)]
 pub struct OutputManager;
 
@@ -11,13 +26,35 @@ impl OutputManager {
 }
 
 impl OutputManagerHandler for OutputManager {
+    #[wlroots_dehandle(compositor)]
     fn output_added<'output>(
         &mut self,
-        _compositor: CompositorHandle,
+        compositor_handle: CompositorHandle,
         output: OutputBuilder<'output>,
     ) -> Option<OutputBuilderResult<'output>> {
+        use compositor_handle as compositor;
+
         let name = output.handle().run(|out| out.name()).unwrap();
         info!("Output added! {}", name);
-        Some(output.build_best_mode(SfOutputHandler::new()))
+
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        let handle = output.handle();
+        server.output_added(handle.clone(), name);
+        let result = output.build_best_mode(SfOutputHandler::new());
+        server.update_output_scale(&handle);
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_comes_from_pixel_density() {
+        // a 12.3" 2736x1824 screen is about 267 DPI
+        assert_eq!(scale_for_dpi(2736, 260), 2.);
+        assert_eq!(scale_for_dpi(1920, 527), 1.);
+        assert_eq!(scale_for_dpi(1920, 0), 1.);
     }
 }
@@ -0,0 +1,414 @@
+//! The overview, which shows every space as a card in a scrollable row.
+//!
+//! Cards are closed by flicking them up, and dragging a card down picks it up, so that it can be
+//! dropped on the right edge of the screen to open its window in Split View with the front one.
+
+use crate::event::Event;
+use crate::server::SpaceID;
+use crate::space::SpaceTransform;
+use crate::spring::{drag_velocity, RealTimeSpring, Spring};
+use cgmath::Vector2;
+use std::collections::HashMap;
+use std::time::Instant;
+use wlroots::wlr_axis_orientation::*;
+use wlroots::TouchId;
+
+/// Size of a card relative to the screen.
+const CARD_SCALE: f64 = 0.6;
+const CARD_GAP: f64 = 24.;
+
+/// Touches that move less than this are taps.
+const TAP_DISTANCE: f64 = 8.;
+
+/// How far a card needs to be dragged up to close it.
+const CLOSE_DISTANCE: f64 = 100.;
+
+/// How fast a card needs to be flicked up to close it.
+const CLOSE_VELOCITY: f64 = 800.;
+
+/// How far ahead the scroll position is projected with its velocity when picking a card to
+/// snap to.
+const SCROLL_PROJECTION: f64 = 0.2;
+
+/// How close to the right edge of the screen a picked up card needs to be dropped to open it in
+/// Split View.
+const SPLIT_DROP_WIDTH: f64 = 80.;
+
+/// Something the server should do after an overview event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverviewAction {
+    /// Bring the space to the front and close the overview.
+    Select(SpaceID),
+    /// Close all views in the space.
+    Close(SpaceID),
+    /// Show the topmost view of the space next to the front one in Split View, and close the
+    /// overview.
+    Split(SpaceID),
+    /// Close the overview.
+    Dismiss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DragAxis {
+    Undecided,
+    Horizontal,
+    Vertical,
+    /// A card was dragged down and follows the drag in both directions.
+    PickUp,
+}
+
+#[derive(Debug)]
+struct Drag {
+    /// The touch point, or None for the pointer.
+    id: Option<TouchId>,
+    start: Vector2<f64>,
+    last: Vector2<f64>,
+    last_time: Instant,
+    velocity: Vector2<f64>,
+    card: Option<SpaceID>,
+    scroll_start: f64,
+    axis: DragAxis,
+}
+
+#[derive(Debug)]
+pub struct Overview {
+    open: bool,
+    progress: RealTimeSpring,
+    scroll: RealTimeSpring,
+    card_offsets: HashMap<SpaceID, RealTimeSpring>,
+    /// The card that was picked up last and how far it’s moved sideways.
+    picked_up: Option<(SpaceID, RealTimeSpring)>,
+    drag: Option<Drag>,
+}
+
+impl Overview {
+    pub fn new() -> Overview {
+        Overview {
+            open: false,
+            progress: RealTimeSpring::new(Spring::new(1., 0.4)),
+            scroll: RealTimeSpring::new(Spring::new(1., 0.4)),
+            card_offsets: HashMap::new(),
+            picked_up: None,
+            drag: None,
+        }
+    }
+
+    /// Returns true if the overview is open and should receive input.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Returns true if the overview is visible, which includes the open and close animations.
+    pub fn is_visible(&self) -> bool {
+        self.open || self.progress.spring.needs_update(0.001)
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.progress.spring.target = 1.;
+        self.scroll.spring.value = 0.;
+        self.scroll.spring.target = 0.;
+        self.scroll.spring.velocity = 0.;
+        self.card_offsets.clear();
+        self.picked_up = None;
+        self.drag = None;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.progress.spring.target = 0.;
+        self.drag = None;
+    }
+
+    /// Forgets the card of a removed space.
+    pub fn remove_space(&mut self, id: SpaceID) {
+        self.card_offsets.remove(&id);
+        if self.picked_up.as_ref().map_or(false, |(card, _)| *card == id) {
+            self.picked_up = None;
+        }
+        if self.drag.as_ref().map_or(false, |drag| drag.card == Some(id)) {
+            self.drag = None;
+        }
+    }
+
+    /// Advances all animations; should be called once per frame.
+    pub fn update(&mut self) {
+        self.progress.update();
+
+        let (dragging_axis, dragged_card) = match self.drag {
+            Some(ref drag) => (drag.axis, drag.card),
+            None => (DragAxis::Undecided, None),
+        };
+
+        if dragging_axis == DragAxis::Horizontal {
+            self.scroll.update_time();
+        } else {
+            self.scroll.update();
+        }
+
+        let dragging_card =
+            dragging_axis == DragAxis::Vertical || dragging_axis == DragAxis::PickUp;
+        for (id, offset) in self.card_offsets.iter_mut() {
+            if dragging_card && dragged_card == Some(*id) {
+                offset.update_time();
+            } else {
+                offset.update();
+            }
+        }
+        if let Some((_, ref mut offset)) = self.picked_up {
+            if dragging_axis == DragAxis::PickUp {
+                offset.update_time();
+            } else {
+                offset.update();
+            }
+        }
+    }
+
+    fn card_size(dims: (f64, f64)) -> (f64, f64) {
+        (dims.0 * CARD_SCALE, dims.1 * CARD_SCALE)
+    }
+
+    /// Returns the distance between the left edges of two cards.
+    fn card_stride(dims: (f64, f64)) -> f64 {
+        Self::card_size(dims).0 + CARD_GAP
+    }
+
+    fn card_transform(&self, index: usize, id: SpaceID, dims: (f64, f64)) -> SpaceTransform {
+        let (width, height) = dims;
+        let (card_width, card_height) = Self::card_size(dims);
+        let y_offset = self
+            .card_offsets
+            .get(&id)
+            .map_or(0., |offset| offset.spring.value);
+        let x_offset = match self.picked_up {
+            Some((card, ref offset)) if card == id => offset.spring.value,
+            _ => 0.,
+        };
+
+        SpaceTransform {
+            offset: Vector2::new(
+                (width - card_width) / 2. + index as f64 * Self::card_stride(dims)
+                    - self.scroll.spring.value
+                    + x_offset,
+                (height - card_height) / 2. + y_offset,
+            ),
+            scale: CARD_SCALE,
+        }
+    }
+
+    /// Returns the transform for a space, where index 0 is the frontmost space.
+    pub fn space_transform(&self, index: usize, id: SpaceID, dims: (f64, f64)) -> SpaceTransform {
+        // when closed, spaces are lined up to the right of the frontmost one
+        let closed = SpaceTransform {
+            offset: Vector2::new(index as f64 * (dims.0 + CARD_GAP), 0.),
+            scale: 1.,
+        };
+        closed.lerp(
+            self.card_transform(index, id, dims),
+            self.progress.spring.value,
+        )
+    }
+
+    /// Returns the space whose card is at the given point.
+    ///
+    /// `order` lists spaces from front to back.
+    fn card_at(&self, point: Vector2<f64>, order: &[SpaceID], dims: (f64, f64)) -> Option<SpaceID> {
+        let (card_width, card_height) = Self::card_size(dims);
+        order.iter().enumerate().find_map(|(index, id)| {
+            let offset = self.card_transform(index, *id, dims).offset;
+            let local = point - offset;
+            if local.x >= 0. && local.y >= 0. && local.x < card_width && local.y < card_height {
+                Some(*id)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Handles an input event.
+    ///
+    /// `order` lists spaces from front to back.
+    pub fn handle_event(
+        &mut self,
+        event: &Event,
+        cursor: Vector2<f64>,
+        order: &[SpaceID],
+        dims: (f64, f64),
+    ) -> Option<OverviewAction> {
+        match *event {
+            Event::TouchDown { id, location } => self.drag_start(Some(id), location, order, dims),
+            Event::PointerDown { .. } => self.drag_start(None, cursor, order, dims),
+            Event::TouchMotion { id, location } => self.drag_move(Some(id), location),
+            Event::PointerMotion { location } => self.drag_move(None, location),
+            Event::TouchUp { id } => return self.drag_end(Some(id), order, dims),
+            Event::PointerUp { .. } => return self.drag_end(None, order, dims),
+            Event::TouchCancel { id } => {
+                if self.drag.as_ref().map_or(false, |drag| drag.id == Some(id)) {
+                    self.drag = None;
+                    for offset in self.card_offsets.values_mut() {
+                        offset.spring.target = 0.;
+                    }
+                    if let Some((_, ref mut offset)) = self.picked_up {
+                        offset.spring.target = 0.;
+                    }
+                }
+            }
+            Event::PointerAxis {
+                orientation: WLR_AXIS_ORIENTATION_HORIZONTAL,
+                delta,
+                ..
+            } => {
+                let target = self.scroll.spring.target + delta;
+                self.scroll.spring.target = self.clamp_scroll(target, order.len(), dims);
+            }
+            _ => (),
+        }
+        None
+    }
+
+    fn clamp_scroll(&self, scroll: f64, count: usize, dims: (f64, f64)) -> f64 {
+        let max = count.saturating_sub(1) as f64 * Self::card_stride(dims);
+        scroll.max(0.).min(max)
+    }
+
+    fn drag_start(
+        &mut self,
+        id: Option<TouchId>,
+        location: Vector2<f64>,
+        order: &[SpaceID],
+        dims: (f64, f64),
+    ) {
+        if self.drag.is_some() {
+            return;
+        }
+
+        self.drag = Some(Drag {
+            id,
+            start: location,
+            last: location,
+            last_time: Instant::now(),
+            velocity: Vector2::new(0., 0.),
+            card: self.card_at(location, order, dims),
+            scroll_start: self.scroll.spring.value,
+            axis: DragAxis::Undecided,
+        });
+    }
+
+    fn drag_move(&mut self, id: Option<TouchId>, location: Vector2<f64>) {
+        let drag = match self.drag {
+            Some(ref mut drag) if drag.id == id => drag,
+            _ => return,
+        };
+
+        if let Some(velocity) = drag_velocity(location - drag.last, &mut drag.last_time) {
+            drag.velocity = velocity;
+        }
+        drag.last = location;
+
+        let delta = location - drag.start;
+        if drag.axis == DragAxis::Undecided && delta.x.hypot(delta.y) > TAP_DISTANCE {
+            drag.axis = if delta.y.abs() <= delta.x.abs() || drag.card.is_none() {
+                DragAxis::Horizontal
+            } else if delta.y < 0. {
+                DragAxis::Vertical
+            } else {
+                DragAxis::PickUp
+            };
+        }
+
+        match drag.axis {
+            DragAxis::Horizontal => {
+                self.scroll.spring.value = drag.scroll_start - delta.x;
+                self.scroll.spring.velocity = -drag.velocity.x;
+            }
+            DragAxis::Vertical => {
+                let card = drag.card.unwrap();
+                let offset = self
+                    .card_offsets
+                    .entry(card)
+                    .or_insert_with(|| RealTimeSpring::new(Spring::new(1., 0.3)));
+                // cards can only be dragged up
+                offset.spring.value = delta.y.min(0.);
+                offset.spring.velocity = drag.velocity.y;
+            }
+            DragAxis::PickUp => {
+                let card = drag.card.unwrap();
+                let offset = self
+                    .card_offsets
+                    .entry(card)
+                    .or_insert_with(|| RealTimeSpring::new(Spring::new(1., 0.3)));
+                offset.spring.value = delta.y;
+                offset.spring.velocity = drag.velocity.y;
+
+                if self.picked_up.as_ref().map_or(true, |(picked_up, _)| *picked_up != card) {
+                    self.picked_up = Some((card, RealTimeSpring::new(Spring::new(1., 0.3))));
+                }
+                let x_offset = &mut self.picked_up.as_mut().unwrap().1;
+                x_offset.spring.value = delta.x;
+                x_offset.spring.velocity = drag.velocity.x;
+            }
+            DragAxis::Undecided => (),
+        }
+    }
+
+    fn drag_end(
+        &mut self,
+        id: Option<TouchId>,
+        order: &[SpaceID],
+        dims: (f64, f64),
+    ) -> Option<OverviewAction> {
+        let drag = match self.drag.take() {
+            Some(drag) => {
+                if drag.id != id {
+                    self.drag = Some(drag);
+                    return None;
+                }
+                drag
+            }
+            None => return None,
+        };
+
+        match drag.axis {
+            DragAxis::Undecided => match drag.card {
+                Some(card) => Some(OverviewAction::Select(card)),
+                None => Some(OverviewAction::Dismiss),
+            },
+            DragAxis::Horizontal => {
+                let stride = Self::card_stride(dims);
+                let projected =
+                    self.scroll.spring.value + self.scroll.spring.velocity * SCROLL_PROJECTION;
+                let target = (projected / stride).round() * stride;
+                self.scroll.spring.target = self.clamp_scroll(target, order.len(), dims);
+                None
+            }
+            DragAxis::Vertical => {
+                let card = drag.card.unwrap();
+                let offset = self.card_offsets.get_mut(&card).unwrap();
+                if offset.spring.value < -CLOSE_DISTANCE || drag.velocity.y < -CLOSE_VELOCITY {
+                    // fly off the top of the screen
+                    offset.spring.target = -dims.1;
+                    Some(OverviewAction::Close(card))
+                } else {
+                    offset.spring.target = 0.;
+                    None
+                }
+            }
+            DragAxis::PickUp => {
+                let card = drag.card.unwrap();
+                if let Some(offset) = self.card_offsets.get_mut(&card) {
+                    offset.spring.target = 0.;
+                }
+                if let Some((_, ref mut offset)) = self.picked_up {
+                    offset.spring.target = 0.;
+                }
+                // the front space is already on screen
+                let is_front = order.first() == Some(&card);
+                if !is_front && drag.last.x > dims.0 - SPLIT_DROP_WIDTH {
+                    Some(OverviewAction::Split(card))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
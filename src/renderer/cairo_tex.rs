@@ -1,8 +1,10 @@
 use crate::renderer::box_render::draw_box_tex;
+use crate::utils::union;
 use cairo::{Context, Operator, Rectangle, Surface};
 use cairo_sys::{cairo_image_surface_create, cairo_image_surface_get_data, enums::Format};
 use cgmath::Matrix4;
 use gl::GLTexture2D;
+use std::cell::Cell;
 
 /// A cairo context that renders into an OpenGL texture.
 #[derive(Debug)]
@@ -15,6 +17,10 @@ pub struct CairoTex {
     resolution: f64,
     tex_width: i32,
     tex_height: i32,
+    /// The part that was cleared since the last commit, in unscaled coordinates.
+    dirty: Cell<Option<Rectangle>>,
+    /// True once the texture has storage, which the first commit allocates.
+    allocated: Cell<bool>,
 }
 
 impl CairoTex {
@@ -47,6 +53,8 @@ impl CairoTex {
             resolution,
             tex_width,
             tex_height,
+            dirty: Cell::new(None),
+            allocated: Cell::new(false),
         }
     }
 
@@ -67,21 +75,67 @@ impl CairoTex {
 
     /// Clears the context and applies the scaling factor.
     pub fn clear(&self) {
+        self.clear_area(Rectangle {
+            x: 0.,
+            y: 0.,
+            width: self.width,
+            height: self.height,
+        });
+    }
+
+    /// Clears part of the context and applies the scaling factor.
+    ///
+    /// The context stays clipped to the area until the next clear, so that drawing everything
+    /// again only changes that part, and only that part is copied into the texture.
+    pub fn clear_area(&self, area: Rectangle) {
+        self.context.reset_clip();
         self.context.identity_matrix();
         self.context.scale(self.resolution, self.resolution);
+        self.context.new_path();
+        self.context
+            .rectangle(area.x, area.y, area.width, area.height);
+        self.context.clip();
         self.context.set_operator(Operator::Clear);
-        self.context.rectangle(0., 0., self.width, self.height);
         self.context.paint_with_alpha(1.);
         self.context.set_operator(Operator::Over);
+
+        let dirty = match self.dirty.get() {
+            Some(dirty) => union(dirty, area),
+            None => area,
+        };
+        self.dirty.set(Some(dirty));
     }
 
-    /// Copies the pixels into the OpenGL texture.
+    /// Returns true if something was cleared since the last commit.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.get().is_some()
+    }
+
+    /// Copies the pixels that were cleared since the last commit into the OpenGL texture.
     pub fn commit(&self) {
+        let dirty = match self.dirty.take() {
+            Some(dirty) => dirty,
+            None => return,
+        };
         unsafe {
+            self.surface.flush();
             let tex_data = cairo_image_surface_get_data(self.surface.to_raw_none());
             self.texture.bind();
-            self.texture
-                .load_image_raw(self.tex_width, self.tex_height, tex_data);
+            if !self.allocated.get() {
+                self.texture
+                    .load_image_raw(self.tex_width, self.tex_height, tex_data);
+                self.allocated.set(true);
+                return;
+            }
+
+            // the rows in texture pixels, rounded outwards
+            let y = (dirty.y * self.resolution).floor().max(0.) as i32;
+            let bottom = ((dirty.y + dirty.height) * self.resolution).ceil() as i32;
+            let bottom = bottom.min(self.tex_height);
+            if bottom > y {
+                let rows = tex_data.add(y as usize * self.tex_width as usize * 4);
+                self.texture.load_rows_raw(y, self.tex_width, bottom - y, rows);
+            }
         }
     }
 
@@ -0,0 +1,150 @@
+//! Cursor rendering.
+
+use crate::renderer::box_render::draw_box_tex;
+use cairo::Rectangle;
+use cgmath::{Matrix4, Vector2};
+use gl::{GLTexture2D, GLVertexArray};
+use wlroots::utils::current_time;
+use wlroots::xcursor::XCursorTheme;
+use wlroots::*;
+
+const CURSOR_SIZE: f64 = 24.;
+const CURSOR_NAME: &str = "left_ptr";
+
+/// A cursor image from the xcursor theme, uploaded to a texture.
+#[derive(Debug)]
+struct ThemeCursor {
+    texture: GLTexture2D,
+    size: (f64, f64),
+    hotspot: (f64, f64),
+}
+
+/// A cursor image set by a client.
+#[derive(Debug, Clone)]
+pub struct ClientCursor {
+    pub surface: SurfaceHandle,
+    pub hotspot: (i32, i32),
+}
+
+/// Renders the cursor, either from the default xcursor theme or from a client surface.
+#[derive(Debug)]
+pub struct Cursor {
+    theme_cursor: Option<ThemeCursor>,
+    resolution: f64,
+}
+
+impl Cursor {
+    pub fn new(resolution: f64) -> Cursor {
+        Cursor {
+            theme_cursor: load_theme_cursor(resolution),
+            resolution,
+        }
+    }
+
+    /// Renders the cursor at the given location.
+    ///
+    /// If a client cursor is given, its surface will be rendered instead of the theme cursor.
+    pub fn render(
+        &self,
+        matrix: Matrix4<f32>,
+        projection: [f32; 9],
+        location: Vector2<f64>,
+        client_cursor: Option<&ClientCursor>,
+        renderer: &mut Renderer,
+    ) {
+        if let Some(client_cursor) = client_cursor {
+            self.render_client_cursor(projection, location, client_cursor, renderer);
+            return;
+        }
+
+        if let Some(ref cursor) = self.theme_cursor {
+            unsafe {
+                cursor.texture.activate(0);
+                draw_box_tex(
+                    matrix,
+                    Rectangle {
+                        x: location.x - cursor.hotspot.0,
+                        y: location.y - cursor.hotspot.1,
+                        width: cursor.size.0,
+                        height: cursor.size.1,
+                    },
+                );
+            }
+        }
+    }
+
+    fn render_client_cursor(
+        &self,
+        projection: [f32; 9],
+        location: Vector2<f64>,
+        client_cursor: &ClientCursor,
+        renderer: &mut Renderer,
+    ) {
+        let scale = self.resolution;
+        let res = client_cursor.surface.run(|surface| {
+            let (width, height) = surface.current_state().size();
+            let (hx, hy) = client_cursor.hotspot;
+            let render_box = Area::new(
+                Origin::new(
+                    ((location.x - hx as f64) * scale) as i32,
+                    ((location.y - hy as f64) * scale) as i32,
+                ),
+                Size::new(
+                    (width as f64 * scale) as i32,
+                    (height as f64 * scale) as i32,
+                ),
+            );
+
+            let transform = renderer.output.get_transform().invert();
+            let matrix = project_box(render_box, transform, 0.0, projection);
+            unsafe { GLVertexArray::unbind() };
+            if let Some(tex) = surface.texture() {
+                renderer.render_texture_with_matrix(&tex, matrix);
+                surface.send_frame_done(current_time());
+            }
+        });
+
+        if let Err(err) = res {
+            warn!("Error rendering cursor: {}", err);
+        }
+    }
+}
+
+fn load_theme_cursor(resolution: f64) -> Option<ThemeCursor> {
+    let theme = match XCursorTheme::load_theme(None, (CURSOR_SIZE * resolution) as u32) {
+        Some(theme) => theme,
+        None => {
+            warn!("Failed to load xcursor theme");
+            return None;
+        }
+    };
+    let cursor = match theme.get_cursor(CURSOR_NAME.into()) {
+        Some(cursor) => cursor,
+        None => {
+            warn!("Cursor theme has no {} cursor", CURSOR_NAME);
+            return None;
+        }
+    };
+    let image = cursor.images().into_iter().next()?;
+
+    let texture = unsafe {
+        let texture = GLTexture2D::new();
+        texture.bind();
+        texture.clamp_to_edge_linear();
+        // xcursor images are ARGB32, just like cairo surfaces
+        texture.load_image(image.width as i32, image.height as i32, image.buffer);
+        texture
+    };
+
+    Some(ThemeCursor {
+        texture,
+        size: (
+            image.width as f64 / resolution,
+            image.height as f64 / resolution,
+        ),
+        hotspot: (
+            image.hotspot_x as f64 / resolution,
+            image.hotspot_y as f64 / resolution,
+        ),
+    })
+}
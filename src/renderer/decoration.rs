@@ -0,0 +1,123 @@
+use crate::renderer::cairo_tex::CairoTex;
+use cairo::LineCap;
+use cgmath::{Matrix4, Vector2};
+
+/// Height of the title strip above decorated views.
+pub const TITLE_HEIGHT: f64 = 24.;
+
+/// Size of the area around each button that can be clicked or tapped.
+const BUTTON_SIZE: f64 = 24.;
+
+/// A part of the title strip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationPart {
+    Title,
+    CloseButton,
+    FullscreenButton,
+}
+
+/// A title strip with the window title and buttons to close the window or make it fullscreen.
+#[derive(Debug)]
+pub struct Decoration {
+    inner: CairoTex,
+    title: String,
+    fullscreen_button: bool,
+}
+
+impl Decoration {
+    pub fn new(width: f64, resolution: f64, title: &str, fullscreen_button: bool) -> Decoration {
+        let decoration = Decoration {
+            inner: CairoTex::new(width, TITLE_HEIGHT, resolution),
+            title: title.into(),
+            fullscreen_button,
+        };
+        decoration.draw();
+        decoration
+    }
+
+    /// Returns true if the strip was drawn for the given parameters.
+    pub fn is_current(
+        &self,
+        width: f64,
+        resolution: f64,
+        title: &str,
+        fullscreen_button: bool,
+    ) -> bool {
+        self.inner.size().0 == width
+            && self.inner.resolution() == resolution
+            && self.title == title
+            && self.fullscreen_button == fullscreen_button
+    }
+
+    /// Returns the part of the strip at a point relative to its top left corner.
+    pub fn part_at(width: f64, point: Vector2<f64>, fullscreen_button: bool) -> DecorationPart {
+        if point.x < BUTTON_SIZE {
+            DecorationPart::CloseButton
+        } else if fullscreen_button && point.x >= width - BUTTON_SIZE {
+            DecorationPart::FullscreenButton
+        } else {
+            DecorationPart::Title
+        }
+    }
+
+    fn draw(&self) {
+        let ctx = self.inner.context();
+        let (width, height) = self.inner.size();
+
+        self.inner.clear();
+
+        ctx.set_source_rgba(0.15, 0.15, 0.15, 1.);
+        ctx.rectangle(0., 0., width, height);
+        ctx.fill();
+
+        ctx.set_source_rgba(1., 1., 1., 0.8);
+        ctx.set_line_cap(LineCap::Round);
+        ctx.set_line_width(1.5);
+
+        // close button: a cross
+        let center = BUTTON_SIZE / 2.;
+        let arm = 4.;
+        ctx.new_path();
+        ctx.move_to(center - arm, height / 2. - arm);
+        ctx.line_to(center + arm, height / 2. + arm);
+        ctx.move_to(center + arm, height / 2. - arm);
+        ctx.line_to(center - arm, height / 2. + arm);
+        ctx.stroke();
+
+        // fullscreen button: two arrowheads pointing at opposite corners
+        if self.fullscreen_button {
+            let center = width - BUTTON_SIZE / 2.;
+            ctx.new_path();
+            ctx.move_to(center - arm, height / 2. - arm / 2.);
+            ctx.line_to(center - arm, height / 2. - arm);
+            ctx.line_to(center - arm / 2., height / 2. - arm);
+            ctx.move_to(center + arm, height / 2. + arm / 2.);
+            ctx.line_to(center + arm, height / 2. + arm);
+            ctx.line_to(center + arm / 2., height / 2. + arm);
+            ctx.stroke();
+        }
+
+        ctx.set_font_size(12.);
+        let max_width = width - 2. * BUTTON_SIZE - 16.;
+        let mut title = self.title.clone();
+        while !title.is_empty() && ctx.text_extents(&title).width > max_width {
+            title.pop();
+        }
+        if title.len() < self.title.len() {
+            title.push('…');
+        }
+        let font_extents = ctx.font_extents();
+        let text_extents = ctx.text_extents(&title);
+        ctx.move_to(
+            (width - text_extents.width) / 2.,
+            height / 2. - font_extents.descent + font_extents.height / 2.,
+        );
+        ctx.show_text(&title);
+
+        self.inner.commit();
+    }
+
+    pub fn render(&self, matrix: Matrix4<f32>, x: f64, y: f64, scale: f64) {
+        self.inner.render(matrix, x, y, scale);
+    }
+}
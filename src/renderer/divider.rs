@@ -0,0 +1,49 @@
+use crate::renderer::cairo_tex::CairoTex;
+use crate::split::DIVIDER_WIDTH;
+use cairo::LineCap;
+use cgmath::Matrix4;
+
+/// The grabber drawn in the middle of the Split View divider.
+#[derive(Debug)]
+pub struct DividerHandle {
+    inner: CairoTex,
+}
+
+impl DividerHandle {
+    pub fn new(height: f64, resolution: f64) -> DividerHandle {
+        let handle = DividerHandle {
+            inner: CairoTex::new(DIVIDER_WIDTH, height, resolution),
+        };
+        handle.draw();
+        handle
+    }
+
+    /// Returns the resolution that the handle was drawn at.
+    pub fn resolution(&self) -> f64 {
+        self.inner.resolution()
+    }
+
+    fn draw(&self) {
+        let ctx = self.inner.context();
+        let (width, height) = self.inner.size();
+
+        self.inner.clear();
+
+        let phi = 1.618;
+        let indicator_height = height / (phi * phi * phi * phi);
+
+        ctx.set_source_rgba(1., 1., 1., 0.5);
+        ctx.set_line_cap(LineCap::Round);
+        ctx.set_line_width(3.);
+        ctx.new_path();
+        ctx.move_to(width / 2., height / 2. - indicator_height / 2.);
+        ctx.line_to(width / 2., height / 2. + indicator_height / 2.);
+        ctx.stroke();
+
+        self.inner.commit();
+    }
+
+    pub fn render(&self, matrix: Matrix4<f32>, x: f64, y: f64, scale: f64) {
+        self.inner.render(matrix, x, y, scale);
+    }
+}
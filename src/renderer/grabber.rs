@@ -0,0 +1,60 @@
+use crate::renderer::cairo_tex::CairoTex;
+use cairo::LineCap;
+use cgmath::Matrix4;
+use std::f64::consts::PI;
+
+/// The strip at the top of the Slide Over panel that it can be dragged by.
+#[derive(Debug)]
+pub struct Grabber {
+    inner: CairoTex,
+}
+
+impl Grabber {
+    pub fn new(width: f64, height: f64, resolution: f64) -> Grabber {
+        let grabber = Grabber {
+            inner: CairoTex::new(width, height, resolution),
+        };
+        grabber.draw();
+        grabber
+    }
+
+    /// Returns the resolution that the grabber was drawn at.
+    pub fn resolution(&self) -> f64 {
+        self.inner.resolution()
+    }
+
+    fn draw(&self) {
+        let ctx = self.inner.context();
+        let (width, height) = self.inner.size();
+
+        self.inner.clear();
+
+        // background with rounded top corners
+        let radius = height / 2.;
+        ctx.set_source_rgba(0.1, 0.1, 0.1, 0.9);
+        ctx.new_path();
+        ctx.arc(radius, radius, radius, PI, PI * 1.5);
+        ctx.arc(width - radius, radius, radius, PI * 1.5, 0.);
+        ctx.line_to(width, height);
+        ctx.line_to(0., height);
+        ctx.close_path();
+        ctx.fill();
+
+        let phi = 1.618;
+        let indicator_width = width / (phi * phi * phi * phi);
+
+        ctx.set_source_rgba(1., 1., 1., 0.5);
+        ctx.set_line_cap(LineCap::Round);
+        ctx.set_line_width(3.);
+        ctx.new_path();
+        ctx.move_to(width / 2. - indicator_width / 2., height / 2.);
+        ctx.line_to(width / 2. + indicator_width / 2., height / 2.);
+        ctx.stroke();
+
+        self.inner.commit();
+    }
+
+    pub fn render(&self, matrix: Matrix4<f32>, x: f64, y: f64) {
+        self.inner.render(matrix, x, y, 1.);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::event::Event;
 use crate::renderer::cairo_tex::CairoTex;
 use crate::spring::{RealTimeSpring, Spring};
-use cairo::LineCap;
+use cairo::{LineCap, Rectangle};
 use cgmath::Matrix4;
 use std::time::Instant;
 
@@ -10,23 +10,67 @@ pub struct HomeBar {
     screen_height: f64,
     inner: CairoTex,
     touch_down_offset: f64,
+    touch_down_x: f64,
     prev_touch_time: Instant,
     touch_down: bool,
+    drag_axis: DragAxis,
     y_pos: RealTimeSpring,
 }
 
-const HOME_BAR_REGION_HEIGHT: f64 = 18.;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DragAxis {
+    Undecided,
+    Horizontal,
+    Vertical,
+}
+
+pub const HOME_BAR_REGION_HEIGHT: f64 = 18.;
+
+/// How far the home bar needs to be dragged up to count as a swipe (this is after the rubber band
+/// effect).
+const SWIPE_UP_DISTANCE: f64 = 10.;
+
+/// How fast the home bar needs to be flicked up to count as a swipe.
+const SWIPE_UP_VELOCITY: f64 = 200.;
+
+/// How far the home bar needs to be dragged before deciding whether the drag is horizontal.
+const AXIS_LOCK_DISTANCE: f64 = 8.;
+
+/// A gesture performed on the home bar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HomeBarGesture {
+    /// The home bar was swiped up.
+    SwipeUp,
+    /// The home bar is being dragged sideways by the given distance.
+    HorizontalDrag { offset: f64 },
+    /// A sideways drag ended.
+    HorizontalEnd,
+    /// A sideways drag was cancelled.
+    HorizontalCancel,
+}
 
 impl HomeBar {
     pub fn new(screen_width: f64, screen_height: f64, resolution: f64) -> HomeBar {
-        HomeBar {
+        let home_bar = HomeBar {
             screen_height,
             inner: CairoTex::new(screen_width, HOME_BAR_REGION_HEIGHT, resolution),
             touch_down_offset: 0.,
+            touch_down_x: 0.,
             prev_touch_time: Instant::now(),
             touch_down: false,
+            drag_axis: DragAxis::Undecided,
             y_pos: RealTimeSpring::new(Spring::new(1., 1.)),
-        }
+        };
+        // the indicator never changes, so it’s only drawn once
+        home_bar.draw();
+        home_bar
+    }
+
+    /// Returns true if the home bar was created for the given screen size and resolution.
+    pub fn is_current(&self, screen_width: f64, screen_height: f64, resolution: f64) -> bool {
+        self.inner.size().0 == screen_width
+            && self.screen_height == screen_height
+            && self.inner.resolution() == resolution
     }
 
     fn indicator_size(&self) -> (f64, f64) {
@@ -67,9 +111,15 @@ impl HomeBar {
     }
 
     pub fn render(&mut self, matrix: Matrix4<f32>) {
-        self.draw();
         let y_pos = if !self.touch_down {
-            self.y_pos.update()
+            // the home bar springs back down into its place from wherever it was dragged to
+            let top = self.screen_height - HOME_BAR_REGION_HEIGHT + self.y_pos.spring.value.min(0.);
+            self.y_pos.update_in(Rectangle {
+                x: 0.,
+                y: top,
+                width: self.inner.size().0,
+                height: self.screen_height - top,
+            })
         } else {
             self.y_pos.update_time();
             self.y_pos.spring.value
@@ -86,28 +136,67 @@ impl HomeBar {
         -(-(y - self.screen_height) / 2.).sqrt() * 2. - self.screen_height
     }
 
-    pub fn handle_event(&mut self, event: Event) {
+    /// Handles a touch event and returns the gesture that was completed, if any.
+    pub fn handle_event(&mut self, event: Event) -> Option<HomeBarGesture> {
         match event {
             Event::TouchDown { location, .. } => {
                 self.touch_down_offset = self.map_touch_y(location.y) - self.y_pos.spring.value;
+                self.touch_down_x = location.x;
                 self.y_pos.spring.velocity = 0.;
                 self.prev_touch_time = Instant::now();
                 self.touch_down = true;
+                self.drag_axis = DragAxis::Undecided;
             }
             Event::TouchMotion { location, .. } => {
-                let prev_value = self.y_pos.spring.value;
-                self.y_pos.spring.value = self.map_touch_y(location.y) - self.touch_down_offset;
-
-                let delta = self.y_pos.spring.value - prev_value;
-                let elapsed = self.prev_touch_time.elapsed();
-                let elapsed_secs = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
-                self.prev_touch_time = Instant::now();
-                self.y_pos.spring.velocity = delta / elapsed_secs;
+                let offset_x = location.x - self.touch_down_x;
+                let offset_y = self.map_touch_y(location.y) - self.touch_down_offset;
+
+                if self.drag_axis == DragAxis::Undecided {
+                    // offset_y is rubber-banded, so compare with the actual distance
+                    let (_, height) = self.inner.size();
+                    let distance_y = self.screen_height - height / 2. - location.y;
+                    if offset_x.abs() > AXIS_LOCK_DISTANCE || distance_y > AXIS_LOCK_DISTANCE {
+                        self.drag_axis = if offset_x.abs() > distance_y {
+                            DragAxis::Horizontal
+                        } else {
+                            DragAxis::Vertical
+                        };
+                    }
+                }
+
+                match self.drag_axis {
+                    DragAxis::Horizontal => {
+                        return Some(HomeBarGesture::HorizontalDrag { offset: offset_x });
+                    }
+                    DragAxis::Vertical => {
+                        self.y_pos.drag_to(offset_y, &mut self.prev_touch_time);
+                    }
+                    DragAxis::Undecided => (),
+                }
             }
             Event::TouchUp { .. } => {
                 self.touch_down = false;
+                match self.drag_axis {
+                    DragAxis::Horizontal => return Some(HomeBarGesture::HorizontalEnd),
+                    DragAxis::Vertical => {
+                        let spring = &self.y_pos.spring;
+                        if spring.value < -SWIPE_UP_DISTANCE
+                            || spring.velocity < -SWIPE_UP_VELOCITY
+                        {
+                            return Some(HomeBarGesture::SwipeUp);
+                        }
+                    }
+                    DragAxis::Undecided => (),
+                }
+            }
+            Event::TouchCancel { .. } => {
+                self.touch_down = false;
+                if self.drag_axis == DragAxis::Horizontal {
+                    return Some(HomeBarGesture::HorizontalCancel);
+                }
             }
             _ => (),
         }
+        None
     }
 }
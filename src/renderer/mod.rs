@@ -1,35 +1,74 @@
 use crate::event::Event;
-use crate::server::Server;
-use crate::status::StatusBar;
-use cgmath::Matrix4;
+use crate::layer_shell::Layer;
+use crate::osk::OnScreenKeyboard;
+use crate::output_layout::OutputRole;
+use crate::server::{Server, SpaceID};
+use crate::space::SpaceTransform;
+use crate::status::{StatusBar, STATUS_HEIGHT};
+use crate::utils::contains_rect;
+use cairo::Rectangle;
+use cgmath::{Matrix4, Vector2};
+use wlroots::wlroots_sys::wl_output_transform;
+use wlroots::wlroots_sys::wl_output_transform::*;
 
 mod box_render;
 mod cairo_tex;
+mod cursor;
+mod decoration;
+mod divider;
+mod grabber;
 mod home_bar;
 
 pub use self::box_render::*;
 pub use self::cairo_tex::*;
+pub use self::cursor::*;
+pub use self::decoration::*;
+pub use self::divider::*;
+pub use self::grabber::*;
 pub use self::home_bar::*;
 
-// TODO: screen rotation with iio-sensor-proxy
-
 #[derive(Debug)]
 pub struct Renderer {
     width: f64,
     height: f64,
     resolution: f64,
+    /// The output transform that width and height are for.
+    transform: wl_output_transform,
+    role: OutputRole,
+    /// The output’s projection matrix, rotated while the screen rotation is animating.
+    projection: [f32; 9],
+    /// The part of the screen that isn’t reserved by layer surfaces.
+    usable_area: Rectangle,
     status_bar: StatusBar,
-    home_bar_captured_events: bool,
+    /// The built-in on-screen keyboard.
+    keyboard: OnScreenKeyboard,
+    cursor: Cursor,
 }
 
 impl Renderer {
-    pub fn new(width: f64, height: f64, resolution: f64) -> Renderer {
+    pub fn new(
+        width: f64,
+        height: f64,
+        resolution: f64,
+        transform: wl_output_transform,
+        role: OutputRole,
+    ) -> Renderer {
         Renderer {
             width,
             height,
             resolution,
+            transform,
+            role,
+            projection: [1., 0., 0., 0., 1., 0., 0., 0., 1.],
+            usable_area: Rectangle {
+                x: 0.,
+                y: 0.,
+                width,
+                height,
+            },
             status_bar: StatusBar::new(width, resolution),
-            home_bar_captured_events: false,
+            keyboard: OnScreenKeyboard::new(width, height, resolution),
+            cursor: Cursor::new(resolution),
         }
     }
 
@@ -41,10 +80,91 @@ impl Renderer {
         self.resolution
     }
 
+    pub fn transform(&self) -> wl_output_transform {
+        self.transform
+    }
+
+    pub fn role(&self) -> OutputRole {
+        self.role
+    }
+
+    /// Returns the projection matrix that surfaces are rendered with.
+    pub fn projection(&self) -> [f32; 9] {
+        self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: [f32; 9]) {
+        self.projection = projection;
+    }
+
+    /// Returns the whole screen, which fullscreen views fill.
+    pub fn screen_area(&self) -> Rectangle {
+        Rectangle {
+            x: 0.,
+            y: 0.,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    pub fn usable_area(&self) -> Rectangle {
+        self.usable_area
+    }
+
+    /// Sets the part of the screen that isn’t reserved by layer surfaces.
+    pub fn set_usable_area(&mut self, area: Rectangle) {
+        self.usable_area = area;
+    }
+
+    pub fn keyboard(&self) -> &OnScreenKeyboard {
+        &self.keyboard
+    }
+
+    pub fn keyboard_mut(&mut self) -> &mut OnScreenKeyboard {
+        &mut self.keyboard
+    }
+
+    /// Returns everything between the status bar and the home bar, which extended outputs
+    /// don’t have.
+    pub fn content_area(&self) -> Rectangle {
+        let home_bar_height = match self.role {
+            OutputRole::Extended => 0.,
+            _ => HOME_BAR_REGION_HEIGHT,
+        };
+        Rectangle {
+            x: 0.,
+            y: STATUS_HEIGHT,
+            width: self.width,
+            height: self.height - STATUS_HEIGHT - home_bar_height,
+        }
+    }
+
+    /// Returns the area that views should fill, which is the content area minus what’s reserved
+    /// by layer surfaces.
+    pub fn view_area(&self) -> Rectangle {
+        let content = self.content_area();
+        let usable = self.usable_area;
+        let top = usable.y.max(content.y);
+        let bottom = (usable.y + usable.height).min(content.y + content.height);
+        Rectangle {
+            x: usable.x,
+            y: top,
+            width: usable.width,
+            height: (bottom - top).max(0.),
+        }
+    }
+
     pub fn map_event(&self, event: &mut Event) {
         if let Some(location) = event.location_mut() {
-            location.x *= self.width;
-            location.y *= self.height;
+            // absolute locations are relative to the panel, which doesn’t rotate with the content
+            let (x, y) = match self.transform {
+                WL_OUTPUT_TRANSFORM_90 => (1. - location.y, location.x),
+                WL_OUTPUT_TRANSFORM_180 => (1. - location.x, 1. - location.y),
+                WL_OUTPUT_TRANSFORM_270 => (location.y, 1. - location.x),
+                _ => (location.x, location.y),
+            };
+            location.x = x * self.width;
+            location.y = y * self.height;
         }
     }
 
@@ -56,8 +176,85 @@ impl Renderer {
     ) {
         renderer.clear([0.3, 0.3, 0.3, 1.]);
 
-        self.render_spaces(matrix, renderer, server);
+        let chrome_hidden = server.chrome_hidden();
+
+        self.render_layer(Layer::Background, matrix, renderer, server, chrome_hidden);
+        self.render_layer(Layer::Bottom, matrix, renderer, server, chrome_hidden);
+        self.render_spaces(matrix, renderer, server, chrome_hidden);
+        if !server.overview.is_visible() {
+            if let Some(ref mut slide_over) = server.slide_over {
+                slide_over.render(matrix, renderer, self);
+            }
+            self.keyboard.render(matrix);
+            if let Some(ref mut unmanaged) = server.unmanaged {
+                unmanaged.render(matrix, renderer, self);
+            }
+        }
+        // held keys repeat
+        if let Some(key) = self.keyboard.update() {
+            server.type_key(key);
+        }
+        self.render_layer(Layer::Top, matrix, renderer, server, chrome_hidden);
+        if !chrome_hidden {
+            self.status_bar.render(matrix);
+        }
+        self.render_layer(Layer::Overlay, matrix, renderer, server, chrome_hidden);
+
+        let on_extended_output = server
+            .outputs
+            .extended_output_at(server.cursor_location())
+            .is_some();
+        if server.cursor_visible() && !on_extended_output {
+            self.cursor.render(
+                matrix,
+                self.projection,
+                server.cursor_location(),
+                server.client_cursor.as_ref(),
+                renderer,
+            );
+        }
+    }
+
+    /// Renders an extended output, which shows the front space of the ones moved to it, and the
+    /// cursor if it’s on the output.
+    pub fn render_extended(
+        &mut self,
+        matrix: Matrix4<f32>,
+        renderer: &mut wlroots::Renderer,
+        server: &mut Server,
+        front_space: Option<SpaceID>,
+        cursor: Option<Vector2<f64>>,
+    ) {
+        renderer.clear([0.3, 0.3, 0.3, 1.]);
+
+        if let Some(space) = front_space.and_then(|id| server.space_mut(id)) {
+            space.render(matrix, SpaceTransform::identity(), renderer, self);
+            space.render_divider(matrix, SpaceTransform::identity(), self);
+        }
         self.status_bar.render(matrix);
+
+        if let Some(location) = cursor.filter(|_| server.cursor_visible()) {
+            self.cursor.render(
+                matrix,
+                self.projection,
+                location,
+                server.client_cursor.as_ref(),
+                renderer,
+            );
+        }
+    }
+
+    fn render_layer(
+        &self,
+        layer: Layer,
+        matrix: Matrix4<f32>,
+        renderer: &mut wlroots::Renderer,
+        server: &mut Server,
+        chrome_hidden: bool,
+    ) {
+        if let Some(ref mut layers) = server.layers {
+            layers.render(layer, matrix, renderer, self, chrome_hidden);
+        }
     }
 
     fn render_spaces(
@@ -65,13 +262,152 @@ impl Renderer {
         matrix: Matrix4<f32>,
         renderer: &mut wlroots::Renderer,
         server: &mut Server,
+        chrome_hidden: bool,
     ) {
-        let space_order: Vec<_> = server.space_order().iter().map(|x| *x).collect();
-        for space_id in space_order.iter().rev() {
-            server
-                .space_mut(*space_id)
-                .unwrap()
-                .render(matrix, self, renderer);
+        server.overview.update();
+
+        // front to back
+        let space_order: Vec<_> = server.space_order().iter().rev().map(|x| *x).collect();
+
+        if server.overview.is_visible() {
+            let dims = self.dimensions();
+            let transforms: Vec<_> = space_order
+                .iter()
+                .enumerate()
+                .map(|(index, id)| server.overview.space_transform(index, *id, dims))
+                .collect();
+            let opaque_cards: Vec<_> = space_order
+                .iter()
+                .zip(&transforms)
+                .map(|(id, transform)| {
+                    let area = server.space(*id).and_then(|space| space.opaque_area());
+                    area.map(|area| transform.apply_rect(area))
+                })
+                .collect();
+
+            // draw back to front so that cards overlap correctly while animating
+            for (index, space_id) in space_order.iter().enumerate().rev() {
+                let transform = transforms[index];
+                // cards that are entirely behind an opaque card in front of them can’t be seen
+                let card = server
+                    .space(*space_id)
+                    .and_then(|space| space.screen_area())
+                    .map(|area| transform.apply_rect(area));
+                let covered = card.map_or(false, |card| {
+                    opaque_cards[..index]
+                        .iter()
+                        .flatten()
+                        .any(|front| contains_rect(*front, card))
+                });
+                if covered {
+                    continue;
+                }
+                let space = server.space_mut(*space_id).unwrap();
+                space.render(matrix, transform, renderer, self);
+                space.render_divider(matrix, transform, self);
+            }
+        } else if let Some(space_id) = space_order.first() {
+            server.switcher.update();
+            let front_transform = if server.switcher.is_active() {
+                if let Some((neighbor_id, transform)) =
+                    server.switcher.neighbor_transform(self.width)
+                {
+                    if let Some(neighbor) = server.space_mut(neighbor_id) {
+                        neighbor.render(matrix, transform, renderer, self);
+                        neighbor.render_divider(matrix, transform, self);
+                    }
+                }
+                server.switcher.front_transform()
+            } else {
+                SpaceTransform::identity()
+            };
+
+            let space = server.space_mut(*space_id).unwrap();
+            space.render(matrix, front_transform, renderer, self);
+            space.render_divider(matrix, front_transform, self);
+            space.render_home_bar(matrix, self, chrome_hidden);
+        }
+    }
+}
+
+/// Returns the box in window coordinates, which start at the bottom left of the buffer, that
+/// contains an area in logical coordinates drawn with a row-major projection matrix like the
+/// ones wlroots uses.
+pub fn scissor_box(
+    projection: [f32; 9],
+    resolution: f64,
+    area: Rectangle,
+    (buffer_width, buffer_height): (i32, i32),
+) -> (i32, i32, i32, i32) {
+    let (buffer_width, buffer_height) = (buffer_width as f64, buffer_height as f64);
+    let p: Vec<f64> = projection.iter().map(|value| f64::from(*value)).collect();
+    let right = area.x + area.width;
+    let bottom = area.y + area.height;
+    let corners = [(area.x, area.y), (right, area.y), (area.x, bottom), (right, bottom)];
+
+    let mut min = Vector2::new(buffer_width, buffer_height);
+    let mut max = Vector2::new(0., 0.);
+    for &(x, y) in &corners {
+        let (x, y) = (x * resolution, y * resolution);
+        let window = Vector2::new(
+            (p[0] * x + p[1] * y + p[2] + 1.) / 2. * buffer_width,
+            (p[3] * x + p[4] * y + p[5] + 1.) / 2. * buffer_height,
+        );
+        min.x = min.x.min(window.x);
+        min.y = min.y.min(window.y);
+        max.x = max.x.max(window.x);
+        max.y = max.y.max(window.y);
+    }
+
+    // rounded outwards with a pixel to spare, since edges are antialiased
+    let x = (min.x.floor() - 1.).max(0.);
+    let y = (min.y.floor() - 1.).max(0.);
+    let right = (max.x.ceil() + 1.).min(buffer_width);
+    let top = (max.y.ceil() + 1.).min(buffer_height);
+    (
+        x as i32,
+        y as i32,
+        (right - x).max(0.) as i32,
+        (top - y).max(0.) as i32,
+    )
+}
+
+/// Scales a row-major projection matrix like the ones wlroots uses and then moves it by an
+/// offset in output pixels.
+pub fn fit_projection(projection: [f32; 9], offset: Vector2<f64>, scale: f64) -> [f32; 9] {
+    let (x, y, scale) = (offset.x as f32, offset.y as f32, scale as f32);
+    let mut result = projection;
+    for row in 0..3 {
+        result[row * 3] *= scale;
+        result[row * 3 + 1] *= scale;
+        result[row * 3 + 2] += projection[row * 3] * x + projection[row * 3 + 1] * y;
+    }
+    result
+}
+
+/// Rotates a row-major projection matrix like the ones wlroots uses by an angle around a point
+/// in output pixels.
+pub fn rotate_projection(projection: [f32; 9], angle: f64, center: Vector2<f64>) -> [f32; 9] {
+    let (sin, cos) = angle.sin_cos();
+    let rotation = [
+        cos,
+        -sin,
+        center.x - cos * center.x + sin * center.y,
+        sin,
+        cos,
+        center.y - sin * center.x - cos * center.y,
+        0.,
+        0.,
+        1.,
+    ];
+    let mut result = [0.; 9];
+    for row in 0..3 {
+        for col in 0..3 {
+            let value: f64 = (0..3)
+                .map(|i| f64::from(projection[row * 3 + i]) * rotation[i * 3 + col])
+                .sum();
+            result[row * 3 + col] = value as f32;
         }
     }
+    result
 }
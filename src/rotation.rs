@@ -0,0 +1,407 @@
+//! Automatic screen rotation with iio-sensor-proxy, which reports the orientation of the
+//! accelerometer on the system bus.
+//!
+//! The D-Bus connection is watched by the Wayland event loop, and the output is rotated on the
+//! next frame after the orientation changes.
+
+use crate::spring::{RealTimeSpring, Spring};
+use crate::utils::with_server;
+use dbus::arg::{Dict, Iter, Variant};
+use dbus::{BusType, Connection, ConnectionItem, Message};
+use std::f64::consts::PI;
+use std::fmt;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use wlroots::wlroots_sys::wl_output_transform::*;
+use wlroots::wlroots_sys::*;
+
+const SERVICE: &str = "net.hadess.SensorProxy";
+const PATH: &str = "/net/hadess/SensorProxy";
+const INTERFACE: &str = "net.hadess.SensorProxy";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// How long to wait for iio-sensor-proxy to reply, in milliseconds.
+const TIMEOUT: i32 = 1000;
+
+/// From `wayland-server-core.h`.
+const WL_EVENT_READABLE: u32 = 0x01;
+
+/// Which edge of the screen is up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Normal,
+    BottomUp,
+    LeftUp,
+    RightUp,
+}
+
+impl Orientation {
+    /// Parses an orientation as reported by iio-sensor-proxy, which is `undefined` if the
+    /// device is lying flat or has no accelerometer.
+    pub fn parse(name: &str) -> Option<Orientation> {
+        match name {
+            "normal" => Some(Orientation::Normal),
+            "bottom-up" => Some(Orientation::BottomUp),
+            "left-up" => Some(Orientation::LeftUp),
+            "right-up" => Some(Orientation::RightUp),
+            _ => None,
+        }
+    }
+
+    /// Returns the output transform that keeps the content upright.
+    pub fn transform(self) -> wl_output_transform {
+        match self {
+            Orientation::Normal => WL_OUTPUT_TRANSFORM_NORMAL,
+            Orientation::LeftUp => WL_OUTPUT_TRANSFORM_90,
+            Orientation::BottomUp => WL_OUTPUT_TRANSFORM_180,
+            Orientation::RightUp => WL_OUTPUT_TRANSFORM_270,
+        }
+    }
+
+    /// Returns how far the content is rotated in this orientation, in radians.
+    fn angle(self) -> f64 {
+        match self {
+            Orientation::Normal => 0.,
+            Orientation::LeftUp => PI / 2.,
+            Orientation::BottomUp => PI,
+            Orientation::RightUp => PI * 1.5,
+        }
+    }
+}
+
+fn method_call(interface: &str, method: &str) -> Result<Message, dbus::Error> {
+    Message::new_method_call(SERVICE, PATH, interface, method)
+        .map_err(|err| dbus::Error::new_custom("org.freedesktop.DBus.Error.Failed", &err))
+}
+
+/// Returns the orientation if the signal says it changed, which is `Some(None)` if it became
+/// undefined.
+fn changed_orientation(signal: &Message) -> Option<Option<Orientation>> {
+    let (interface, changed): (&str, Dict<&str, Variant<Iter>, Iter>) = signal.read2().ok()?;
+    if interface != INTERFACE {
+        return None;
+    }
+    for (name, value) in changed {
+        if name == "AccelerometerOrientation" {
+            let Variant(mut value) = value;
+            return Some(value.get::<&str>().and_then(Orientation::parse));
+        }
+    }
+    None
+}
+
+/// A connection to iio-sensor-proxy with the accelerometer claimed.
+pub struct SensorProxy {
+    conn: Connection,
+}
+
+impl fmt::Debug for SensorProxy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SensorProxy")
+    }
+}
+
+impl SensorProxy {
+    /// Connects to iio-sensor-proxy on the system bus.
+    pub fn connect() -> Result<SensorProxy, dbus::Error> {
+        SensorProxy::with_connection(Connection::get_private(BusType::System)?)
+    }
+
+    /// Claims the accelerometer of the iio-sensor-proxy on the given bus.
+    pub fn with_connection(conn: Connection) -> Result<SensorProxy, dbus::Error> {
+        conn.add_match(&format!(
+            "type='signal',sender='{}',path='{}',interface='{}',member='PropertiesChanged'",
+            SERVICE, PATH, PROPERTIES_INTERFACE
+        ))?;
+        conn.send_with_reply_and_block(method_call(INTERFACE, "ClaimAccelerometer")?, TIMEOUT)?;
+        Ok(SensorProxy { conn })
+    }
+
+    /// Asks iio-sensor-proxy for the current orientation.
+    pub fn orientation(&self) -> Result<Option<Orientation>, dbus::Error> {
+        let get = method_call(PROPERTIES_INTERFACE, "Get")?
+            .append2(INTERFACE, "AccelerometerOrientation");
+        let reply = self.conn.send_with_reply_and_block(get, TIMEOUT)?;
+        let name: Variant<String> = reply.read1()?;
+        Ok(Orientation::parse(&name.0))
+    }
+
+    /// Handles the messages that arrive within the timeout and returns the orientation if it
+    /// changed.
+    ///
+    /// The orientation is read from the signals, since asking for it would block the event loop.
+    pub fn poll(&self, timeout_ms: u32) -> Option<Orientation> {
+        let mut orientation = None;
+        for item in self.conn.incoming(timeout_ms) {
+            if let ConnectionItem::Signal(signal) = item {
+                if let Some(changed) = changed_orientation(&signal) {
+                    orientation = changed;
+                }
+            }
+        }
+        orientation
+    }
+
+    /// Calls `poll` whenever the connection has messages.
+    unsafe fn watch(&self, event_loop: *mut wl_event_loop) {
+        unsafe extern "C" fn readable_notify(_: c_int, _: u32, _: *mut c_void) -> c_int {
+            with_server("sensor", |server| {
+                server.rotation.poll_sensor();
+                if server.rotation.has_pending() {
+                    server.outputs.damage_whole();
+                }
+            });
+            0
+        }
+
+        for watch in self.conn.watch_fds() {
+            if watch.readable() {
+                wl_event_loop_add_fd(
+                    event_loop,
+                    watch.fd(),
+                    WL_EVENT_READABLE,
+                    readable_notify,
+                    ptr::null_mut(),
+                );
+            }
+        }
+    }
+}
+
+/// Decides which way the screen should be rotated and animates rotations.
+#[derive(Debug)]
+pub struct Rotation {
+    sensor: Option<SensorProxy>,
+    /// The most recent orientation reported by the sensor.
+    orientation: Orientation,
+    /// The orientation the output was last rotated to.
+    applied: Orientation,
+    locked: bool,
+    /// How far the content is still rotated away from the applied orientation, in radians.
+    angle: RealTimeSpring,
+}
+
+impl Rotation {
+    pub fn new() -> Rotation {
+        Rotation {
+            sensor: None,
+            orientation: Orientation::Normal,
+            applied: Orientation::Normal,
+            locked: false,
+            angle: RealTimeSpring::new(Spring::new(1., 0.5)),
+        }
+    }
+
+    /// Starts following the sensor, and watches it with the event loop of the given display.
+    pub unsafe fn set_sensor(&mut self, sensor: SensorProxy, display: *mut wl_display) {
+        match sensor.orientation() {
+            Ok(Some(orientation)) => self.orientation = orientation,
+            Ok(None) => (),
+            Err(err) => warn!("Failed to get orientation: {}", err),
+        }
+        sensor.watch(wl_display_get_event_loop(display));
+        self.sensor = Some(sensor);
+        // signals that arrived during the blocking calls are already read from the socket, so
+        // the event loop won’t wake up for them
+        self.poll_sensor();
+    }
+
+    /// Handles pending messages from the sensor.
+    pub fn poll_sensor(&mut self) {
+        let orientation = self.sensor.as_ref().and_then(|sensor| sensor.poll(0));
+        if let Some(orientation) = orientation {
+            self.orientation = orientation;
+        }
+    }
+
+    /// Sets the orientation as if the sensor had reported it.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Keeps the current orientation until unlocked, which rotates to the latest orientation.
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    /// Returns true if the output should be rotated on the next frame.
+    pub fn has_pending(&self) -> bool {
+        !self.locked && self.orientation != self.applied
+    }
+
+    /// Returns the orientation to rotate the output to if it should change, and starts
+    /// animating from the previous one.
+    pub fn take_pending(&mut self) -> Option<Orientation> {
+        if !self.has_pending() {
+            return None;
+        }
+
+        // turn the shorter way, continuing from wherever a previous animation is
+        let mut delta = self.applied.angle() - self.orientation.angle();
+        if delta > PI {
+            delta -= 2. * PI;
+        } else if delta < -PI {
+            delta += 2. * PI;
+        }
+        self.angle.update();
+        self.angle.spring.value += delta;
+
+        self.applied = self.orientation;
+        Some(self.orientation)
+    }
+
+    /// Returns how far the content should be rotated for the animation, in radians.
+    pub fn angle(&mut self) -> f64 {
+        self.angle.update()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    /// A private session bus that’s shut down when dropped.
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        fn start() -> Option<PrivateBus> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(&["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.as_mut().unwrap())
+                .read_line(&mut address)
+                .ok()?;
+            Some(PrivateBus {
+                daemon,
+                address: address.trim().into(),
+            })
+        }
+
+        fn connect(&self) -> Connection {
+            let conn = Connection::open_private(&self.address).unwrap();
+            conn.register().unwrap();
+            conn
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// Runs a fake iio-sensor-proxy that reports the orientations sent through the channel.
+    fn mock_sensor_proxy(bus: &PrivateBus) -> mpsc::Sender<&'static str> {
+        let (sender, receiver) = mpsc::channel::<&'static str>();
+        let (ready_sender, ready_receiver) = mpsc::channel();
+        let address = bus.address.clone();
+
+        thread::spawn(move || {
+            let conn = Connection::open_private(&address).unwrap();
+            conn.register().unwrap();
+            conn.register_name(SERVICE, 0).unwrap();
+            ready_sender.send(()).unwrap();
+
+            let mut orientation = "normal";
+            loop {
+                for item in conn.incoming(10) {
+                    let call = match item {
+                        ConnectionItem::MethodCall(call) => call,
+                        _ => continue,
+                    };
+                    let reply = match call.member().as_ref().map(|m| &**m) {
+                        Some("Get") => call
+                            .method_return()
+                            .append1(Variant(String::from(orientation))),
+                        _ => call.method_return(),
+                    };
+                    conn.send(reply).unwrap();
+                }
+                match receiver.try_recv() {
+                    Ok(next) => {
+                        orientation = next;
+                        let mut changed = HashMap::new();
+                        changed.insert(
+                            String::from("AccelerometerOrientation"),
+                            Variant(String::from(orientation)),
+                        );
+                        let signal =
+                            Message::new_signal(PATH, PROPERTIES_INTERFACE, "PropertiesChanged")
+                                .unwrap()
+                                .append3(INTERFACE, changed, Vec::<String>::new());
+                        conn.send(signal).unwrap();
+                    }
+                    Err(mpsc::TryRecvError::Empty) => (),
+                    Err(mpsc::TryRecvError::Disconnected) => return,
+                }
+            }
+        });
+
+        ready_receiver.recv().unwrap();
+        sender
+    }
+
+    fn poll_until_changed(sensor: &SensorProxy) -> Option<Orientation> {
+        for _ in 0..50 {
+            if let Some(orientation) = sensor.poll(100) {
+                return Some(orientation);
+            }
+        }
+        None
+    }
+
+    // run with `cargo test -- --ignored` where dbus-daemon is installed
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn follows_mock_sensor_proxy() {
+        let bus = PrivateBus::start().expect("dbus-daemon is not available");
+        let orientations = mock_sensor_proxy(&bus);
+
+        let sensor = SensorProxy::with_connection(bus.connect()).unwrap();
+        assert_eq!(sensor.orientation().unwrap(), Some(Orientation::Normal));
+
+        orientations.send("left-up").unwrap();
+        assert_eq!(poll_until_changed(&sensor), Some(Orientation::LeftUp));
+
+        orientations.send("undefined").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(sensor.poll(100), None);
+    }
+
+    #[test]
+    fn lock_keeps_orientation() {
+        let mut rotation = Rotation::new();
+        rotation.set_locked(true);
+        rotation.set_orientation(Orientation::RightUp);
+        assert_eq!(rotation.take_pending(), None);
+
+        rotation.set_locked(false);
+        assert_eq!(rotation.take_pending(), Some(Orientation::RightUp));
+        assert_eq!(rotation.take_pending(), None);
+    }
+
+    #[test]
+    fn rotation_turns_the_shorter_way() {
+        let mut rotation = Rotation::new();
+        rotation.set_orientation(Orientation::RightUp);
+        rotation.take_pending();
+        assert!((rotation.angle.spring.value - PI / 2.).abs() < 1e-9);
+    }
+}
@@ -1,23 +1,77 @@
-use crate::view::ViewID;
+use crate::view::View;
 use cgmath::Vector2;
 use std::collections::HashMap;
+use std::rc::Rc;
 use wlroots::TouchId;
 
 /// Event handling.
 ///
 /// This exists because wlroots uses global-ish state for event handling.
+#[derive(Debug, Default)]
 pub struct SfSeat {
-    kbd_focus: ViewID,
-    ptr_focus: ViewID,
+    /// The view with keyboard focus.
+    pub kbd_focus: Option<Rc<View>>,
     touch_points: HashMap<TouchId, SfTouchPoint>,
 }
 
-struct SfTouchPoint {
-    pos: Vector2<f64>,
-    down: bool,
-    focus: ViewID,
+/// A finger on the screen.
+#[derive(Debug, Clone)]
+pub struct SfTouchPoint {
+    pub pos: Vector2<f64>,
+    pub focus: TouchFocus,
+}
+
+/// Whatever a touch point went down on; it receives all events for that touch point.
+#[derive(Debug, Clone)]
+pub enum TouchFocus {
+    /// The home bar captured the touch point.
+    HomeBar,
+    /// The touch point is typing on the built-in on-screen keyboard.
+    Keyboard,
+    /// The touch point is dragging the Split View divider.
+    Divider,
+    /// A surface of a view (which may be a popup), and the surface’s origin in screen
+    /// coordinates.
+    View(Rc<View>, Vector2<f64>),
+    /// The touch point went down on the top edge while the status bar was hidden, at the given
+    /// height.
+    TopEdge(f64),
+    /// The touch point did not go down on anything.
+    None,
 }
 
 impl SfSeat {
+    pub fn new() -> SfSeat {
+        SfSeat::default()
+    }
+
+    /// Adds a touch point.
+    pub fn touch_down(&mut self, id: TouchId, pos: Vector2<f64>, focus: TouchFocus) {
+        self.touch_points.insert(id, SfTouchPoint { pos, focus });
+    }
+
+    /// Updates the location of a touch point and returns it.
+    pub fn touch_motion(&mut self, id: TouchId, pos: Vector2<f64>) -> Option<&SfTouchPoint> {
+        let point = self.touch_points.get_mut(&id)?;
+        point.pos = pos;
+        Some(point)
+    }
+
+    /// Removes a touch point and returns it.
+    pub fn touch_up(&mut self, id: TouchId) -> Option<SfTouchPoint> {
+        self.touch_points.remove(&id)
+    }
+
+    /// Returns a touch point.
+    pub fn touch_point(&self, id: TouchId) -> Option<&SfTouchPoint> {
+        self.touch_points.get(&id)
+    }
 
+    /// Returns true if any touch point is captured by the home bar.
+    pub fn home_bar_captured(&self) -> bool {
+        self.touch_points.values().any(|point| match point.focus {
+            TouchFocus::HomeBar => true,
+            _ => false,
+        })
+    }
 }
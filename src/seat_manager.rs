@@ -1,4 +1,6 @@
+use crate::renderer::ClientCursor;
 use crate::server::Server;
+use std::ptr;
 use wlroots::*;
 
 pub struct SeatManager {}
@@ -34,6 +36,31 @@ impl SeatHandler for SeatManager {
         server.pointer_grabbed = false;
     }
 
+    #[wlroots_dehandle(compositor)]
+    fn cursor_set(
+        &mut self,
+        compositor_handle: CompositorHandle,
+        _: SeatHandle,
+        event: &SetCursorEvent,
+    ) {
+        use compositor_handle as compositor;
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        // only the client the pointer is over may change the cursor
+        let focused = server
+            .seat
+            .run(|seat| unsafe { (*seat.as_ptr()).pointer_state.focused_client })
+            .unwrap_or(ptr::null_mut());
+        if focused.is_null() || unsafe { (*event.as_ptr()).seat_client } != focused {
+            return;
+        }
+        server.client_cursor = event.surface().map(|surface| ClientCursor {
+            surface,
+            hotspot: event.location(),
+        });
+        let area = server.cursor_damage_area();
+        server.outputs.damage(area);
+    }
+
     fn keyboard_grabbed(&mut self, _: CompositorHandle, _: SeatHandle, _: &KeyboardGrab) {
         info!("Keyboard grabbed!");
     }
@@ -1,21 +1,120 @@
+use crate::config::{Config, DecorationPolicy, OutputMode, SpaceRule, UNKNOWN_APP_ID};
+use crate::damage::DamageTracker;
 use crate::event::{Event, RawEvent};
-use crate::renderer::Renderer;
+use crate::input_method::InputMethodRelay;
+use crate::layer_shell::{LayerShell, Layers};
+use crate::osk::{KeyPress, OskDevice};
+use crate::output_layout::{OutputLayout, OutputRole};
+use crate::output_manager::scale_for_dpi;
+use crate::overview::{Overview, OverviewAction};
+use crate::renderer::{ClientCursor, HomeBarGesture, Renderer};
+use crate::rotation::{Rotation, SensorProxy};
+use crate::seat::{SfSeat, TouchFocus};
+use crate::shell::string_from_ptr;
+use crate::slide_over::SlideOver;
 use crate::space::Space;
-use crate::view::View;
+use crate::status::STATUS_HEIGHT;
+use crate::switcher::SpaceSwitcher;
+use crate::tablet::TabletManager;
+use crate::unmanaged::Unmanaged;
+use crate::utils::{contains, union};
+use crate::view::{View, ViewShell};
+use crate::xdg_decoration::XdgDecorationManager;
+use crate::xwayland::XWayland;
+use cairo::Rectangle;
+use cgmath::Vector2;
 use std::collections::HashMap;
+use std::ptr;
 use std::rc::Rc;
-use wlroots::{wlroots_dehandle, Capability, SeatHandle, XdgV6ShellSurfaceHandle};
+use std::time::{Duration, Instant};
+use wlroots::utils::current_time;
+use wlroots::wlr_key_state::*;
+use wlroots::wlroots_sys::{
+    wlr_compositor, wlr_input_device, wlr_seat_keyboard_clear_focus, wlr_seat_keyboard_notify_enter,
+    wlr_surface, wlr_xdg_surface,
+};
+use wlroots::{
+    wlroots_dehandle, Capability, KeyboardHandle, OutputHandle, SeatHandle, SurfaceHandle,
+    XdgShellSurfaceHandle,
+};
 
 pub type SpaceID = u64;
 
+/// How long the status bar and home bar stay visible after being revealed over a fullscreen
+/// view.
+const CHROME_REVEAL_DURATION: Duration = Duration::from_secs(3);
+
+/// How far a touch needs to move down from the top edge to reveal the status bar.
+const TOP_EDGE_REVEAL_DISTANCE: f64 = 20.;
+
+/// How far cursor images are assumed to reach from the hotspot in any direction.
+const CURSOR_DAMAGE_SIZE: f64 = 64.;
+
+/// Where a surface that committed is shown.
+enum SurfacePlacement {
+    /// In the given area of the layout.
+    Shown(Rectangle),
+    /// Nowhere, since it belongs to a view in a space that isn’t shown.
+    Hidden,
+    /// Somewhere that moves around or isn’t known.
+    Unknown,
+}
+
 #[derive(Debug)]
 pub struct Server {
     spaces: HashMap<SpaceID, Space>,
     space_order: Vec<SpaceID>,
     space_id_counter: SpaceID,
     app_id_mapping: HashMap<String, SpaceID>,
+    /// Decides which space new views go into, and holds the user’s settings.
+    pub config: Config,
+    /// When the status bar and home bar were last revealed over a fullscreen view.
+    chrome_revealed: Option<Instant>,
+    pub overview: Overview,
+    /// Taken out while it handles an event.
+    pub slide_over: Option<SlideOver>,
+    /// Mapped layer surfaces, taken out while they handle an event.
+    pub layers: Option<Layers>,
+    /// X11 menus and tooltips, taken out while they handle an event.
+    pub unmanaged: Option<Unmanaged>,
+    pub switcher: SpaceSwitcher,
     pub seat: SeatHandle,
-    pub renderer: Option<Renderer>,
+    /// The keyboard that was used most recently.
+    pub keyboard: Option<KeyboardHandle>,
+    /// Input state that isn’t tracked by wlroots.
+    pub input: SfSeat,
+    /// Mapped popups and the views they belong to.
+    popups: Vec<(ViewShell, Rc<View>)>,
+    /// Created once the display exists.
+    pub xdg_decoration: Option<Box<XdgDecorationManager>>,
+    /// Created once the display exists.
+    pub layer_shell: Option<Box<LayerShell>>,
+    /// Created once the display exists, but the X server only starts when a client connects.
+    pub xwayland: Option<Box<XWayland>>,
+    /// Created once the display exists.
+    pub input_method: Option<Box<InputMethodRelay>>,
+    /// Created once the display exists.
+    damage_tracker: Option<Box<DamageTracker>>,
+    /// The device that the built-in keyboard types with, created when it’s first used.
+    osk_device: Option<Box<OskDevice>>,
+    /// Where the input method’s keyboard is shown, if its layer surface is mapped.
+    input_method_area: Option<Rectangle>,
+    /// Created when the first tablet is added.
+    pub tablet: Option<TabletManager>,
+    /// Layer surfaces are placed on the primary output.
+    pub outputs: OutputLayout,
+    /// The extended output that the keyboard focus is on, or `None` for the primary output.
+    focused_output: Option<String>,
+    /// The extended output that the cursor is on, or `None` for the primary output.
+    pointer_output: Option<String>,
+    /// Follows the accelerometer, and is applied to the output when it renders the next frame.
+    pub rotation: Rotation,
+    cursor: Vector2<f64>,
+    cursor_visible: bool,
+    /// Pointer buttons that are held, which drag things around while the pointer moves.
+    pointer_buttons: usize,
+    /// The cursor surface set by the client with pointer focus.
+    pub client_cursor: Option<ClientCursor>,
     keyboards: usize,
     pointers: usize,
     touch: usize,
@@ -30,8 +129,33 @@ impl Server {
             space_order: Vec::new(),
             space_id_counter: 0,
             app_id_mapping: HashMap::new(),
+            config: Config::default(),
+            chrome_revealed: None,
+            overview: Overview::new(),
+            slide_over: Some(SlideOver::new()),
+            unmanaged: Some(Unmanaged::new()),
+            layers: Some(Layers::new()),
+            switcher: SpaceSwitcher::new(),
             seat: SeatHandle::default(),
-            renderer: None,
+            keyboard: None,
+            input: SfSeat::new(),
+            popups: Vec::new(),
+            xdg_decoration: None,
+            layer_shell: None,
+            xwayland: None,
+            input_method: None,
+            damage_tracker: None,
+            osk_device: None,
+            input_method_area: None,
+            tablet: None,
+            outputs: OutputLayout::new(),
+            focused_output: None,
+            pointer_output: None,
+            rotation: Rotation::new(),
+            cursor: Vector2::new(0., 0.),
+            cursor_visible: false,
+            pointer_buttons: 0,
+            client_cursor: None,
             keyboards: 0,
             pointers: 0,
             touch: 0,
@@ -75,6 +199,9 @@ impl Server {
 
     pub fn pointer_removed(&mut self) {
         self.pointers -= 1;
+        if self.pointers == 0 {
+            self.cursor_visible = false;
+        }
         self.update_capabilities();
     }
 
@@ -88,6 +215,403 @@ impl Server {
         self.update_capabilities();
     }
 
+    /// Creates the xdg-decoration global, which needs the seat to find the display.
+    pub fn init_xdg_decoration(&mut self) {
+        let seat = self.seat.run(|seat| seat.as_ptr()).unwrap();
+        self.xdg_decoration = Some(unsafe { XdgDecorationManager::new((*seat).display) });
+    }
+
+    /// Creates the layer shell global, which needs the seat to find the display.
+    pub fn init_layer_shell(&mut self) {
+        let seat = self.seat.run(|seat| seat.as_ptr()).unwrap();
+        self.layer_shell = Some(unsafe { LayerShell::new((*seat).display) });
+    }
+
+    /// Creates the text input and input method globals, which need the seat to find the display.
+    pub fn init_input_method(&mut self) {
+        let seat = self.seat.run(|seat| seat.as_ptr()).unwrap();
+        self.input_method = Some(unsafe { InputMethodRelay::new((*seat).display, seat) });
+    }
+
+    /// Starts tracking surface commits and the clock, which need the seat to find the display.
+    pub fn init_damage_tracking(&mut self, compositor: *mut wlr_compositor) {
+        let seat = self.seat.run(|seat| seat.as_ptr()).unwrap();
+        self.damage_tracker = Some(unsafe { DamageTracker::new((*seat).display, compositor) });
+    }
+
+    /// Redraws the clock in the status bars and waits for the next minute.
+    pub fn clock_ticked(&mut self) {
+        let width = self.outputs.bounds().width;
+        self.outputs.damage(Rectangle {
+            x: 0.,
+            y: 0.,
+            width,
+            height: STATUS_HEIGHT,
+        });
+        if let Some(ref tracker) = self.damage_tracker {
+            tracker.schedule_clock();
+        }
+    }
+
+    /// Damages where a surface was and is shown after it committed, and returns where it’s
+    /// shown now.
+    ///
+    /// Surfaces of views in spaces that aren’t shown don’t damage anything, while surfaces that
+    /// can’t be placed damage all outputs.
+    pub fn surface_committed(
+        &mut self,
+        surface: *mut wlr_surface,
+        previous: Option<Rectangle>,
+    ) -> Option<Rectangle> {
+        match self.surface_placement(surface) {
+            SurfacePlacement::Shown(bounds) => {
+                self.outputs
+                    .damage(previous.map_or(bounds, |previous| union(previous, bounds)));
+                Some(bounds)
+            }
+            SurfacePlacement::Hidden => {
+                if let Some(previous) = previous {
+                    self.outputs.damage(previous);
+                }
+                None
+            }
+            SurfacePlacement::Unknown => {
+                self.outputs.damage_whole();
+                None
+            }
+        }
+    }
+
+    /// Finds where a surface is shown.
+    ///
+    /// Everything moves while the overview or the space switcher is shown, so surfaces aren’t
+    /// placed then. The Slide Over panel is placed where it currently is, since it asks for
+    /// frames while it moves anyway.
+    fn surface_placement(&self, surface: *mut wlr_surface) -> SurfacePlacement {
+        if self.overview.is_visible() || self.switcher.is_active() {
+            return SurfacePlacement::Unknown;
+        }
+        let is_cursor = self.client_cursor.as_ref().map_or(false, |cursor| {
+            cursor.surface.run(|cursor| cursor.as_ptr()).ok() == Some(surface)
+        });
+        if is_cursor {
+            return SurfacePlacement::Shown(self.cursor_damage_area());
+        }
+
+        let front = self.space_order.last().and_then(|id| self.spaces.get(id));
+        let front_views = front.into_iter().flat_map(|space| space.views());
+        let layer_views = self.layers.iter().flat_map(|layers| layers.views());
+        let primary = front_views
+            .chain(layer_views)
+            .find_map(|view| view.surface_bounds(surface));
+        if let Some(bounds) = primary {
+            return SurfacePlacement::Shown(bounds);
+        }
+
+        let slide_over = self
+            .slide_over
+            .as_ref()
+            .and_then(|slide_over| slide_over.surface_bounds(surface));
+        if let Some(bounds) = slide_over {
+            return SurfacePlacement::Shown(bounds);
+        }
+
+        let unmanaged = self
+            .unmanaged
+            .as_ref()
+            .and_then(|unmanaged| unmanaged.surface_bounds(surface));
+        if let Some(bounds) = unmanaged {
+            return SurfacePlacement::Shown(bounds);
+        }
+
+        for output in self.outputs.iter() {
+            let bounds = output
+                .front_space()
+                .and_then(|id| self.spaces.get(&id))
+                .and_then(|space| {
+                    space
+                        .views()
+                        .iter()
+                        .find_map(|view| view.surface_bounds(surface))
+                });
+            if let Some(bounds) = bounds {
+                return SurfacePlacement::Shown(Rectangle {
+                    x: bounds.x + output.x,
+                    ..bounds
+                });
+            }
+        }
+
+        let hidden = self
+            .spaces
+            .values()
+            .flat_map(|space| space.views())
+            .any(|view| view.surface_bounds(surface).is_some());
+        if hidden {
+            SurfacePlacement::Hidden
+        } else {
+            SurfacePlacement::Unknown
+        }
+    }
+
+    /// Returns the area that the cursor image may cover.
+    pub fn cursor_damage_area(&self) -> Rectangle {
+        Rectangle {
+            x: self.cursor.x - CURSOR_DAMAGE_SIZE,
+            y: self.cursor.y - CURSOR_DAMAGE_SIZE,
+            width: CURSOR_DAMAGE_SIZE * 2.,
+            height: CURSOR_DAMAGE_SIZE * 2.,
+        }
+    }
+
+    /// Starts following the accelerometer if iio-sensor-proxy is running.
+    pub fn init_rotation(&mut self) {
+        match SensorProxy::connect() {
+            Ok(sensor) => {
+                let seat = self.seat.run(|seat| seat.as_ptr()).unwrap();
+                unsafe { self.rotation.set_sensor(sensor, (*seat).display) };
+            }
+            Err(err) => info!("Not rotating the screen automatically: {}", err),
+        }
+    }
+
+    /// Locks the screen in its current orientation, or rotates it to the sensor’s orientation
+    /// again.
+    pub fn toggle_rotation_lock(&mut self) {
+        let locked = !self.rotation.is_locked();
+        self.rotation.set_locked(locked);
+        self.outputs.damage_whole();
+        info!("Rotation lock {}", if locked { "on" } else { "off" });
+    }
+
+    /// Sets the output scale from the config, or else from the screen’s pixel density.
+    pub fn update_output_scale(&mut self, output_handle: &OutputHandle) {
+        let config_scale = self.config.scale;
+        output_handle
+            .run(|output| {
+                let (width, phys_width) = unsafe {
+                    let output = output.as_ptr();
+                    ((*output).width, (*output).phys_width)
+                };
+                let scale = config_scale.unwrap_or_else(|| scale_for_dpi(width, phys_width));
+                output.set_scale(scale as f32);
+            })
+            .unwrap();
+    }
+
+    /// Returns the primary output’s renderer, once the output has drawn a frame.
+    pub fn renderer(&self) -> Option<&Renderer> {
+        self.outputs
+            .primary()
+            .and_then(|output| output.renderer.as_ref())
+    }
+
+    pub fn renderer_mut(&mut self) -> Option<&mut Renderer> {
+        self.outputs
+            .primary_mut()
+            .and_then(|output| output.renderer.as_mut())
+    }
+
+    /// Adds an output, which becomes the primary one if it fits better than the current one.
+    pub fn output_added(&mut self, output_handle: OutputHandle, name: String) {
+        let orphaned = self.outputs.add(output_handle, name);
+        self.adopt_spaces(orphaned);
+        // the primary output may have changed
+        self.arrange_layers();
+        self.update_keyboard_focus();
+    }
+
+    /// Removes an output, closes the layer surfaces on it and moves the spaces it showed to the
+    /// primary output.
+    pub fn output_removed(&mut self, output_handle: &OutputHandle) {
+        let output = output_handle.run(|output| output.as_ptr());
+        if let (Some(layers), Ok(output)) = (&self.layers, output) {
+            unsafe { layers.close_on_output(output) };
+        }
+        let orphaned = self.outputs.remove(output_handle);
+        self.adopt_spaces(orphaned);
+        // the primary output may have changed
+        self.arrange_layers();
+        self.update_keyboard_focus();
+    }
+
+    /// Mirrors the primary output on the other outputs or extends the layout onto them.
+    pub fn set_output_mode(&mut self, mode: OutputMode) {
+        let orphaned = self.outputs.set_mode(mode);
+        self.adopt_spaces(orphaned);
+        self.configure_views();
+        self.update_keyboard_focus();
+    }
+
+    /// Switches between mirroring and extending.
+    pub fn toggle_output_mode(&mut self) {
+        let mode = match self.outputs.mode() {
+            OutputMode::Mirror => OutputMode::Extend,
+            OutputMode::Extend => OutputMode::Mirror,
+        };
+        info!("Output mode {:?}", mode);
+        self.set_output_mode(mode);
+    }
+
+    /// Moves spaces to the primary output, behind the ones it already shows.
+    fn adopt_spaces(&mut self, mut ids: Vec<SpaceID>) {
+        ids.append(&mut self.space_order);
+        self.space_order = ids;
+
+        // inputs on outputs that don’t show spaces anymore go to the primary output
+        let outputs = &self.outputs;
+        let is_extended = |name: &String| {
+            outputs
+                .by_name(name)
+                .and_then(|output| outputs.role(&output.handle))
+                == Some(OutputRole::Extended)
+        };
+        if !self.focused_output.as_ref().map_or(true, is_extended) {
+            self.focused_output = None;
+        }
+        if !self.pointer_output.as_ref().map_or(true, is_extended) {
+            self.pointer_output = None;
+        }
+    }
+
+    /// Moves the space with keyboard focus to the next extended output, or from the last one
+    /// back to the primary output.
+    pub fn move_space_to_next_output(&mut self) {
+        let id = match self.focused_space() {
+            Some(id) => id,
+            None => return,
+        };
+        let from = self.focused_output.clone();
+        let to = self.outputs.next_output(from.as_ref().map(String::as_str));
+        if to == from {
+            return;
+        }
+
+        match from.and_then(|name| self.outputs.by_name_mut(&name)) {
+            Some(output) => output.space_order.retain(|x| *x != id),
+            None => {
+                self.space_order.retain(|x| *x != id);
+                self.overview.remove_space(id);
+            }
+        }
+        match to.as_ref().and_then(|name| self.outputs.by_name_mut(name)) {
+            Some(output) => output.space_order.push(id),
+            None => self.space_order.push(id),
+        }
+        self.focused_output = to;
+        self.configure_views();
+        self.update_keyboard_focus();
+    }
+
+    /// Returns the front space of the output with keyboard focus.
+    fn focused_space(&self) -> Option<SpaceID> {
+        match self.focused_output {
+            Some(ref name) => self
+                .outputs
+                .by_name(name)
+                .and_then(|output| output.front_space()),
+            None => self.space_order.last().cloned(),
+        }
+    }
+
+    /// Returns the space that has a view with the given surface.
+    fn space_of_surface(&self, surface: &ViewShell) -> Option<SpaceID> {
+        self.spaces
+            .iter()
+            .find(|(_, space)| space.views().iter().any(|view| &view.shell == surface))
+            .map(|(id, _)| *id)
+    }
+
+    /// Returns the view area and the screen area of the output that shows a space.
+    fn space_areas(&self, id: SpaceID) -> Option<(Rectangle, Rectangle)> {
+        self.outputs
+            .output_of_space(id)
+            .and_then(|output| output.renderer.as_ref())
+            .or_else(|| self.renderer())
+            .map(|renderer| (renderer.view_area(), renderer.screen_area()))
+    }
+
+    /// Sends pointer events on an extended output to its front space, and returns true if the
+    /// event was one of those.
+    fn handle_extended_output_event(&mut self, event: Event) -> bool {
+        match event {
+            Event::PointerDown { .. }
+            | Event::PointerUp { .. }
+            | Event::PointerMotion { .. }
+            | Event::PointerAxis { .. } => (),
+            _ => return false,
+        }
+
+        let target = self
+            .outputs
+            .extended_output_at(self.cursor)
+            .map(|output| (output.name.clone(), output.x, output.front_space()));
+        let target_name = target.as_ref().map(|(name, ..)| name.clone());
+        if target_name != self.pointer_output {
+            // the space that the pointer left enters its surface again when the pointer is back
+            let left = match self.pointer_output {
+                Some(ref name) => self
+                    .outputs
+                    .by_name(name)
+                    .and_then(|output| output.front_space()),
+                None => self.space_order.last().cloned(),
+            };
+            if let Some(space) = left.and_then(|id| self.spaces.get_mut(&id)) {
+                space.reset_pointer_focus();
+            }
+            self.pointer_output = target_name;
+        }
+
+        let (name, x, front) = match target {
+            Some(target) => target,
+            None => return false,
+        };
+        let event = match event {
+            Event::PointerMotion { location } => Event::PointerMotion {
+                location: location - Vector2::new(x, 0.),
+            },
+            Event::PointerDown { .. } => {
+                self.focused_output = Some(name);
+                event
+            }
+            event => event,
+        };
+        if let Some(id) = front {
+            let mut space = self.spaces.remove(&id).unwrap();
+            space.handle_event(event, self);
+            self.spaces.insert(id, space);
+        }
+        true
+    }
+
+    /// Sets up XWayland, which needs the seat to find the display.
+    pub fn init_xwayland(&mut self, compositor: *mut wlr_compositor) {
+        let seat = self.seat.run(|seat| seat.as_ptr()).unwrap();
+        self.xwayland = Some(unsafe { XWayland::new((*seat).display, compositor, seat) });
+    }
+
+    fn tablet_manager(&mut self) -> &mut TabletManager {
+        if self.tablet.is_none() {
+            let seat = self.seat.run(|seat| seat.as_ptr()).unwrap();
+            self.tablet = Some(unsafe { TabletManager::new(seat) });
+        }
+        self.tablet.as_mut().unwrap()
+    }
+
+    pub fn tablet_added(&mut self, device: *mut wlr_input_device) {
+        unsafe { self.tablet_manager().add_tablet(device) };
+    }
+
+    pub fn tablet_pad_added(&mut self, device: *mut wlr_input_device) {
+        unsafe { self.tablet_manager().add_pad(device) };
+    }
+
+    pub fn tablet_device_removed(&mut self, device: *mut wlr_input_device) {
+        if let Some(ref mut tablet) = self.tablet {
+            tablet.remove_device(device);
+        }
+    }
+
     fn add_space(&mut self) -> SpaceID {
         let id = self.space_id_counter;
         self.space_id_counter += 1;
@@ -96,25 +620,639 @@ impl Server {
         id
     }
 
-    pub fn add_view(&mut self, app_id: String, view: Rc<View>) {
-        let space_id = if !self.app_id_mapping.contains_key(&app_id) {
-            let space_id = self.add_space();
-            self.app_id_mapping.insert(app_id, space_id);
-            space_id
-        } else {
-            *self.app_id_mapping.get(&app_id).unwrap()
+    /// Adds a view to the space chosen by the grouping rules.
+    ///
+    /// If the view is already marked fullscreen, because the client asked for it before the
+    /// view was mapped, it goes fullscreen in its space right away.
+    pub fn add_view(&mut self, app_id: String, title: &str, view: Rc<View>) {
+        let rule = self.config.space_rule(&app_id, title);
+        let space_id = match rule {
+            SpaceRule::NewSpace => self.add_space(),
+            SpaceRule::GroupByAppId => match self.app_id_mapping.get(&app_id) {
+                Some(space_id) => *space_id,
+                None => {
+                    let space_id = self.add_space();
+                    self.app_id_mapping.insert(app_id, space_id);
+                    space_id
+                }
+            },
+            SpaceRule::JoinFocused | SpaceRule::Split => match self.focused_space() {
+                Some(space_id) => space_id,
+                None => self.add_space(),
+            },
+            SpaceRule::SlideOver => {
+                if view.is_fullscreen() {
+                    view.set_fullscreen(false);
+                }
+                if let Some(ref mut slide_over) = self.slide_over {
+                    slide_over.add_view(view);
+                }
+                self.outputs.damage_whole();
+                self.update_keyboard_focus();
+                return;
+            }
+        };
+
+        view.set_decorated(self.config.decorations == DecorationPolicy::All);
+        let areas = self.space_areas(space_id);
+        let space = self.spaces.get_mut(&space_id).unwrap();
+        space.add_view(Rc::clone(&view));
+        match areas {
+            Some((area, screen)) if view.is_fullscreen() => {
+                space.set_fullscreen(&view.shell, true, area, screen);
+                self.chrome_revealed = None;
+            }
+            Some((area, _)) => {
+                view.configure(area);
+                if rule == SpaceRule::Split {
+                    space.split_with(&view, area);
+                }
+            }
+            None => (),
+        }
+        self.outputs.damage_whole();
+        self.update_keyboard_focus();
+    }
+
+    /// Returns true if sfc draws a title strip above an xdg toplevel, or will once it’s mapped.
+    pub fn decorates_toplevel(&self, surface: *mut wlr_xdg_surface) -> bool {
+        if self.config.decorations == DecorationPolicy::All {
+            return true;
+        }
+        let shell = ViewShell::Xdg(unsafe { XdgShellSurfaceHandle::from_ptr(surface) });
+        if let Some(view) = self.find_view(&shell) {
+            return view.is_decorated();
+        }
+        // only views in the Slide Over panel are decorated
+        let (app_id, title) = unsafe {
+            let toplevel = (*surface).__bindgen_anon_1.toplevel;
+            (
+                string_from_ptr((*toplevel).app_id).unwrap_or_else(|| UNKNOWN_APP_ID.into()),
+                string_from_ptr((*toplevel).title).unwrap_or_default(),
+            )
+        };
+        self.config.space_rule(&app_id, &title) == SpaceRule::SlideOver
+    }
+
+    /// Adds an X11 menu or tooltip, which is shown above the front space.
+    pub fn add_unmanaged_view(&mut self, view: Rc<View>) {
+        if let Some(ref mut unmanaged) = self.unmanaged {
+            unmanaged.add_view(view);
+        }
+        self.outputs.damage_whole();
+    }
+
+    /// Adds a mapped layer surface and makes room for it.
+    pub fn add_layer_view(&mut self, view: Rc<View>) {
+        if let Some(ref mut layers) = self.layers {
+            layers.add_view(view);
+        }
+        self.arrange_layers();
+        self.update_keyboard_focus();
+    }
+
+    /// Places the layer surfaces and configures all views to fill the area they leave.
+    ///
+    /// This should be called whenever the renderer is replaced.
+    pub fn arrange_layers(&mut self) {
+        let keyboard_client = self
+            .input_method
+            .as_ref()
+            .and_then(|relay| relay.input_method_client());
+        let renderer = self
+            .outputs
+            .primary_mut()
+            .and_then(|output| output.renderer.as_mut());
+        if let (Some(layers), Some(renderer)) = (&self.layers, renderer) {
+            let (usable_area, keyboard_area) =
+                layers.arrange(renderer.screen_area(), keyboard_client);
+            renderer.set_usable_area(usable_area);
+            self.input_method_area = keyboard_area;
+        }
+        self.configure_views();
+    }
+
+    /// Configures all views to fill the view area of the output that shows them.
+    ///
+    /// While the on-screen keyboard is shown, the front space ends where the keyboard starts.
+    pub fn configure_views(&mut self) {
+        self.outputs.damage_whole();
+        let builtin_keyboard = self.is_keyboard_shown() && !self.has_input_method();
+        // mirrors show the keyboard too
+        for output in self.outputs.iter_mut() {
+            if let Some(ref mut renderer) = output.renderer {
+                if renderer.role() != OutputRole::Extended {
+                    renderer.keyboard_mut().set_shown(builtin_keyboard);
+                }
+            }
+        }
+        let (area, screen) = match self.renderer() {
+            Some(renderer) => (renderer.view_area(), renderer.screen_area()),
+            None => return,
+        };
+        let front_area = match self.shown_keyboard_area() {
+            Some(keyboard) => Rectangle {
+                height: (keyboard.y - area.y).max(0.).min(area.height),
+                ..area
+            },
+            _ => area,
+        };
+        let front_id = self.space_order.last().cloned();
+        let ids: Vec<_> = self.spaces.keys().cloned().collect();
+        for id in ids {
+            let (area, screen) = if Some(id) == front_id {
+                (front_area, screen)
+            } else {
+                self.space_areas(id).unwrap_or((area, screen))
+            };
+            self.spaces.get_mut(&id).unwrap().configure_views(area, screen);
+        }
+        // the Slide Over panel floats above the front space
+        if let Some(ref mut slide_over) = self.slide_over {
+            slide_over.configure(front_area);
+        }
+    }
+
+    /// Adds a popup that belongs to the view with the given toplevel surface and moves it onto
+    /// the screen if necessary.
+    pub fn add_popup(&mut self, popup: ViewShell, toplevel: &ViewShell) {
+        let owner = match self.find_view(toplevel) {
+            Some(owner) => owner,
+            None => {
+                warn!("Popup has no parent view");
+                return;
+            }
         };
 
-        self.spaces.get_mut(&space_id).unwrap().add_view(view);
+        let screen = match self.space_of_surface(&owner.shell) {
+            Some(id) => self.space_areas(id).map(|(_, screen)| screen),
+            None => self.renderer().map(|renderer| renderer.screen_area()),
+        };
+        if let Some(screen) = screen {
+            owner.unconstrain_popup(&popup, screen);
+        }
+
+        self.popups.push((popup, owner));
     }
 
-    pub fn remove_view_for_surface(&mut self, surface: XdgV6ShellSurfaceHandle) {
-        for (_, space) in &mut self.spaces {
-            let removed = space.remove_view_for_surface(&surface);
+    /// Returns the view with the given surface, in any space, the Slide Over panel, the layers
+    /// or the X11 menus and tooltips.
+    pub fn find_view(&self, surface: &ViewShell) -> Option<Rc<View>> {
+        self.spaces
+            .values()
+            .flat_map(|space| space.views())
+            .chain(
+                self.slide_over
+                    .iter()
+                    .flat_map(|slide_over| slide_over.views()),
+            )
+            .chain(self.layers.iter().flat_map(|layers| layers.views()))
+            .chain(
+                self.unmanaged
+                    .iter()
+                    .flat_map(|unmanaged| unmanaged.views()),
+            )
+            .find(|view| &view.shell == surface)
+            .map(Rc::clone)
+    }
+
+    /// Dismisses all popups that don’t belong to the given view.
+    pub fn dismiss_popups_outside(&mut self, view: Option<&Rc<View>>) {
+        for (popup, owner) in &self.popups {
+            let inside = view.map_or(false, |view| Rc::ptr_eq(view, owner));
+            if !inside {
+                popup.close();
+            }
+        }
+    }
+
+    pub fn remove_view_for_surface(&mut self, surface: &ViewShell) {
+        self.outputs.damage_whole();
+        self.popups.retain(|(popup, owner)| popup != surface && &owner.shell != surface);
+
+        if let Some(ref mut unmanaged) = self.unmanaged {
+            if unmanaged.remove_view_for_surface(surface) {
+                return;
+            }
+        }
+
+        if let Some(ref mut slide_over) = self.slide_over {
+            if slide_over.remove_view_for_surface(surface) {
+                self.update_keyboard_focus();
+                return;
+            }
+        }
+
+        if let Some(ref mut layers) = self.layers {
+            if layers.remove_view_for_surface(surface) {
+                self.arrange_layers();
+                self.update_keyboard_focus();
+                return;
+            }
+        }
+
+        let mut emptied_space = None;
+        for (id, space) in &mut self.spaces {
+            let removed = space.remove_view_for_surface(surface);
             if removed {
+                if space.is_empty() {
+                    emptied_space = Some(*id);
+                }
                 break;
             }
         }
+
+        if let Some(id) = emptied_space {
+            self.remove_space(id);
+        }
+        self.update_keyboard_focus();
+    }
+
+    /// Makes the view with the given surface fullscreen or leaves fullscreen.
+    ///
+    /// Views in the Slide Over panel can’t go fullscreen. The state is stored in the view, so
+    /// it’s cleared when the view is unmapped.
+    pub fn set_fullscreen(&mut self, surface: &ViewShell, fullscreen: bool) {
+        if let Some(id) = self.space_of_surface(surface) {
+            let (area, screen) = match self.space_areas(id) {
+                Some(areas) => areas,
+                None => return,
+            };
+            let space = self.spaces.get_mut(&id).unwrap();
+            space.set_fullscreen(surface, fullscreen, area, screen);
+        }
+        self.outputs.damage_whole();
+        self.chrome_revealed = None;
+        self.update_keyboard_focus();
+    }
+
+    /// Returns true if the status bar and home bar are hidden because the view in front is
+    /// fullscreen.
+    pub fn chrome_hidden(&self) -> bool {
+        if self.overview.is_visible() {
+            return false;
+        }
+        let front_fullscreen = self
+            .space_order
+            .last()
+            .and_then(|id| self.spaces.get(id))
+            .and_then(|space| space.top_view())
+            .map_or(false, |view| view.is_fullscreen());
+        front_fullscreen && !self.is_chrome_revealed()
+    }
+
+    /// Returns true for a while after the status bar and home bar were revealed.
+    pub fn is_chrome_revealed(&self) -> bool {
+        self.chrome_revealed
+            .map_or(false, |time| time.elapsed() < CHROME_REVEAL_DURATION)
+    }
+
+    /// Shows the status bar and home bar over a fullscreen view for a while.
+    pub fn reveal_chrome(&mut self) {
+        self.chrome_revealed = Some(Instant::now());
+        if let Some(ref tracker) = self.damage_tracker {
+            tracker.schedule_chrome_hide(CHROME_REVEAL_DURATION);
+        }
+    }
+
+    /// Hides the status bar and home bar again once the reveal times out.
+    pub fn hide_chrome(&mut self) {
+        if self.chrome_revealed.take().is_some() {
+            self.outputs.damage_whole();
+        }
+    }
+
+    /// Lets touches swipe down from the top edge to reveal the status bar, and returns true if
+    /// the event was one of those touches.
+    fn handle_top_edge_event(&mut self, event: Event) -> bool {
+        match event {
+            Event::TouchDown { id, location } => {
+                if !self.chrome_hidden() || location.y >= STATUS_HEIGHT {
+                    return false;
+                }
+                self.input
+                    .touch_down(id, location, TouchFocus::TopEdge(location.y));
+                true
+            }
+            Event::TouchMotion { id, location } => match self.input.touch_point(id) {
+                Some(point) => match point.focus {
+                    TouchFocus::TopEdge(start_y) => {
+                        if location.y - start_y > TOP_EDGE_REVEAL_DISTANCE {
+                            self.reveal_chrome();
+                        }
+                        true
+                    }
+                    _ => false,
+                },
+                None => false,
+            },
+            Event::TouchUp { id } | Event::TouchCancel { id } => {
+                match self.input.touch_point(id).map(|point| &point.focus) {
+                    Some(TouchFocus::TopEdge(_)) => {
+                        self.input.touch_up(id);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Removes a space and forgets which app ids it was used for.
+    ///
+    /// If it was the frontmost space, the next one slides in.
+    fn remove_space(&mut self, id: SpaceID) {
+        let was_front = self.space_order.last() == Some(&id);
+
+        self.spaces.remove(&id);
+        self.space_order.retain(|x| *x != id);
+        self.outputs.remove_space(id);
+        self.app_id_mapping.retain(|_, space_id| *space_id != id);
+        self.overview.remove_space(id);
+
+        if self.space_order.is_empty() {
+            self.overview.close();
+        } else if was_front && !self.overview.is_open() {
+            if let Some(width) = self.renderer().map(|renderer| renderer.dimensions().0) {
+                self.switcher.reveal(width);
+            }
+        }
+    }
+
+    /// Returns the view that should have keyboard focus, which is a layer surface that asks
+    /// for it, the topmost view of the front space of an extended output if it was clicked
+    /// last, of the Slide Over panel if it was used last, or of the frontmost space otherwise.
+    pub fn focus_target(&self) -> Option<Rc<View>> {
+        let layer_target = self
+            .layers
+            .as_ref()
+            .and_then(|layers| layers.focus_target());
+        if layer_target.is_some() {
+            return layer_target;
+        }
+
+        let output_target = self
+            .focused_output
+            .as_ref()
+            .and_then(|name| self.outputs.by_name(name))
+            .and_then(|output| output.front_space())
+            .and_then(|id| self.spaces.get(&id))
+            .and_then(|space| space.top_view());
+        if output_target.is_some() {
+            return output_target;
+        }
+
+        let slide_over_target = self
+            .slide_over
+            .as_ref()
+            .and_then(|slide_over| slide_over.focus_target());
+        if slide_over_target.is_some() {
+            return slide_over_target;
+        }
+
+        self.space_order
+            .last()
+            .and_then(|id| self.spaces.get(id))
+            .and_then(|space| space.top_view())
+    }
+
+    /// Moves keyboard focus to the current focus target if it changed.
+    #[wlroots_dehandle(seat, keyboard)]
+    pub fn update_keyboard_focus(&mut self) {
+        let seat_ptr = match self.seat.run(|seat| seat.as_ptr()) {
+            Ok(seat_ptr) => seat_ptr,
+            Err(_) => return,
+        };
+
+        // views whose surface is already gone can’t be focused
+        let target = self.focus_target();
+        let surface = target.as_ref().and_then(|view| view.surface_ptr());
+        let target = target.filter(|_| surface.is_some());
+        let changed = match (&target, &self.input.kbd_focus) {
+            (Some(a), Some(b)) => !Rc::ptr_eq(a, b),
+            (None, None) => false,
+            _ => true,
+        };
+        if !changed {
+            return;
+        }
+
+        match (self.keyboard.clone(), surface) {
+            (Some(keyboard_h), Some(surface)) => {
+                let seat_h = &self.seat;
+                use seat_h as seat;
+                use keyboard_h as keyboard;
+
+                let res = unsafe { SurfaceHandle::from_ptr(surface) }.run(|surface| {
+                    seat.keyboard_notify_enter(
+                        surface,
+                        &mut keyboard.keycodes(),
+                        &mut keyboard.get_modifier_masks(),
+                    );
+                });
+                if let Err(err) = res {
+                    warn!("Error in update_keyboard_focus: {}", err);
+                }
+            }
+            // without a hardware keyboard, the surface is still entered so that its text
+            // fields can be typed into with the on-screen keyboard
+            (None, Some(surface)) => unsafe {
+                wlr_seat_keyboard_notify_enter(
+                    seat_ptr,
+                    surface,
+                    ptr::null_mut(),
+                    0,
+                    ptr::null_mut(),
+                );
+            },
+            (_, None) => unsafe { wlr_seat_keyboard_clear_focus(seat_ptr) },
+        }
+
+        let surface = surface.unwrap_or(ptr::null_mut());
+        let was_shown = self.is_keyboard_shown();
+        if let Some(ref mut relay) = self.input_method {
+            relay.set_focus(surface);
+        }
+        if self.is_keyboard_shown() != was_shown {
+            self.configure_views();
+        }
+
+        // views that were just removed can’t be told anymore
+        let previous = self.input.kbd_focus.take();
+        if let Some(view) = previous.filter(|view| self.find_view(&view.shell).is_some()) {
+            view.set_activated(false);
+        }
+        if let Some(ref view) = target {
+            view.set_activated(true);
+        }
+        self.input.kbd_focus = target;
+    }
+
+    /// Returns true while an on-screen keyboard is typing into a text field.
+    fn is_keyboard_shown(&self) -> bool {
+        self.input_method
+            .as_ref()
+            .map_or(false, |relay| relay.is_active())
+    }
+
+    /// Returns true if an input method is connected, which is used instead of the built-in
+    /// keyboard.
+    fn has_input_method(&self) -> bool {
+        self.input_method
+            .as_ref()
+            .map_or(false, |relay| relay.has_input_method())
+    }
+
+    /// Returns where the on-screen keyboard is while it’s shown.
+    fn shown_keyboard_area(&self) -> Option<Rectangle> {
+        if !self.is_keyboard_shown() {
+            None
+        } else if self.has_input_method() {
+            self.input_method_area
+        } else {
+            self.renderer().map(|renderer| renderer.keyboard().area())
+        }
+    }
+
+    /// Passes a touch event to the built-in keyboard and types the key it returns, if any.
+    pub fn handle_keyboard_event(&mut self, event: Event) {
+        let key = match self.renderer_mut() {
+            Some(renderer) => renderer.keyboard_mut().handle_event(event),
+            None => return,
+        };
+        if let Some(key) = key {
+            self.type_key(key);
+        }
+    }
+
+    /// Types a key from the built-in keyboard into the surface with keyboard focus.
+    pub fn type_key(&mut self, key: KeyPress) {
+        if self.osk_device.is_none() {
+            let seat = self.seat.run(|seat| seat.as_ptr()).unwrap();
+            self.osk_device = Some(unsafe { OskDevice::new(seat) });
+        }
+        self.osk_device.as_mut().unwrap().type_key(key);
+
+        // the physical keyboard is made active again when it’s used next
+        self.keyboard = None;
+    }
+
+    /// Hides the on-screen keyboard when a touch or click lands outside of both the keyboard and
+    /// the view that is being typed into.
+    fn handle_tap_away(&mut self, event: Event) {
+        let location = match event {
+            Event::TouchDown { location, .. } => location,
+            Event::PointerDown { .. } => self.cursor_location(),
+            _ => return,
+        };
+        let on_keyboard = self
+            .shown_keyboard_area()
+            .map_or(false, |area| contains(area, location));
+        if !self.is_keyboard_shown() || on_keyboard {
+            return;
+        }
+        // the client disables the text input itself when its own text field loses focus
+        let on_focus = self
+            .input
+            .kbd_focus
+            .as_ref()
+            .map_or(false, |view| view.contains_point(location));
+        if on_focus {
+            return;
+        }
+        if let Some(ref mut relay) = self.input_method {
+            relay.deactivate();
+        }
+        self.configure_views();
+    }
+
+    /// Sets the keyboard that was used most recently and makes it the seat’s active keyboard.
+    #[wlroots_dehandle(seat, keyboard)]
+    pub fn set_keyboard(&mut self, keyboard_handle: KeyboardHandle) {
+        if self.keyboard.as_ref() != Some(&keyboard_handle) {
+            {
+                let seat_h = &self.seat;
+                use seat_h as seat;
+                use keyboard_handle as keyboard;
+                seat.set_keyboard(keyboard.input_device());
+            }
+            self.keyboard = Some(keyboard_handle);
+            // the previous keyboard may not have existed
+            self.input.kbd_focus = None;
+            self.update_keyboard_focus();
+        }
+    }
+
+    /// Forwards the modifier state of the given keyboard to the focused client.
+    #[wlroots_dehandle(seat, keyboard)]
+    pub fn notify_modifiers(&mut self, keyboard_handle: KeyboardHandle) {
+        self.set_keyboard(keyboard_handle.clone());
+        let seat_h = &self.seat;
+        use seat_h as seat;
+        use keyboard_handle as keyboard;
+        seat.keyboard_notify_modifiers(&mut keyboard.get_modifier_masks());
+    }
+
+    /// Returns the cursor location in screen coordinates.
+    pub fn cursor_location(&self) -> Vector2<f64> {
+        self.cursor
+    }
+
+    /// Returns true if the cursor should be drawn.
+    ///
+    /// The cursor is hidden while using touch input.
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// Updates the cursor with pointer motion and resolves relative motion to absolute motion.
+    fn update_cursor(&mut self, event: Event) -> Event {
+        match event {
+            Event::PointerMotionRelative { delta } => {
+                self.cursor = self.outputs.clamp_location(self.cursor + delta);
+                self.cursor_visible = true;
+                Event::PointerMotion {
+                    location: self.cursor,
+                }
+            }
+            Event::PointerMotion { location } => {
+                self.cursor = self.outputs.clamp_location(location);
+                self.cursor_visible = true;
+                Event::PointerMotion {
+                    location: self.cursor,
+                }
+            }
+            Event::PointerDown { .. } | Event::PointerUp { .. } | Event::PointerAxis { .. } => {
+                self.cursor_visible = true;
+                event
+            }
+            Event::TouchDown { .. } => {
+                self.cursor_visible = false;
+                event
+            }
+            event => event,
+        }
+    }
+
+    /// Damages what input can change: just the cursor while it moves without dragging anything,
+    /// nothing for tablet tools since clients draw what they do, and everything otherwise.
+    fn damage_for_event(&mut self, event: Event, previous_cursor: Rectangle) {
+        match event {
+            Event::PointerDown { .. } => self.pointer_buttons += 1,
+            Event::PointerUp { .. } => {
+                self.pointer_buttons = self.pointer_buttons.saturating_sub(1);
+            }
+            _ => (),
+        }
+        match event {
+            Event::PointerMotion { .. } if self.pointer_buttons == 0 => {
+                let area = union(previous_cursor, self.cursor_damage_area());
+                self.outputs.damage(area);
+            }
+            _ if event.tablet_tool().is_some() => (),
+            _ => self.outputs.damage_whole(),
+        }
     }
 
     pub fn space(&self, id: SpaceID) -> Option<&Space> {
@@ -129,20 +1267,402 @@ impl Server {
         &*self.space_order
     }
 
+    /// Moves a space to the front.
+    pub fn bring_to_front(&mut self, id: SpaceID) {
+        if let Some(pos) = self.space_order.iter().position(|x| *x == id) {
+            self.space_order.remove(pos);
+            self.space_order.push(id);
+        }
+        self.outputs.damage_whole();
+        self.update_keyboard_focus();
+    }
+
+    pub fn handle_home_bar_gesture(&mut self, gesture: Option<HomeBarGesture>) {
+        match gesture {
+            // over a fullscreen view, the first swipe only reveals the home bar
+            Some(HomeBarGesture::SwipeUp) if self.chrome_hidden() => self.reveal_chrome(),
+            Some(HomeBarGesture::SwipeUp) => self.overview.open(),
+            Some(HomeBarGesture::HorizontalDrag { offset }) => {
+                let order: Vec<_> = self.space_order.iter().rev().map(|x| *x).collect();
+                self.switcher.drag(offset, &order);
+            }
+            Some(HomeBarGesture::HorizontalEnd) => {
+                let width = match self.renderer() {
+                    Some(renderer) => renderer.dimensions().0,
+                    None => return,
+                };
+                let previous_front = self.space_order.last().map(|x| *x);
+                if let Some(id) = self.switcher.end(width) {
+                    self.bring_to_front(id);
+                    if let Some(previous_front) = previous_front {
+                        self.switcher.switched(previous_front);
+                    }
+                }
+            }
+            Some(HomeBarGesture::HorizontalCancel) => self.switcher.cancel(),
+            None => (),
+        }
+    }
+
+    fn handle_overview_event(&mut self, event: Event) {
+        let dims = match self.renderer() {
+            Some(renderer) => renderer.dimensions(),
+            None => return,
+        };
+        let order: Vec<_> = self.space_order.iter().rev().map(|x| *x).collect();
+
+        let action = self
+            .overview
+            .handle_event(&event, self.cursor, &order, dims);
+
+        match action {
+            Some(OverviewAction::Select(id)) => {
+                self.bring_to_front(id);
+                self.overview.close();
+            }
+            Some(OverviewAction::Close(id)) => {
+                if let Some(space) = self.spaces.get(&id) {
+                    space.close_views();
+                }
+            }
+            Some(OverviewAction::Split(id)) => {
+                self.split_with_space(id);
+                self.overview.close();
+            }
+            Some(OverviewAction::Dismiss) => self.overview.close(),
+            None => (),
+        }
+    }
+
+    /// Moves the topmost view of a space into the front space, to the right of its topmost view
+    /// in Split View.
+    ///
+    /// The space is removed if that was its only view.
+    pub fn split_with_space(&mut self, id: SpaceID) {
+        let front_id = match self.space_order.last() {
+            Some(front_id) if *front_id != id => *front_id,
+            _ => return,
+        };
+        let view = match self.spaces.get_mut(&id) {
+            Some(space) => match space.top_view() {
+                Some(view) => {
+                    space.remove_view_for_surface(&view.shell);
+                    view
+                }
+                None => return,
+            },
+            None => return,
+        };
+        if self.spaces.get(&id).map_or(false, |space| space.is_empty()) {
+            self.remove_space(id);
+        }
+
+        if view.is_fullscreen() {
+            view.set_fullscreen(false);
+        }
+        let areas = self.space_areas(front_id);
+        let space = self.spaces.get_mut(&front_id).unwrap();
+        space.add_view(Rc::clone(&view));
+        if let Some((area, _)) = areas {
+            view.configure(area);
+            space.split_with(&view, area);
+        }
+        self.outputs.damage_whole();
+        self.update_keyboard_focus();
+    }
+
+    /// Lets the layer surfaces handle an event and returns true if they did.
+    fn handle_layer_event(&mut self, event: Event) -> bool {
+        let areas = match self.renderer() {
+            Some(renderer) => (renderer.view_area(), renderer.content_area()),
+            None => return false,
+        };
+        let mut layers = match self.layers.take() {
+            Some(layers) => layers,
+            None => return false,
+        };
+
+        let was_inside = layers.pointer_inside();
+        let handled = layers.handle_event(event, self, areas);
+        if layers.pointer_inside() && !was_inside {
+            // the views below will send enter events once the pointer leaves the layer surface
+            let front = self.space_order.last().map(|x| *x);
+            if let Some(space) = front.and_then(|id| self.spaces.get_mut(&id)) {
+                space.reset_pointer_focus();
+            }
+            if let Some(ref mut slide_over) = self.slide_over {
+                slide_over.reset_pointer_focus();
+            }
+            if let Some(ref mut unmanaged) = self.unmanaged {
+                unmanaged.reset_pointer_focus();
+            }
+        }
+
+        self.layers = Some(layers);
+        handled
+    }
+
+    /// Lets the Slide Over panel handle an event and returns true if it did.
+    fn handle_slide_over_event(&mut self, event: Event) -> bool {
+        let mut slide_over = match self.slide_over.take() {
+            Some(slide_over) => slide_over,
+            None => return false,
+        };
+
+        let was_inside = slide_over.pointer_inside();
+        let handled = slide_over.handle_event(event, self);
+        if slide_over.pointer_inside() && !was_inside {
+            // the space below will send enter events once the pointer leaves the panel
+            let front = self.space_order.last().map(|x| *x);
+            if let Some(space) = front.and_then(|id| self.spaces.get_mut(&id)) {
+                space.reset_pointer_focus();
+            }
+        }
+
+        self.slide_over = Some(slide_over);
+        handled
+    }
+
+    /// Lets the X11 menus and tooltips handle an event and returns true if they did.
+    fn handle_unmanaged_event(&mut self, event: Event) -> bool {
+        let mut unmanaged = match self.unmanaged.take() {
+            Some(unmanaged) => unmanaged,
+            None => return false,
+        };
+
+        let was_inside = unmanaged.pointer_inside();
+        let handled = unmanaged.handle_event(event, self);
+        if unmanaged.pointer_inside() && !was_inside {
+            // the views below will send enter events once the pointer leaves the menu
+            let front = self.space_order.last().map(|x| *x);
+            if let Some(space) = front.and_then(|id| self.spaces.get_mut(&id)) {
+                space.reset_pointer_focus();
+            }
+            if let Some(ref mut slide_over) = self.slide_over {
+                slide_over.reset_pointer_focus();
+            }
+        }
+
+        self.unmanaged = Some(unmanaged);
+        handled
+    }
+
+    #[wlroots_dehandle(seat)]
     pub fn handle_event(&mut self, event: RawEvent) {
-        if self.renderer.is_none() {
-            warn!("No renderer, ignoring event");
+        let mut event: Event = event.into();
+
+        match event {
+            Event::KeyDown { code } | Event::KeyUp { code } => {
+                let state = match event {
+                    Event::KeyDown { .. } => WLR_KEY_PRESSED,
+                    _ => WLR_KEY_RELEASED,
+                };
+                let seat_h = &self.seat;
+                use seat_h as seat;
+                seat.keyboard_notify_key(current_time(), code, state as u32);
+                return;
+            }
+            _ => (),
+        }
+
+        if let Some(ref mut tablet) = self.tablet {
+            tablet.track_location(&mut event);
+        }
+        match self.renderer() {
+            Some(renderer) => renderer.map_event(&mut event),
+            None => {
+                warn!("No renderer, ignoring event");
+                return;
+            }
         }
+        let previous_cursor = self.cursor_damage_area();
+        let event = self.update_cursor(event);
+        self.damage_for_event(event, previous_cursor);
 
-        let renderer = self.renderer.take().unwrap();
-        let mut event = event.into();
-        renderer.map_event(&mut event);
-        self.renderer = Some(renderer);
+        // the pointer can move onto extended outputs, which only show their front space
+        if self.handle_extended_output_event(event) {
+            self.update_keyboard_focus();
+            return;
+        }
+        match event {
+            Event::PointerDown { .. } | Event::TouchDown { .. } => self.focused_output = None,
+            _ => (),
+        }
+
+        self.handle_tap_away(event);
+
+        // layer surfaces above the views get to handle events first
+        if self.handle_layer_event(event) {
+            self.update_keyboard_focus();
+            return;
+        }
+
+        if self.overview.is_open() {
+            self.handle_overview_event(event);
+            return;
+        }
+
+        if self.handle_top_edge_event(event) {
+            return;
+        }
+
+        // X11 menus and tooltips float above the Slide Over panel
+        if self.handle_unmanaged_event(event) {
+            return;
+        }
+
+        // the Slide Over panel floats above the space and gets to handle events first
+        if self.handle_slide_over_event(event) {
+            self.update_keyboard_focus();
+            return;
+        }
 
         if let Some(top_space_id) = self.space_order.last().map(|x| *x) {
             let mut space = self.spaces.remove(&top_space_id).unwrap();
             space.handle_event(event, self);
             self.spaces.insert(top_space_id, space);
         }
+
+        // focus may have moved to a different view
+        self.update_keyboard_focus();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wlroots::XdgShellSurfaceHandle;
+
+    fn test_view() -> Rc<View> {
+        Rc::new(View::new(ViewShell::Xdg(XdgShellSurfaceHandle::default())))
+    }
+
+    /// Returns a view that can be told apart from other views by its shell.
+    ///
+    /// The shell is a dummy pointer, so the view must only be compared, and not focused, asked
+    /// whether it wants focus, or rendered.
+    fn distinct_view(id: usize) -> Rc<View> {
+        Rc::new(View::new(ViewShell::XWayland(id as *mut _)))
+    }
+
+    #[test]
+    fn removing_last_view_removes_space() {
+        let mut server = Server::new();
+        let view = test_view();
+        server.add_view("app".into(), "", Rc::clone(&view));
+        assert_eq!(server.space_order().len(), 1);
+
+        server.remove_view_for_surface(&view.shell);
+        assert!(server.space_order().is_empty());
+        assert!(server.spaces.is_empty());
+        assert!(server.app_id_mapping.is_empty());
+    }
+
+    #[test]
+    fn space_with_views_is_kept() {
+        let mut server = Server::new();
+        let first = distinct_view(1);
+        let second = distinct_view(2);
+        server.add_view("app".into(), "", Rc::clone(&first));
+        server.add_view("app".into(), "", Rc::clone(&second));
+        assert_eq!(server.space_order().len(), 1);
+
+        server.remove_view_for_surface(&first.shell);
+        assert_eq!(server.space_order().len(), 1);
+        assert!(server.app_id_mapping.contains_key("app"));
+        assert!(server.find_view(&first.shell).is_none());
+        assert!(server.find_view(&second.shell).is_some());
+
+        server.remove_view_for_surface(&second.shell);
+        assert!(server.space_order().is_empty());
+        assert!(server.app_id_mapping.is_empty());
+    }
+
+    #[test]
+    fn next_space_comes_to_front() {
+        let mut server = Server::new();
+        let back_view = distinct_view(1);
+        let front_view = distinct_view(2);
+        server.add_view("back".into(), "", Rc::clone(&back_view));
+        let back_space = server.space_order()[0];
+        server.add_view("front".into(), "", Rc::clone(&front_view));
+        let front_space = server.space_order()[1];
+
+        server.remove_view_for_surface(&front_view.shell);
+        assert_eq!(server.space_order(), &[back_space]);
+        assert!(server.space(front_space).is_none());
+        assert!(!server.app_id_mapping.contains_key("front"));
+        let back_views = server.space(back_space).unwrap().views();
+        assert!(Rc::ptr_eq(&back_views[0], &back_view));
+    }
+
+    #[test]
+    fn removed_app_id_gets_a_new_space() {
+        let mut server = Server::new();
+        let view = test_view();
+        server.add_view("app".into(), "", Rc::clone(&view));
+        let first_space = server.space_order()[0];
+        server.remove_view_for_surface(&view.shell);
+
+        server.add_view("app".into(), "", test_view());
+        assert_eq!(server.space_order().len(), 1);
+        assert_ne!(server.space_order()[0], first_space);
+    }
+
+    #[test]
+    fn split_with_space_moves_the_view_to_the_front() {
+        let mut server = Server::new();
+        let front_view = test_view();
+        let back_view = test_view();
+        server.add_view("back".into(), "", Rc::clone(&back_view));
+        let back_space = server.space_order()[0];
+        server.add_view("front".into(), "", Rc::clone(&front_view));
+        let front_space = server.space_order()[1];
+
+        server.split_with_space(front_space);
+        assert_eq!(server.space_order().len(), 2);
+
+        server.split_with_space(back_space);
+        assert_eq!(server.space_order(), &[front_space]);
+        assert!(!server.app_id_mapping.contains_key("back"));
+        let front_views = server.space(front_space).unwrap().views();
+        assert_eq!(front_views.len(), 2);
+        assert!(Rc::ptr_eq(&front_views[1], &back_view));
+    }
+
+    #[test]
+    fn rules_choose_the_space() {
+        let mut server = Server::new();
+        server.config = Config::parse(
+            "app_id=browser new-space\n\
+             title=*Picture* join-focused\n",
+        )
+        .0;
+
+        server.add_view("browser".into(), "", test_view());
+        server.add_view("browser".into(), "", test_view());
+        assert_eq!(server.space_order().len(), 2);
+
+        server.add_view("player".into(), "Picture in Picture", test_view());
+        assert_eq!(server.space_order().len(), 2);
+
+        server.add_view("???".into(), "", test_view());
+        server.add_view("???".into(), "", test_view());
+        assert_eq!(server.space_order().len(), 4);
+
+        server.add_view("app".into(), "", test_view());
+        server.add_view("app".into(), "", test_view());
+        assert_eq!(server.space_order().len(), 5);
+    }
+
+    #[test]
+    fn spaces_without_outputs_stay_in_front() {
+        let mut server = Server::new();
+        server.add_view("app".into(), "", test_view());
+        let space = server.space_order()[0];
+        server.move_space_to_next_output();
+        assert_eq!(server.space_order(), &[space]);
+        server.set_output_mode(OutputMode::Mirror);
+        assert_eq!(server.space_order(), &[space]);
     }
 }
@@ -1,33 +1,136 @@
+use crate::config::UNKNOWN_APP_ID;
 use crate::server::Server;
-use crate::view::View;
+use crate::view::{View, ViewShell};
+use std::ffi::CStr;
+use std::os::raw::c_char;
 use std::rc::Rc;
+use wlroots::wlroots_sys::{
+    wlr_layer_surface_v1_from_wlr_surface, wlr_surface_is_layer_surface,
+    wlr_surface_is_xdg_surface, wlr_xdg_surface, wlr_xdg_surface_from_wlr_surface,
+    wlr_xdg_surface_role, wlr_xdg_surface_v6, wlr_xdg_surface_v6_role, wlr_xdg_toplevel,
+    wlr_xdg_toplevel_v6,
+};
 use wlroots::*;
 
+/// Reads a string set by the client, returning None if it’s unset or invalid.
+pub(crate) unsafe fn string_from_ptr(string: *const c_char) -> Option<String> {
+    if string.is_null() {
+        return None;
+    }
+    CStr::from_ptr(string).to_str().ok().map(String::from)
+}
+
+/// Reads an app id, falling back to `???` if it’s unset or invalid.
+unsafe fn app_id_from_ptr(app_id: *const c_char) -> String {
+    string_from_ptr(app_id).unwrap_or_else(|| String::from(UNKNOWN_APP_ID))
+}
+
+/// Returns the wlroots toplevel, which wlroots-rs doesn’t give access to.
+unsafe fn raw_toplevel_v6(top_level: &XdgV6TopLevel) -> *mut wlr_xdg_toplevel_v6 {
+    use std::mem;
+
+    // horrible hack that relies on deterministic struct layout
+    #[derive(Debug, Eq, PartialEq, Hash)]
+    struct HorribleHack {
+        _shell_surface: *mut wlr_xdg_surface_v6,
+        toplevel: *mut wlr_xdg_toplevel_v6,
+    }
+
+    mem::transmute_copy::<_, HorribleHack>(top_level).toplevel
+}
+
+/// Same as `raw_toplevel_v6`, but for stable xdg-shell.
+unsafe fn raw_toplevel(top_level: &XdgTopLevel) -> *mut wlr_xdg_toplevel {
+    use std::mem;
+
+    // same horrible hack
+    #[derive(Debug, Eq, PartialEq, Hash)]
+    struct HorribleHack {
+        _shell_surface: *mut wlr_xdg_surface,
+        toplevel: *mut wlr_xdg_toplevel,
+    }
+
+    mem::transmute_copy::<_, HorribleHack>(top_level).toplevel
+}
+
 /// Why does this exist? Because for some reason XdgV6TopLevel.app_id() panics
 /// when the app id string is invalid instead of simply returning an error
-fn obtain_app_id_safely_unsafely(top_level: &XdgV6TopLevel) -> String {
+///
+/// Returns the app id and the title, which has the same problem.
+pub fn obtain_app_id_safely_unsafely(top_level: &XdgV6TopLevel) -> (String, String) {
     unsafe {
-        use std::ffi::CStr;
-        use std::mem;
-        use wlroots::wlroots_sys::{wlr_xdg_surface_v6, wlr_xdg_toplevel_v6};
-
-        // horrible hack that relies on deterministic struct layout
-        #[derive(Debug, Eq, PartialEq, Hash)]
-        struct HorribleHack {
-            _shell_surface: *mut wlr_xdg_surface_v6,
-            toplevel: *mut wlr_xdg_toplevel_v6,
-        }
+        let toplevel = raw_toplevel_v6(top_level);
+        (
+            app_id_from_ptr((*toplevel).app_id),
+            string_from_ptr((*toplevel).title).unwrap_or_default(),
+        )
+    }
+}
+
+/// Same as `obtain_app_id_safely_unsafely`, but for stable xdg-shell, which has the same problem.
+pub fn obtain_stable_app_id_safely_unsafely(top_level: &XdgTopLevel) -> (String, String) {
+    unsafe {
+        let toplevel = raw_toplevel(top_level);
+        (
+            app_id_from_ptr((*toplevel).app_id),
+            string_from_ptr((*toplevel).title).unwrap_or_default(),
+        )
+    }
+}
+
+/// Returns true if the client asked to be fullscreen, which it may do before the surface is
+/// mapped (`mpv --fs` does), when there’s no view to make fullscreen yet.
+fn requested_fullscreen_v6(top_level: &XdgV6TopLevel) -> bool {
+    unsafe { (*raw_toplevel_v6(top_level)).client_pending.fullscreen }
+}
 
-        let hh = mem::transmute_copy::<_, HorribleHack>(top_level);
+/// Same as `requested_fullscreen_v6`, but for stable xdg-shell.
+fn requested_fullscreen(top_level: &XdgTopLevel) -> bool {
+    unsafe { (*raw_toplevel(top_level)).client_pending.fullscreen }
+}
 
-        if (*hh.toplevel).app_id.is_null() {
-            return String::from("???");
+/// Returns the toplevel that a popup (or a popup of a popup) belongs to, or `None` if a popup
+/// on the way has no parent.
+unsafe fn popup_toplevel_v6(surface: *mut wlr_xdg_surface_v6) -> Option<*mut wlr_xdg_surface_v6> {
+    let mut surface = surface;
+    loop {
+        match (*surface).role {
+            wlr_xdg_surface_v6_role::WLR_XDG_SURFACE_V6_ROLE_TOPLEVEL => return Some(surface),
+            wlr_xdg_surface_v6_role::WLR_XDG_SURFACE_V6_ROLE_POPUP => {
+                surface = (*(*surface).__bindgen_anon_1.popup).parent;
+                if surface.is_null() {
+                    return None;
+                }
+            }
+            _ => return None,
         }
-        let cstr = CStr::from_ptr((*hh.toplevel).app_id);
+    }
+}
 
-        match cstr.to_str() {
-            Ok(s) => String::from(s),
-            Err(_) => String::from("???"),
+/// Same as `popup_toplevel_v6`, but for stable xdg-shell, where the popups of panels and
+/// launchers belong to a layer surface instead of a toplevel.
+unsafe fn popup_toplevel(surface: *mut wlr_xdg_surface) -> Option<ViewShell> {
+    let mut surface = surface;
+    loop {
+        match (*surface).role {
+            wlr_xdg_surface_role::WLR_XDG_SURFACE_ROLE_TOPLEVEL => {
+                return Some(ViewShell::Xdg(XdgShellSurfaceHandle::from_ptr(surface)));
+            }
+            wlr_xdg_surface_role::WLR_XDG_SURFACE_ROLE_POPUP => {
+                let parent = (*(*surface).__bindgen_anon_1.popup).parent;
+                if parent.is_null() {
+                    return None;
+                }
+                if wlr_surface_is_layer_surface(parent) {
+                    let layer_surface = wlr_layer_surface_v1_from_wlr_surface(parent);
+                    return Some(ViewShell::Layer(layer_surface));
+                }
+                if !wlr_surface_is_xdg_surface(parent) {
+                    return None;
+                }
+                surface = wlr_xdg_surface_from_wlr_surface(parent);
+            }
+            _ => return None,
         }
     }
 }
@@ -52,22 +155,38 @@ impl XdgV6ShellHandler for XdgV6Shell {
     ) {
         use compositor_handle as compositor;
 
-        let app_id = {
+        let ids = {
             use xdg_surface_handle as surface;
 
             match surface.state().unwrap() {
                 XdgV6ShellState::TopLevel(top_level) => {
-                    Some(obtain_app_id_safely_unsafely(top_level))
+                    let (app_id, title) = obtain_app_id_safely_unsafely(top_level);
+                    Some((app_id, title, requested_fullscreen_v6(top_level)))
                 }
                 _ => None,
             }
         };
 
-        if let Some(app_id) = app_id {
-            let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
 
-            let view = Rc::new(View::new(xdg_surface_handle));
-            server.add_view(app_id, view);
+        if let Some((app_id, title, fullscreen)) = ids {
+            let view = Rc::new(View::new(ViewShell::XdgV6(xdg_surface_handle)));
+            if fullscreen {
+                view.set_fullscreen(true);
+            }
+            server.add_view(app_id, &title, view);
+        } else {
+            let toplevel = xdg_surface_handle
+                .run(|surface| unsafe { popup_toplevel_v6(surface.as_ptr()) })
+                .ok()
+                .and_then(|toplevel| toplevel);
+            match toplevel {
+                Some(toplevel) => server.add_popup(
+                    ViewShell::XdgV6(xdg_surface_handle),
+                    &ViewShell::XdgV6(unsafe { XdgV6ShellSurfaceHandle::from_ptr(toplevel) }),
+                ),
+                None => warn!("Popup has no toplevel"),
+            }
         }
     }
 
@@ -82,7 +201,22 @@ impl XdgV6ShellHandler for XdgV6Shell {
 
         let server: &mut Server = compositor.data.downcast_mut().unwrap();
 
-        server.remove_view_for_surface(surface);
+        server.remove_view_for_surface(&ViewShell::XdgV6(surface));
+    }
+
+    #[wlroots_dehandle(compositor)]
+    fn fullscreen_request(
+        &mut self,
+        compositor_handle: CompositorHandle,
+        _: SurfaceHandle,
+        surface: XdgV6ShellSurfaceHandle,
+        event: &mut xdg_shell_v6_events::SetFullscreenEvent,
+    ) {
+        use compositor_handle as compositor;
+
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+
+        server.set_fullscreen(&ViewShell::XdgV6(surface), event.fullscreen());
     }
 }
 
@@ -103,3 +237,103 @@ impl XdgV6ShellManagerHandler for XdgV6ShellManager {
         (Some(Box::new(XdgV6Shell::new(surface))), None)
     }
 }
+
+pub struct XdgShell {
+    surface: XdgShellSurfaceHandle,
+}
+
+impl XdgShell {
+    pub fn new(surface: XdgShellSurfaceHandle) -> XdgShell {
+        XdgShell { surface }
+    }
+}
+
+impl XdgShellHandler for XdgShell {
+    #[wlroots_dehandle(compositor, surface)]
+    fn map_request(
+        &mut self,
+        compositor_handle: CompositorHandle,
+        surface_handle: SurfaceHandle,
+        xdg_surface_handle: XdgShellSurfaceHandle,
+    ) {
+        use compositor_handle as compositor;
+
+        let ids = {
+            use xdg_surface_handle as surface;
+
+            match surface.state().unwrap() {
+                XdgShellState::TopLevel(top_level) => {
+                    let (app_id, title) = obtain_stable_app_id_safely_unsafely(top_level);
+                    Some((app_id, title, requested_fullscreen(top_level)))
+                }
+                _ => None,
+            }
+        };
+
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+
+        if let Some((app_id, title, fullscreen)) = ids {
+            let view = Rc::new(View::new(ViewShell::Xdg(xdg_surface_handle)));
+            if fullscreen {
+                view.set_fullscreen(true);
+            }
+            server.add_view(app_id, &title, view);
+        } else {
+            let toplevel = xdg_surface_handle
+                .run(|surface| unsafe { popup_toplevel(surface.as_ptr()) })
+                .ok()
+                .and_then(|toplevel| toplevel);
+            match toplevel {
+                Some(toplevel) => server.add_popup(ViewShell::Xdg(xdg_surface_handle), &toplevel),
+                None => warn!("Popup has no toplevel"),
+            }
+        }
+    }
+
+    #[wlroots_dehandle(compositor)]
+    fn unmap_request(
+        &mut self,
+        compositor_handle: CompositorHandle,
+        _: SurfaceHandle,
+        surface: XdgShellSurfaceHandle,
+    ) {
+        use compositor_handle as compositor;
+
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+
+        server.remove_view_for_surface(&ViewShell::Xdg(surface));
+    }
+
+    #[wlroots_dehandle(compositor)]
+    fn fullscreen_request(
+        &mut self,
+        compositor_handle: CompositorHandle,
+        _: SurfaceHandle,
+        surface: XdgShellSurfaceHandle,
+        event: &mut xdg_shell_events::SetFullscreenEvent,
+    ) {
+        use compositor_handle as compositor;
+
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+
+        server.set_fullscreen(&ViewShell::Xdg(surface), event.fullscreen());
+    }
+}
+
+pub struct XdgShellManager;
+
+impl XdgShellManager {
+    pub fn new() -> XdgShellManager {
+        XdgShellManager
+    }
+}
+
+impl XdgShellManagerHandler for XdgShellManager {
+    fn new_surface(
+        &mut self,
+        _: CompositorHandle,
+        surface: XdgShellSurfaceHandle,
+    ) -> (Option<Box<XdgShellHandler>>, Option<Box<SurfaceHandler>>) {
+        (Some(Box::new(XdgShell::new(surface))), None)
+    }
+}
@@ -0,0 +1,400 @@
+//! Slide Over, a narrow panel that floats above the frontmost space, docked to the right edge.
+
+use crate::event::Event;
+use crate::renderer::{Grabber, Renderer};
+use crate::server::Server;
+use crate::space::{Space, SpaceTransform};
+use crate::spring::{RealTimeSpring, Spring};
+use crate::view::{View, ViewShell};
+use cairo::Rectangle;
+use cgmath::{Matrix4, Vector2};
+use std::rc::Rc;
+use std::time::Instant;
+use wlroots::wlroots_sys::wlr_surface;
+use wlroots::TouchId;
+
+/// Width of the panel, which is about as wide as a phone.
+const PANEL_WIDTH: f64 = 360.;
+
+/// Distance between the panel and the edges of the view area.
+const PANEL_MARGIN: f64 = 8.;
+
+/// Height of the strip above the views that the panel can be dragged by.
+const GRABBER_HEIGHT: f64 = 16.;
+
+/// Touches this close to the right edge of the screen swipe the panel in.
+const EDGE_WIDTH: f64 = 12.;
+
+/// How far ahead the offset is projected with its velocity when deciding whether to hide.
+const OFFSET_PROJECTION: f64 = 0.2;
+
+/// How far the grabber needs to be dragged left to show the next view in the stack.
+const CYCLE_DISTANCE: f64 = 60.;
+
+#[derive(Debug)]
+struct PanelDrag {
+    /// The touch point, or None for the pointer.
+    id: Option<TouchId>,
+    start_x: f64,
+    last_x: f64,
+    start_offset: f64,
+    prev_time: Instant,
+}
+
+#[derive(Debug)]
+pub struct SlideOver {
+    /// The views in the panel, of which only the topmost one is visible.
+    space: Space,
+    /// The panel area including the grabber.
+    panel: Option<Rectangle>,
+    /// How far the panel is pushed off the right edge.
+    offset: RealTimeSpring,
+    shown: bool,
+    /// True if the panel was used more recently than the space below.
+    focused: bool,
+    drag: Option<PanelDrag>,
+    /// Touch points that went down on the panel.
+    touch_ids: Vec<TouchId>,
+    /// Pointer buttons that were pressed on the panel.
+    pointer_buttons: usize,
+    pointer_inside: bool,
+    grabber: Option<Grabber>,
+}
+
+impl SlideOver {
+    pub fn new() -> SlideOver {
+        SlideOver {
+            space: Space::new_floating(),
+            panel: None,
+            offset: RealTimeSpring::new(Spring::new(1., 0.35)),
+            shown: false,
+            focused: false,
+            drag: None,
+            touch_ids: Vec::new(),
+            pointer_buttons: 0,
+            pointer_inside: false,
+            grabber: None,
+        }
+    }
+
+    pub fn views(&self) -> &[Rc<View>] {
+        self.space.views()
+    }
+
+    /// Returns where a surface of a view in the panel is shown, in screen coordinates.
+    ///
+    /// The area is off screen while the panel is hidden.
+    pub fn surface_bounds(&self, surface: *mut wlr_surface) -> Option<Rectangle> {
+        let transform = self.transform();
+        self.space
+            .views()
+            .iter()
+            .find_map(|view| view.surface_bounds(surface))
+            .map(|bounds| transform.apply_rect(bounds))
+    }
+
+    /// Returns the view that should have keyboard focus if the panel was used last.
+    pub fn focus_target(&self) -> Option<Rc<View>> {
+        if self.shown && self.focused {
+            self.space.top_view()
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the pointer is over the panel, or was pressed on it.
+    pub fn pointer_inside(&self) -> bool {
+        self.pointer_inside
+    }
+
+    /// Forgets which surface has pointer focus, so that it’s entered again on the next event.
+    pub fn reset_pointer_focus(&mut self) {
+        self.space.reset_pointer_focus();
+        self.pointer_inside = false;
+    }
+
+    /// How far the panel needs to be pushed to be off screen.
+    fn hidden_offset() -> f64 {
+        PANEL_WIDTH + PANEL_MARGIN
+    }
+
+    /// Places the panel at the right edge of the view area.
+    pub fn configure(&mut self, view_area: Rectangle) {
+        let panel = Rectangle {
+            x: view_area.x + view_area.width - PANEL_MARGIN - PANEL_WIDTH,
+            y: view_area.y + PANEL_MARGIN,
+            width: PANEL_WIDTH,
+            height: view_area.height - 2. * PANEL_MARGIN,
+        };
+        if self.panel != Some(panel) {
+            self.grabber = None;
+        }
+        self.panel = Some(panel);
+        let content = Self::content_area(panel);
+        self.space.configure_views(content, content);
+    }
+
+    /// Returns the area below the grabber.
+    fn content_area(panel: Rectangle) -> Rectangle {
+        Rectangle {
+            y: panel.y + GRABBER_HEIGHT,
+            height: panel.height - GRABBER_HEIGHT,
+            ..panel
+        }
+    }
+
+    /// Adds a view on top of the stack and slides the panel in.
+    ///
+    /// Views in the panel always have a title strip.
+    pub fn add_view(&mut self, view: Rc<View>) {
+        view.set_decorated(true);
+        if let Some(panel) = self.panel {
+            view.configure(Self::content_area(panel));
+        }
+        self.space.add_view(view);
+        self.show();
+    }
+
+    /// Removes a view and hides the panel if it was the last one.
+    pub fn remove_view_for_surface(&mut self, surface: &ViewShell) -> bool {
+        let removed = self.space.remove_view_for_surface(surface);
+        if removed && self.space.is_empty() {
+            self.hide();
+        }
+        removed
+    }
+
+    pub fn show(&mut self) {
+        self.shown = true;
+        self.focused = true;
+        self.offset.spring.target = 0.;
+    }
+
+    pub fn hide(&mut self) {
+        self.shown = false;
+        self.focused = false;
+        self.offset.spring.target = Self::hidden_offset();
+    }
+
+    /// Returns true if any part of the panel is on screen.
+    fn is_visible(&self) -> bool {
+        !self.space.is_empty() && self.offset.spring.value < Self::hidden_offset()
+    }
+
+    fn transform(&self) -> SpaceTransform {
+        SpaceTransform {
+            offset: Vector2::new(self.offset.spring.value, 0.),
+            scale: 1.,
+        }
+    }
+
+    pub fn render(
+        &mut self,
+        matrix: Matrix4<f32>,
+        wlr_renderer: &mut wlroots::Renderer,
+        renderer: &Renderer,
+    ) {
+        if self.drag.is_some() {
+            self.offset.update_time();
+        } else if let Some(panel) = self.panel {
+            // the panel only slides between its place and the right edge
+            let x = panel.x + self.offset.spring.value.min(0.);
+            self.offset.update_in(Rectangle {
+                x,
+                width: panel.x + panel.width + Self::hidden_offset() - x,
+                ..panel
+            });
+        } else {
+            self.offset.update();
+        }
+
+        let panel = match self.panel {
+            Some(panel) if self.is_visible() => panel,
+            _ => return,
+        };
+
+        let transform = self.transform();
+        let resolution = renderer.resolution();
+        if self.grabber.as_ref().map_or(false, |g| g.resolution() != resolution) {
+            self.grabber = None;
+        }
+        let grabber = self
+            .grabber
+            .get_or_insert_with(|| Grabber::new(PANEL_WIDTH, GRABBER_HEIGHT, resolution));
+        let origin = transform.apply(Vector2::new(panel.x, panel.y));
+        grabber.render(matrix, origin.x, origin.y);
+
+        self.space.render(matrix, transform, wlr_renderer, renderer);
+    }
+
+    fn panel_contains(&self, point: Vector2<f64>) -> bool {
+        match self.panel {
+            Some(panel) if self.shown => {
+                point.x >= panel.x
+                    && point.y >= panel.y
+                    && point.x < panel.x + panel.width
+                    && point.y < panel.y + panel.height
+            }
+            _ => false,
+        }
+    }
+
+    fn grabber_contains(&self, point: Vector2<f64>) -> bool {
+        match self.panel {
+            Some(panel) => self.panel_contains(point) && point.y < panel.y + GRABBER_HEIGHT,
+            None => false,
+        }
+    }
+
+    /// Returns true if the point is on the edge the panel can be swiped in from.
+    fn edge_contains(&self, point: Vector2<f64>) -> bool {
+        match self.panel {
+            Some(panel) => {
+                let right_edge = panel.x + panel.width + PANEL_MARGIN;
+                !self.shown && !self.space.is_empty() && point.x >= right_edge - EDGE_WIDTH
+            }
+            None => false,
+        }
+    }
+
+    fn drag_start(&mut self, id: Option<TouchId>, x: f64) {
+        self.drag = Some(PanelDrag {
+            id,
+            start_x: x,
+            last_x: x,
+            start_offset: self.offset.spring.value,
+            prev_time: Instant::now(),
+        });
+    }
+
+    fn drag_move(&mut self, x: f64) {
+        let drag = match self.drag {
+            Some(ref mut drag) => drag,
+            None => return,
+        };
+
+        let offset = (drag.start_offset + x - drag.start_x)
+            .max(0.)
+            .min(Self::hidden_offset());
+        self.offset.drag_to(offset, &mut drag.prev_time);
+        drag.last_x = x;
+    }
+
+    fn drag_end(&mut self) {
+        let drag = match self.drag.take() {
+            Some(drag) => drag,
+            None => return,
+        };
+
+        // dragging the grabber left shows the next view in the stack
+        if drag.start_offset == 0. && drag.start_x - drag.last_x > CYCLE_DISTANCE {
+            self.space.cycle_views();
+        }
+
+        let spring = self.offset.spring;
+        let projected = spring.value + spring.velocity * OFFSET_PROJECTION;
+        if projected > Self::hidden_offset() / 2. {
+            self.hide();
+        } else {
+            self.show();
+        }
+    }
+
+    fn is_dragging(&self, id: Option<TouchId>) -> bool {
+        self.drag.as_ref().map_or(false, |drag| drag.id == id)
+    }
+
+    /// Handles an event and returns true if it was meant for the panel.
+    pub fn handle_event(&mut self, event: Event, server: &mut Server) -> bool {
+        match event {
+            Event::TouchDown { id, location } => {
+                if self.drag.is_none()
+                    && (self.edge_contains(location) || self.grabber_contains(location))
+                {
+                    self.drag_start(Some(id), location.x);
+                    return true;
+                }
+                if self.panel_contains(location) {
+                    self.focused = true;
+                    self.touch_ids.push(id);
+                    self.space.handle_event(event, server);
+                    return true;
+                }
+                self.focused = false;
+                false
+            }
+            Event::TouchMotion { id, location } => {
+                if self.is_dragging(Some(id)) {
+                    self.drag_move(location.x);
+                    return true;
+                }
+                if self.touch_ids.contains(&id) {
+                    self.space.handle_event(event, server);
+                    return true;
+                }
+                false
+            }
+            Event::TouchUp { id } | Event::TouchCancel { id } => {
+                if self.is_dragging(Some(id)) {
+                    self.drag_end();
+                    return true;
+                }
+                if let Some(pos) = self.touch_ids.iter().position(|x| *x == id) {
+                    self.touch_ids.remove(pos);
+                    self.space.handle_event(event, server);
+                    return true;
+                }
+                false
+            }
+            Event::PointerMotion { .. }
+            | Event::PointerDown { .. }
+            | Event::PointerUp { .. }
+            | Event::PointerAxis { .. } => self.handle_pointer_event(event, server),
+            _ => false,
+        }
+    }
+
+    fn handle_pointer_event(&mut self, event: Event, server: &mut Server) -> bool {
+        let location = server.cursor_location();
+
+        if self.is_dragging(None) {
+            match event {
+                Event::PointerMotion { .. } => self.drag_move(location.x),
+                Event::PointerUp { .. } => self.drag_end(),
+                _ => (),
+            }
+            return true;
+        }
+
+        let inside = self.pointer_buttons > 0 || self.panel_contains(location);
+        if !inside {
+            if self.pointer_inside {
+                // the space below will send enter events once the pointer moves over it
+                self.space.reset_pointer_focus();
+                self.pointer_inside = false;
+            }
+            if let Event::PointerDown { .. } = event {
+                self.focused = false;
+            }
+            return false;
+        }
+        self.pointer_inside = true;
+
+        match event {
+            Event::PointerDown { .. } => {
+                self.focused = true;
+                if self.pointer_buttons == 0 && self.grabber_contains(location) {
+                    self.drag_start(None, location.x);
+                    return true;
+                }
+                self.pointer_buttons += 1;
+            }
+            Event::PointerUp { .. } => {
+                self.pointer_buttons = self.pointer_buttons.saturating_sub(1);
+            }
+            _ => (),
+        }
+        self.space.handle_event(event, server);
+        true
+    }
+}
@@ -1,31 +1,100 @@
 use wlroots::{WLR_BUTTON_PRESSED, WLR_BUTTON_RELEASED};
 use crate::event::Event;
-use crate::renderer::HomeBar;
+use crate::renderer::{DecorationPart, HomeBar};
 use crate::renderer::Renderer;
+use crate::seat::TouchFocus;
 use crate::server::Server;
-use crate::view::View;
-use cgmath::Matrix4;
+use crate::split::Split;
+use crate::view::{View, ViewShell};
+use cairo::Rectangle;
+use cgmath::{Matrix4, Vector2};
 use std::mem;
 use std::rc::Rc;
 use wlroots::utils::current_time;
 use wlroots::TouchId;
-use wlroots::{wlroots_dehandle, XdgV6ShellSurfaceHandle};
+use wlroots::{wlroots_dehandle, SurfaceHandle};
+
+/// Where a space is drawn: its contents are scaled by `scale` and then moved by `offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpaceTransform {
+    pub offset: Vector2<f64>,
+    pub scale: f64,
+}
+
+impl SpaceTransform {
+    pub fn identity() -> SpaceTransform {
+        SpaceTransform {
+            offset: Vector2::new(0., 0.),
+            scale: 1.,
+        }
+    }
+
+    /// Maps a point in space coordinates to screen coordinates.
+    pub fn apply(&self, point: Vector2<f64>) -> Vector2<f64> {
+        point * self.scale + self.offset
+    }
+
+    /// Maps a rectangle in space coordinates to screen coordinates.
+    pub fn apply_rect(&self, rect: Rectangle) -> Rectangle {
+        let origin = self.apply(Vector2::new(rect.x, rect.y));
+        Rectangle {
+            x: origin.x,
+            y: origin.y,
+            width: rect.width * self.scale,
+            height: rect.height * self.scale,
+        }
+    }
+
+    /// Interpolates linearly between two transforms.
+    pub fn lerp(&self, other: SpaceTransform, t: f64) -> SpaceTransform {
+        SpaceTransform {
+            offset: self.offset + (other.offset - self.offset) * t,
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Space {
     views: Vec<Rc<View>>,
+    /// True for the Slide Over panel, where views can’t go fullscreen.
+    floating: bool,
+    /// Two of the views tiled side by side, if the space is in Split View.
+    split: Option<Split>,
     home_bar: Option<HomeBar>,
-    home_bar_captured_events: bool,
     pointer_target: Option<usize>,
+    /// The surface with pointer focus and its origin in screen coordinates.
+    pointer_surface: Option<(SurfaceHandle, Vector2<f64>)>,
+    pointer_location: Vector2<f64>,
+    pointer_buttons: usize,
+    /// True while a pointer button pressed on a title strip is held.
+    decoration_pressed: bool,
+    /// The view area and the screen area that the views were last configured for, which
+    /// depend on the output that shows the space.
+    areas: Option<(Rectangle, Rectangle)>,
 }
 
 impl Space {
     pub fn new() -> Space {
         Space {
             views: Vec::new(),
+            floating: false,
+            split: None,
             home_bar: None,
-            home_bar_captured_events: false,
             pointer_target: None,
+            pointer_surface: None,
+            pointer_location: Vector2::new(0., 0.),
+            pointer_buttons: 0,
+            decoration_pressed: false,
+            areas: None,
+        }
+    }
+
+    /// Creates a space for the Slide Over panel.
+    pub fn new_floating() -> Space {
+        Space {
+            floating: true,
+            ..Space::new()
         }
     }
 
@@ -33,137 +102,639 @@ impl Space {
         self.views.push(view);
     }
 
-    pub fn remove_view_for_surface(&mut self, surface: &XdgV6ShellSurfaceHandle) -> bool {
-        if let Some(pos) = self.views.iter().position(|x| &x.shell == surface) {
+    pub fn remove_view_for_surface(&mut self, surface: &ViewShell) -> bool {
+        let view = match self.views.iter().find(|x| &x.shell == surface) {
+            Some(view) => Rc::clone(view),
+            None => return false,
+        };
+
+        // the other view of a split takes up the whole space
+        let other = self
+            .split
+            .as_ref()
+            .filter(|split| split.contains(&view))
+            .map(|split| split.other(&view));
+        if let Some(other) = other {
+            self.end_split(&other);
+        }
+
+        if let Some(pos) = self.views.iter().position(|x| Rc::ptr_eq(x, &view)) {
             self.views.remove(pos);
+            if self.pointer_target == Some(pos) {
+                self.pointer_surface = None;
+            }
+            self.pointer_target = index_after_removal(self.pointer_target, pos);
             true
         } else {
             false
         }
     }
 
+    /// Configures all views to fill the given area, or their column of it in Split View.
+    ///
+    /// Fullscreen views fill the whole screen instead.
+    pub fn configure_views(&mut self, area: Rectangle, screen: Rectangle) {
+        self.areas = Some((area, screen));
+        for view in &self.views {
+            if view.is_fullscreen() {
+                view.configure(screen);
+            } else if !self.split.as_ref().map_or(false, |split| split.contains(view)) {
+                view.configure(area);
+            }
+        }
+        if let Some(ref mut split) = self.split {
+            split.set_area(area);
+        }
+    }
+
+    /// Makes a view fullscreen or leaves fullscreen, and returns false if the view isn’t in this
+    /// space.
+    ///
+    /// A view that goes fullscreen is raised and leaves Split View.
+    pub fn set_fullscreen(
+        &mut self,
+        surface: &ViewShell,
+        fullscreen: bool,
+        area: Rectangle,
+        screen: Rectangle,
+    ) -> bool {
+        let view = match self.views.iter().find(|x| &x.shell == surface) {
+            Some(view) => Rc::clone(view),
+            None => return false,
+        };
+
+        if fullscreen {
+            if self.split.as_ref().map_or(false, |split| split.contains(&view)) {
+                self.end_split(&view);
+            } else if let Some(index) = self.views.iter().position(|v| Rc::ptr_eq(v, &view)) {
+                self.raise_view(index);
+            }
+        }
+
+        view.set_fullscreen(fullscreen);
+        view.configure(if fullscreen { screen } else { area });
+        true
+    }
+
+    /// Tiles the view to the right of the topmost other view.
+    pub fn split_with(&mut self, view: &Rc<View>, area: Rectangle) {
+        let left = match self.views.iter().rev().find(|v| !Rc::ptr_eq(v, view)) {
+            Some(left) => Rc::clone(left),
+            None => return,
+        };
+        if let Some(split) = self.split.take() {
+            split.left.configure(split.area());
+            split.right.configure(split.area());
+        }
+        self.split = Some(Split::new(left, Rc::clone(view), area));
+    }
+
+    /// Leaves Split View, raising the view that remains.
+    fn end_split(&mut self, keep: &Rc<View>) {
+        let split = match self.split.take() {
+            Some(split) => split,
+            None => return,
+        };
+        split.left.configure(split.area());
+        split.right.configure(split.area());
+        if let Some(index) = self.views.iter().position(|v| Rc::ptr_eq(v, keep)) {
+            self.raise_view(index);
+        }
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.split.is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.views.is_empty()
+    }
+
+    pub fn views(&self) -> &[Rc<View>] {
+        &self.views
+    }
+
+    /// Returns the topmost view that can have keyboard focus.
+    pub fn top_view(&self) -> Option<Rc<View>> {
+        self.views
+            .iter()
+            .rev()
+            .find(|view| view.wants_focus())
+            .map(Rc::clone)
+    }
+
+    /// Returns the screen area that the space’s views were configured for, if they were.
+    pub fn screen_area(&self) -> Option<Rectangle> {
+        self.areas.map(|(_, screen)| screen)
+    }
+
+    /// Returns the view area if a view fills it, so that nothing behind the space can be seen
+    /// there.
+    ///
+    /// Views are assumed to be opaque, while the Slide Over panel and layers have none that
+    /// fill them.
+    pub fn opaque_area(&self) -> Option<Rectangle> {
+        let (area, _) = self.areas.filter(|_| !self.floating)?;
+        if self.views.iter().any(|view| view.covers(area)) {
+            Some(area)
+        } else {
+            None
+        }
+    }
+
+    /// Moves the bottommost view to the top.
+    pub fn cycle_views(&mut self) {
+        if self.views.len() > 1 {
+            self.raise_view(0);
+        }
+    }
+
+    /// Forgets which surface has pointer focus, so that it’s entered again on the next event.
+    pub fn reset_pointer_focus(&mut self) {
+        self.pointer_target = None;
+        self.pointer_surface = None;
+    }
+
+    /// Moves the view at the given index to the top and returns its new index.
+    fn raise_view(&mut self, index: usize) -> usize {
+        let view = self.views.remove(index);
+        self.views.push(view);
+        let top = self.views.len() - 1;
+        self.pointer_target = match self.pointer_target {
+            Some(target) if target == index => Some(top),
+            target => index_after_removal(target, index),
+        };
+        top
+    }
+
     pub fn render(
         &mut self,
         matrix: Matrix4<f32>,
-        renderer: &Renderer,
+        transform: SpaceTransform,
         wlr_renderer: &mut wlroots::Renderer,
+        renderer: &Renderer,
     ) {
-        if self.home_bar.is_none() {
-            let (width, height) = renderer.dimensions();
-            let resolution = renderer.resolution();
+        if let Some(ref mut split) = self.split {
+            split.update();
+        }
+
+        // the topmost view is the last one, and views below one that fills the space are hidden
+        let first = match self.areas {
+            Some((area, _)) if !self.floating => self
+                .views
+                .iter()
+                .rposition(|view| view.covers(area))
+                .unwrap_or(0),
+            _ => 0,
+        };
+        for view in &self.views[first..] {
+            view.render_decoration(matrix, transform, renderer, !self.floating);
+            view.render(transform, renderer.projection(), renderer.resolution(), wlr_renderer);
+        }
+    }
+
+    /// Renders the Split View divider, if any.
+    pub fn render_divider(
+        &mut self,
+        matrix: Matrix4<f32>,
+        transform: SpaceTransform,
+        renderer: &Renderer,
+    ) {
+        if let Some(ref mut split) = self.split {
+            split.render_divider(matrix, transform, renderer);
+        }
+    }
+
+    /// Renders the home bar, which is only shown for the frontmost space.
+    ///
+    /// While the home bar is hidden it’s still created so that it can be swiped up. It’s created
+    /// again when the screen is rotated or its scale changes.
+    pub fn render_home_bar(&mut self, matrix: Matrix4<f32>, renderer: &Renderer, hidden: bool) {
+        let (width, height) = renderer.dimensions();
+        let resolution = renderer.resolution();
+        let current = self
+            .home_bar
+            .as_ref()
+            .map_or(false, |home_bar| home_bar.is_current(width, height, resolution));
+        if !current {
             self.home_bar = Some(HomeBar::new(width, height, resolution));
         }
 
-        for view in self.views.iter_mut().rev() {
-            view.render(matrix, wlr_renderer);
+        if !hidden {
+            self.home_bar.as_mut().unwrap().render(matrix);
         }
+    }
 
-        self.home_bar.as_mut().unwrap().render(matrix);
+    /// Asks all views to close.
+    pub fn close_views(&self) {
+        for view in &self.views {
+            view.shell.close();
+        }
     }
 
-    #[wlroots_dehandle(seat)]
     pub fn handle_event(&mut self, event: Event, server: &mut Server) {
-        if let Some(ref mut home_bar) = self.home_bar {
-            match event {
-                Event::TouchDown { location, .. } => {
-                    if home_bar.should_capture_event(location.x, location.y) {
-                        self.home_bar_captured_events = true;
-                        home_bar.handle_event(event);
-                        return;
+        match event {
+            Event::PointerDown { .. }
+            | Event::PointerUp { .. }
+            | Event::PointerMotion { .. }
+            | Event::PointerAxis { .. } => self.handle_pointer_event(event, server),
+            Event::TouchDown { .. }
+            | Event::TouchMotion { .. }
+            | Event::TouchUp { .. }
+            | Event::TouchCancel { .. } => self.handle_touch_event(event, server),
+            Event::TabletProximityIn { .. }
+            | Event::TabletProximityOut { .. }
+            | Event::TabletTipDown { .. }
+            | Event::TabletTipUp { .. }
+            | Event::TabletButtonDown { .. }
+            | Event::TabletButtonUp { .. }
+            | Event::TabletAxis { .. } => self.handle_tablet_event(event, server),
+            _ => (),
+        }
+    }
+
+    /// Sends tablet tool events to the view under the tool, or to the view the tip went down
+    /// on while it’s touching the screen.
+    fn handle_tablet_event(&mut self, event: Event, server: &mut Server) {
+        let tablet = match server.tablet {
+            Some(ref mut tablet) => tablet,
+            None => return,
+        };
+        let tool = event.tablet_tool().unwrap();
+
+        match event {
+            Event::TabletProximityIn { location, .. } => {
+                let view = self.view_at(location).map(|i| Rc::clone(&self.views[i]));
+                tablet.set_focus(tool, view, location);
+            }
+            Event::TabletAxis { location, axes, .. } => {
+                let view = if tablet.tip_down(tool) {
+                    tablet.focus(tool)
+                } else {
+                    self.view_at(location).map(|i| Rc::clone(&self.views[i]))
+                };
+                tablet.set_focus(tool, view, location);
+                tablet.axis(tool, axes);
+            }
+            Event::TabletTipDown { .. } => {
+                // drawing on a view raises it, which also moves keyboard focus to it
+                if let Some(view) = tablet.focus(tool) {
+                    if let Some(index) = self.views.iter().position(|v| Rc::ptr_eq(v, &view)) {
+                        self.raise_view(index);
                     }
                 }
-                Event::TouchMotion { .. } => {
-                    if self.home_bar_captured_events {
-                        home_bar.handle_event(event);
+                tablet.tip(tool, true);
+            }
+            Event::TabletTipUp { .. } => tablet.tip(tool, false),
+            Event::TabletButtonDown { button, .. } => tablet.button(tool, button, true),
+            Event::TabletButtonUp { button, .. } => tablet.button(tool, button, false),
+            Event::TabletProximityOut { .. } => tablet.proximity_out(tool),
+            _ => (),
+        }
+    }
+
+    /// Sends touch events to whatever each touch point went down on, even if it has since left
+    /// it.
+    #[wlroots_dehandle(seat)]
+    fn handle_touch_event(&mut self, event: Event, server: &mut Server) {
+        let time = current_time();
+
+        match event {
+            Event::TouchDown { id, location } => {
+                // the on-screen keyboard is above the views
+                let keyboard_captures = server
+                    .renderer()
+                    .map_or(false, |renderer| renderer.keyboard().contains(location));
+                if keyboard_captures {
+                    server.input.touch_down(id, location, TouchFocus::Keyboard);
+                    server.handle_keyboard_event(event);
+                    return;
+                }
+
+                let home_bar_captures = match self.home_bar {
+                    Some(ref home_bar) => {
+                        !server.input.home_bar_captured()
+                            && home_bar.should_capture_event(location.x, location.y)
+                    }
+                    None => false,
+                };
+                if home_bar_captures {
+                    server.input.touch_down(id, location, TouchFocus::HomeBar);
+                    let gesture = self.home_bar.as_mut().unwrap().handle_event(event);
+                    server.handle_home_bar_gesture(gesture);
+                    return;
+                }
+
+                if let Some(ref mut split) = self.split {
+                    if split.divider_contains(location) {
+                        split.drag_start(Some(id));
+                        server.input.touch_down(id, location, TouchFocus::Divider);
                         return;
                     }
                 }
-                Event::TouchUp { .. } => {
-                    if self.home_bar_captured_events {
-                        home_bar.handle_event(event);
-                        self.home_bar_captured_events = false;
+
+                if let Some((index, part)) = self.decoration_at(location) {
+                    self.handle_decoration(index, part, server);
+                    server.input.touch_down(id, location, TouchFocus::None);
+                    return;
+                }
+
+                let (index, surface_h, origin) = match self.surface_at(location) {
+                    Some(hit) => hit,
+                    None => {
+                        server.dismiss_popups_outside(None);
+                        server.input.touch_down(id, location, TouchFocus::None);
                         return;
                     }
+                };
+
+                // tapping a view raises it, which also moves keyboard focus to it
+                let index = self.raise_view(index);
+                let view = Rc::clone(&self.views[index]);
+                server.dismiss_popups_outside(Some(&view));
+
+                {
+                    let local = location - origin;
+                    let seat_h = &server.seat;
+                    use seat_h as seat;
+                    let _ = surface_h.run(|surface| {
+                        seat.touch_notify_down(surface, time, id, local.x, local.y);
+                    });
+                }
+                server.input.touch_down(id, location, TouchFocus::View(view, origin));
+            }
+            Event::TouchMotion { id, location } => {
+                let focus = match server.input.touch_motion(id, location) {
+                    Some(point) => point.focus.clone(),
+                    None => return,
+                };
+
+                match focus {
+                    TouchFocus::HomeBar => {
+                        if let Some(ref mut home_bar) = self.home_bar {
+                            let gesture = home_bar.handle_event(event);
+                            server.handle_home_bar_gesture(gesture);
+                        }
+                    }
+                    TouchFocus::Keyboard => server.handle_keyboard_event(event),
+                    TouchFocus::Divider => {
+                        if let Some(ref mut split) = self.split {
+                            split.drag_move(Some(id), location.x);
+                        }
+                    }
+                    TouchFocus::View(_, origin) => {
+                        let local = location - origin;
+                        let seat_h = &server.seat;
+                        use seat_h as seat;
+                        seat.touch_notify_motion(time, id, local.x, local.y);
+                    }
+                    TouchFocus::TopEdge(_) | TouchFocus::None => (),
                 }
-                _ => (),
             }
+            Event::TouchUp { id } | Event::TouchCancel { id } => {
+                let point = match server.input.touch_up(id) {
+                    Some(point) => point,
+                    None => return,
+                };
+
+                match point.focus {
+                    TouchFocus::HomeBar => {
+                        if let Some(ref mut home_bar) = self.home_bar {
+                            let gesture = home_bar.handle_event(event);
+                            server.handle_home_bar_gesture(gesture);
+                        }
+                    }
+                    TouchFocus::Keyboard => server.handle_keyboard_event(event),
+                    TouchFocus::Divider => self.end_divider_drag(Some(id)),
+                    TouchFocus::View(..) => {
+                        // wlroots can’t cancel touch points, so cancelled points are lifted
+                        let seat_h = &server.seat;
+                        use seat_h as seat;
+                        seat.touch_notify_up(time, id);
+                    }
+                    TouchFocus::TopEdge(_) | TouchFocus::None => (),
+                }
+            }
+            _ => (),
         }
+    }
+
+    /// Returns the index of the topmost view at the given point.
+    fn view_at(&self, location: Vector2<f64>) -> Option<usize> {
+        self.views.iter().rposition(|view| view.contains_point(location))
+    }
+
+    /// Returns true if there’s a view at the given point.
+    pub fn contains_point(&self, location: Vector2<f64>) -> bool {
+        self.view_at(location).is_some()
+    }
 
-        let mut event_target = None;
-        let mut target_index = self.views.len();
-        if event.is_pointer_event()
-            && ((server.touch_grabbed || server.pointer_grabbed) || event.location().is_none())
+    /// Returns the topmost surface at the given point, the index of its view, and its origin in
+    /// screen coordinates.
+    fn surface_at(&self, location: Vector2<f64>) -> Option<(usize, SurfaceHandle, Vector2<f64>)> {
+        self.views
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(index, view)| {
+                view.surface_at(location)
+                    .map(|(surface, origin)| (index, surface, origin))
+            })
+            .next()
+    }
+
+    /// Sends pointer events to the surface under the cursor, or to the surface that received
+    /// the button press while buttons are held.
+    ///
+    /// Popup grabs are handled by wlroots, which dismisses popups when a surface of a different
+    /// client is clicked.
+    #[wlroots_dehandle(seat)]
+    fn handle_pointer_event(&mut self, event: Event, server: &mut Server) {
+        let time = current_time();
+
+        if let Event::PointerMotion { location } = event {
+            self.pointer_location = location;
+        }
+        let location = self.pointer_location;
+
+        if self.handle_divider_pointer_event(event)
+            || self.handle_decoration_pointer_event(event, server)
         {
-            if let Some(target) = self.pointer_target {
-                event_target = Some(&self.views[target]);
-                target_index = target;
-            }
+            return;
         }
 
-        if event_target.is_none() {
-            if let Some(loc) = event.location() {
-                for view in self.views.iter().rev() {
-                    target_index -= 1;
-                    if view.contains_point(loc) {
-                        event_target = Some(view);
-                        break;
+        if self.pointer_buttons == 0 {
+            let (target, surface) = match self.surface_at(location) {
+                Some((index, surface, origin)) => (Some(index), Some((surface, origin))),
+                None => (None, None),
+            };
+
+            let surface_changed = surface.as_ref().map(|(surface, _)| surface)
+                != self.pointer_surface.as_ref().map(|(surface, _)| surface);
+
+            if surface_changed {
+                {
+                    let seat_h = &server.seat;
+                    use seat_h as seat;
+
+                    match &surface {
+                        Some((surface_h, origin)) => {
+                            let local = location - origin;
+                            let _ = surface_h.run(|surface| {
+                                seat.pointer_notify_enter(surface, local.x, local.y);
+                            });
+                        }
+                        None => seat.pointer_clear_focus(),
                     }
                 }
+
+                // the newly entered client will set its own cursor
+                server.client_cursor = None;
             }
+
+            self.pointer_target = target;
+            self.pointer_surface = surface;
         }
 
-        if let Some(event_target) = event_target {
-            if event.is_pointer_event() {
-                if let Some(loc) = event.location() {
-                    if Some(target_index) != self.pointer_target {
-                        let (sx, sy) = event_target.map_location(loc);
-                        event_target.with_surface(|surf| {
-                            let seat_h = &server.seat;
-                            use seat_h as seat;
-                            seat.pointer_notify_enter(surf, sx, sy);
-                        });
-                        self.pointer_target = Some(target_index);
-                    }
+        // clicking a view raises it, which also moves keyboard focus to it
+        if let Event::PointerDown { .. } = event {
+            if let Some(index) = self.pointer_target {
+                self.raise_view(index);
+            }
+        }
+
+        let seat_h = &server.seat;
+        use seat_h as seat;
+
+        match event {
+            Event::PointerMotion { .. } => {
+                if let Some((_, origin)) = self.pointer_surface {
+                    let local = location - origin;
+                    seat.pointer_notify_motion(time, local.x, local.y);
                 }
+            }
+            Event::PointerDown { button } => {
+                self.pointer_buttons += 1;
+                seat.pointer_notify_button(time, button, WLR_BUTTON_PRESSED as u32);
+            }
+            Event::PointerUp { button } => {
+                self.pointer_buttons = self.pointer_buttons.saturating_sub(1);
+                seat.pointer_notify_button(time, button, WLR_BUTTON_RELEASED as u32);
+            }
+            Event::PointerAxis {
+                source,
+                orientation,
+                delta,
+                delta_discrete,
+            } => {
+                seat.pointer_notify_axis(time, orientation, delta, delta_discrete, source);
+            }
+            _ => (),
+        }
 
-                let seat_h = &server.seat;
-                use seat_h as seat;
+        seat.pointer_notify_frame();
+    }
 
-                let time = current_time();
+    /// Returns the topmost view whose title strip is at the given point and the part of the strip.
+    fn decoration_at(&self, location: Vector2<f64>) -> Option<(usize, DecorationPart)> {
+        for (index, view) in self.views.iter().enumerate().rev() {
+            if let Some(part) = view.decoration_part_at(location, !self.floating) {
+                return Some((index, part));
+            }
+            if view.contains_point(location) {
+                // covered by a view above
+                return None;
+            }
+        }
+        None
+    }
 
-                match event {
-                    Event::PointerDown { button } => {
-                        seat.pointer_notify_button(time, button, WLR_BUTTON_PRESSED as u32);
-                    }
-                    Event::PointerMotion { .. } => {
-                        // TODO: seat.pointer_notify_motion(time, sx, sy);
-                    }
-                    Event::PointerAxis { source, orientation, delta } => {
-                        // FIXME: where does value_discrete come from?
-                        seat.pointer_notify_axis(
-                            time,
-                            orientation,
-                            delta,
-                            0,
-                            source,
-                        );
-                    }
-                    Event::TouchDown { id, location } => {
-                        let (sx, sy) = event_target.map_location(location);
-                        event_target.with_surface(|surf| {
-                            seat.touch_notify_down(surf, time, id, sx, sy);
-                        });
-                    }
-                    Event::TouchMotion { id, location } => {
-                        let (sx, sy) = event_target.map_location(location);
-                        seat.touch_notify_motion(time, id, sx, sy);
-                    }
-                    Event::TouchUp { id } => {
-                        seat.touch_notify_up(time, id);
+    /// Does what a title strip button does.
+    fn handle_decoration(&mut self, index: usize, part: DecorationPart, server: &mut Server) {
+        match part {
+            DecorationPart::Title => {
+                self.raise_view(index);
+            }
+            DecorationPart::CloseButton => self.views[index].shell.close(),
+            DecorationPart::FullscreenButton => {
+                let shell = self.views[index].shell.clone();
+                let areas = self.areas.or_else(|| {
+                    server
+                        .renderer()
+                        .map(|renderer| (renderer.view_area(), renderer.screen_area()))
+                });
+                if let Some((area, screen)) = areas {
+                    self.set_fullscreen(&shell, true, area, screen);
+                }
+            }
+        }
+    }
+
+    /// Lets the pointer press title strip buttons and returns true if it did.
+    fn handle_decoration_pointer_event(&mut self, event: Event, server: &mut Server) -> bool {
+        match event {
+            Event::PointerDown { .. } if self.pointer_buttons == 0 => {
+                match self.decoration_at(self.pointer_location) {
+                    Some((index, part)) => {
+                        self.decoration_pressed = true;
+                        self.handle_decoration(index, part, server);
+                        true
                     }
-                    _ => warn!("Unhandled event"),
+                    None => false,
                 }
             }
+            Event::PointerUp { .. } if self.decoration_pressed => {
+                self.decoration_pressed = false;
+                true
+            }
+            _ => false,
         }
     }
+
+    /// Lets the pointer drag the Split View divider and returns true if it did.
+    fn handle_divider_pointer_event(&mut self, event: Event) -> bool {
+        let location = self.pointer_location;
+        let split = match self.split {
+            Some(ref mut split) => split,
+            None => return false,
+        };
+
+        match event {
+            Event::PointerDown { .. }
+                if self.pointer_buttons == 0 && split.divider_contains(location) =>
+            {
+                split.drag_start(None);
+                true
+            }
+            Event::PointerMotion { .. } if split.is_dragging(None) => {
+                split.drag_move(None, location.x);
+                true
+            }
+            Event::PointerUp { .. } if split.is_dragging(None) => {
+                self.end_divider_drag(None);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Releases the divider, which leaves Split View if it was released at an edge.
+    fn end_divider_drag(&mut self, id: Option<TouchId>) {
+        let keep = match self.split {
+            Some(ref mut split) => split.drag_end(id),
+            None => return,
+        };
+        if let Some(keep) = keep {
+            self.end_split(&keep);
+        }
+    }
+}
+
+/// Adjusts a view index after the view at `removed` was removed.
+fn index_after_removal(index: Option<usize>, removed: usize) -> Option<usize> {
+    match index {
+        Some(index) if index == removed => None,
+        Some(index) if index > removed => Some(index - 1),
+        index => index,
+    }
 }
@@ -0,0 +1,224 @@
+//! Split View, which tiles two views side by side with a draggable divider between them.
+
+use crate::renderer::{DividerHandle, Renderer};
+use crate::space::SpaceTransform;
+use crate::spring::{RealTimeSpring, Spring};
+use crate::view::View;
+use cairo::Rectangle;
+use cgmath::{Matrix4, Vector2};
+use std::rc::Rc;
+use std::time::Instant;
+use wlroots::TouchId;
+
+/// Width of the gap between the two views.
+pub const DIVIDER_WIDTH: f64 = 8.;
+
+/// How far outside the gap the divider can still be grabbed.
+const DIVIDER_SLOP: f64 = 8.;
+
+/// Where the divider snaps to, as a fraction of the width.
+const SNAP_POINTS: [f64; 3] = [1. / 3., 1. / 2., 2. / 3.];
+
+/// Releasing the divider closer than this to an edge (as a fraction of the width) ends the split.
+const DISMISS_FRACTION: f64 = 0.15;
+
+/// How far ahead the divider is projected with its velocity when picking a snap point.
+const POSITION_PROJECTION: f64 = 0.2;
+
+#[derive(Debug)]
+struct DividerDrag {
+    /// The touch point, or None for the pointer.
+    id: Option<TouchId>,
+    prev_time: Instant,
+}
+
+/// Two views tiled left and right.
+#[derive(Debug)]
+pub struct Split {
+    pub left: Rc<View>,
+    pub right: Rc<View>,
+    area: Rectangle,
+    /// The divider position as a fraction of the width.
+    position: RealTimeSpring,
+    drag: Option<DividerDrag>,
+    /// The width of the left column when the views were last configured.
+    configured_width: Option<f64>,
+    handle: Option<DividerHandle>,
+}
+
+impl Split {
+    pub fn new(left: Rc<View>, right: Rc<View>, area: Rectangle) -> Split {
+        let mut position = RealTimeSpring::new(Spring::new(1., 0.3));
+        position.spring.value = 0.5;
+        position.spring.target = 0.5;
+
+        let mut split = Split {
+            left,
+            right,
+            area,
+            position,
+            drag: None,
+            configured_width: None,
+            handle: None,
+        };
+        split.configure_views();
+        split
+    }
+
+    /// Returns true if the view is one of the two tiled views.
+    pub fn contains(&self, view: &Rc<View>) -> bool {
+        Rc::ptr_eq(&self.left, view) || Rc::ptr_eq(&self.right, view)
+    }
+
+    /// Returns the view on the other side.
+    pub fn other(&self, view: &Rc<View>) -> Rc<View> {
+        if Rc::ptr_eq(&self.left, view) {
+            Rc::clone(&self.right)
+        } else {
+            Rc::clone(&self.left)
+        }
+    }
+
+    /// Returns the area both views are tiled in.
+    pub fn area(&self) -> Rectangle {
+        self.area
+    }
+
+    /// Moves the split into a new area and reconfigures both views.
+    pub fn set_area(&mut self, area: Rectangle) {
+        self.area = area;
+        self.configured_width = None;
+        self.handle = None;
+        self.configure_views();
+    }
+
+    fn divider_x(&self) -> f64 {
+        self.area.x + self.area.width * self.position.spring.value
+    }
+
+    /// Returns the areas of the left and right view.
+    fn columns(&self) -> (Rectangle, Rectangle) {
+        let divider_x = self.divider_x();
+        let left_width = (divider_x - DIVIDER_WIDTH / 2. - self.area.x).max(0.).round();
+        let right_x = self.area.x + left_width + DIVIDER_WIDTH;
+        let right_width = (self.area.x + self.area.width - right_x).max(0.);
+        (
+            Rectangle {
+                width: left_width,
+                ..self.area
+            },
+            Rectangle {
+                x: right_x,
+                width: right_width,
+                ..self.area
+            },
+        )
+    }
+
+    /// Configures both views to their columns if the divider moved by at least a pixel.
+    fn configure_views(&mut self) {
+        let (left, right) = self.columns();
+        if self.configured_width == Some(left.width) {
+            return;
+        }
+        self.configured_width = Some(left.width);
+        self.left.configure(left);
+        self.right.configure(right);
+    }
+
+    /// Advances the divider animation; should be called once per frame.
+    pub fn update(&mut self) {
+        if self.drag.is_some() {
+            self.position.update_time();
+        } else if self.position.spring.needs_update(0.0001) {
+            self.position.update();
+        } else {
+            self.position.spring.finish();
+        }
+        self.configure_views();
+    }
+
+    /// Returns true if the point is on the divider.
+    pub fn divider_contains(&self, point: Vector2<f64>) -> bool {
+        let in_area = point.y >= self.area.y && point.y < self.area.y + self.area.height;
+        in_area && (point.x - self.divider_x()).abs() < DIVIDER_WIDTH / 2. + DIVIDER_SLOP
+    }
+
+    /// Returns true if the divider is being dragged by the given touch point, or the pointer
+    /// for None.
+    pub fn is_dragging(&self, id: Option<TouchId>) -> bool {
+        self.drag.as_ref().map_or(false, |drag| drag.id == id)
+    }
+
+    pub fn drag_start(&mut self, id: Option<TouchId>) {
+        if self.drag.is_some() {
+            return;
+        }
+        self.position.spring.velocity = 0.;
+        self.drag = Some(DividerDrag {
+            id,
+            prev_time: Instant::now(),
+        });
+    }
+
+    pub fn drag_move(&mut self, id: Option<TouchId>, x: f64) {
+        let drag = match self.drag {
+            Some(ref mut drag) if drag.id == id => drag,
+            _ => return,
+        };
+
+        let position = ((x - self.area.x) / self.area.width).max(0.).min(1.);
+        self.position.drag_to(position, &mut drag.prev_time);
+        self.position.spring.target = position;
+        self.configure_views();
+    }
+
+    /// Ends the drag and snaps the divider, or returns the view that should remain if the divider
+    /// was released at an edge.
+    pub fn drag_end(&mut self, id: Option<TouchId>) -> Option<Rc<View>> {
+        if !self.is_dragging(id) {
+            return None;
+        }
+        self.drag = None;
+
+        let spring = self.position.spring;
+        let projected = spring.value + spring.velocity * POSITION_PROJECTION;
+        if projected < DISMISS_FRACTION {
+            return Some(Rc::clone(&self.right));
+        }
+        if projected > 1. - DISMISS_FRACTION {
+            return Some(Rc::clone(&self.left));
+        }
+
+        let mut snap = SNAP_POINTS[0];
+        for point in &SNAP_POINTS {
+            if (point - projected).abs() < (snap - projected).abs() {
+                snap = *point;
+            }
+        }
+        self.position.spring.target = snap;
+        None
+    }
+
+    /// Renders the divider handle with the given space transform.
+    pub fn render_divider(
+        &mut self,
+        matrix: Matrix4<f32>,
+        transform: SpaceTransform,
+        renderer: &Renderer,
+    ) {
+        let area = self.area;
+        let resolution = renderer.resolution();
+        if self.handle.as_ref().map_or(false, |h| h.resolution() != resolution) {
+            self.handle = None;
+        }
+        let handle = self
+            .handle
+            .get_or_insert_with(|| DividerHandle::new(area.height, resolution));
+        let origin = transform.apply(Vector2::new(
+            self.area.x + self.area.width * self.position.spring.value - DIVIDER_WIDTH / 2.,
+            self.area.y,
+        ));
+        handle.render(matrix, origin.x, origin.y, transform.scale);
+    }
+}
@@ -1,8 +1,27 @@
+use crate::damage;
+use cairo::Rectangle;
 use std::f64::consts::PI;
+use std::ops::Div;
 use std::time::{Duration, Instant};
 
 const MAX_TIME_INTERVAL: f64 = 1. / 60.;
 
+/// How close to its target a real-time spring has to be to stop asking for frames.
+const SETTLED_TOLERANCE: f64 = 0.0001;
+
+/// Returns the velocity of a drag that moved by `delta` since `prev_time`, which is reset to
+/// now, or `None` if no time has passed.
+pub fn drag_velocity<V: Div<f64, Output = V>>(delta: V, prev_time: &mut Instant) -> Option<V> {
+    let elapsed = prev_time.elapsed();
+    *prev_time = Instant::now();
+    let elapsed_secs = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
+    if elapsed_secs > 0. {
+        Some(delta / elapsed_secs)
+    } else {
+        None
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Spring {
     pub value: f64,
@@ -74,9 +93,36 @@ impl RealTimeSpring {
         self.prev_update = Instant::now();
     }
 
+    /// Advances the spring to the current time, and asks for another frame if it’s still
+    /// moving.
     pub fn update(&mut self) -> f64 {
+        if self.advance() {
+            damage::request_frame();
+        }
+        self.spring.value
+    }
+
+    /// Same as `update`, but the frame only redraws the given area, which has to cover
+    /// everything the spring moves.
+    pub fn update_in(&mut self, area: Rectangle) -> f64 {
+        if self.advance() {
+            damage::request_frame_in(area);
+        }
+        self.spring.value
+    }
+
+    /// Advances the spring to the current time and returns true if it’s still moving.
+    fn advance(&mut self) -> bool {
         self.spring.update(self.prev_update.elapsed());
         self.update_time();
-        self.spring.value
+        self.spring.needs_update(SETTLED_TOLERANCE)
+    }
+
+    /// Moves the spring to where it was dragged, and takes over the velocity of the drag.
+    pub fn drag_to(&mut self, value: f64, prev_time: &mut Instant) {
+        if let Some(velocity) = drag_velocity(value - self.spring.value, prev_time) {
+            self.spring.velocity = velocity;
+        }
+        self.spring.value = value;
     }
 }
@@ -13,6 +13,8 @@ const CHECK_INTERVAL: u64 = 5;
 pub struct BatteryIndicator {
     battery_state: Result<BatteryState, io::Error>,
     prev_bat_check: Instant,
+    /// The state that was drawn last, which is `None` inside if it couldn’t be read.
+    drawn: Option<Option<BatteryState>>,
 }
 
 impl BatteryIndicator {
@@ -20,16 +22,27 @@ impl BatteryIndicator {
         BatteryIndicator {
             battery_state: battery_status(),
             prev_bat_check: Instant::now(),
+            drawn: None,
         }
     }
-}
 
-impl StatusIndicator for BatteryIndicator {
-    fn draw(&mut self, ctx: &Context, right_inset: &mut f64, height: f64) {
+    fn check_battery(&mut self) {
         if self.prev_bat_check.elapsed().as_secs() > CHECK_INTERVAL {
             self.battery_state = battery_status();
             self.prev_bat_check = Instant::now();
         }
+    }
+}
+
+impl StatusIndicator for BatteryIndicator {
+    fn has_changed(&mut self) -> bool {
+        self.check_battery();
+        self.drawn != Some(self.battery_state.as_ref().ok().cloned())
+    }
+
+    fn draw(&mut self, ctx: &Context, right_inset: &mut f64, height: f64) {
+        self.check_battery();
+        self.drawn = Some(self.battery_state.as_ref().ok().cloned());
 
         let font_extents = ctx.font_extents();
         let text_y = height / 2. - font_extents.descent + font_extents.height / 2.;
@@ -2,28 +2,40 @@ use crate::status::StatusIndicator;
 use cairo::Context;
 
 #[derive(Debug)]
-pub struct ClockIndicator;
+pub struct ClockIndicator {
+    /// The text that was drawn last.
+    drawn: Option<String>,
+}
 
 impl ClockIndicator {
     pub fn new() -> ClockIndicator {
-        ClockIndicator
+        ClockIndicator { drawn: None }
     }
 }
 
+/// Returns the weekday and the time, which only change every minute.
+fn time_text() -> String {
+    let time = time::now();
+    let day = match time.tm_wday {
+        0 => "Sun",
+        1 => "Mon",
+        2 => "Tue",
+        3 => "Wed",
+        4 => "Thu",
+        5 => "Fri",
+        6 => "Sat",
+        _ => "Judgment Day",
+    };
+    format!("{} {}:{:02}", day, time.tm_hour, time.tm_min)
+}
+
 impl StatusIndicator for ClockIndicator {
+    fn has_changed(&mut self) -> bool {
+        self.drawn.as_ref() != Some(&time_text())
+    }
+
     fn draw(&mut self, ctx: &Context, right_inset: &mut f64, height: f64) {
-        let time = time::now();
-        let day = match time.tm_wday {
-            0 => "Sun",
-            1 => "Mon",
-            2 => "Tue",
-            3 => "Wed",
-            4 => "Thu",
-            5 => "Fri",
-            6 => "Sat",
-            _ => "Judgment Day",
-        };
-        let time_text = format!("{} {}:{:02}", day, time.tm_hour, time.tm_min);
+        let time_text = time_text();
 
         let font_extents = ctx.font_extents();
         let text_extents = ctx.text_extents(&time_text);
@@ -33,5 +45,6 @@ impl StatusIndicator for ClockIndicator {
             height / 2. - font_extents.descent + font_extents.height / 2.,
         );
         ctx.show_text(&time_text);
+        self.drawn = Some(time_text);
     }
 }
@@ -1,10 +1,13 @@
 use crate::renderer::CairoTex;
-use cairo::Context;
+use cairo::{Context, Rectangle};
 use cgmath::Matrix4;
 use std::f64::consts::PI;
 use std::fmt;
 
-const STATUS_HEIGHT: f64 = 22.;
+pub const STATUS_HEIGHT: f64 = 22.;
+
+/// How far text may be drawn outside of its extents.
+const INDICATOR_OVERHANG: f64 = 2.;
 
 pub mod battery;
 pub mod clock;
@@ -13,9 +16,15 @@ pub mod clock;
 pub struct StatusBar {
     inner: CairoTex,
     indicators: Vec<Box<dyn StatusIndicator>>,
+    /// The left and right edge of each indicator when it was last drawn, which is empty until
+    /// the status bar is drawn for the first time.
+    extents: Vec<(f64, f64)>,
 }
 
 pub trait StatusIndicator: fmt::Debug {
+    /// Returns true if the indicator would look different than when it was last drawn.
+    fn has_changed(&mut self) -> bool;
+
     fn draw(&mut self, ctx: &Context, right_inset: &mut f64, height: f64);
 }
 
@@ -27,31 +36,104 @@ impl StatusBar {
                 Box::new(clock::ClockIndicator::new()),
                 Box::new(battery::BatteryIndicator::new()),
             ],
+            extents: Vec::new(),
+        }
+    }
+
+    /// Draws the indicators from right to left and returns where each of them was drawn.
+    fn draw_indicators(&mut self) -> Vec<(f64, f64)> {
+        let ctx = self.inner.context();
+        let (width, height) = self.inner.size();
+        ctx.set_source_rgba(1., 1., 1., 1.);
+        ctx.set_font_size(12.);
+
+        let mut right_x = width - 16.;
+        let mut extents = Vec::new();
+        for indicator in self.indicators.iter_mut() {
+            let right = right_x;
+            indicator.draw(ctx, &mut right_x, height);
+            extents.push((right_x, right));
+            right_x -= 8.;
+        }
+        extents
+    }
+
+    /// Returns where each indicator would be drawn now, without drawing anything.
+    fn layout(&mut self) -> Vec<(f64, f64)> {
+        let ctx = self.inner.context();
+        let resolution = self.inner.resolution();
+        ctx.save();
+        ctx.identity_matrix();
+        ctx.scale(resolution, resolution);
+        ctx.new_path();
+        ctx.rectangle(0., 0., 0., 0.);
+        ctx.clip();
+        let extents = self.draw_indicators();
+        self.inner.context().restore();
+        extents
+    }
+
+    /// Returns the part of the status bar that changes if the indicators are drawn again,
+    /// or `None` if nothing changes.
+    ///
+    /// Indicators are placed from right to left, so the first one that changed moves all of the
+    /// ones after it.
+    fn changed_area(&mut self) -> Option<Rectangle> {
+        let (width, height) = self.inner.size();
+        let whole = Rectangle {
+            x: 0.,
+            y: 0.,
+            width,
+            height,
+        };
+        let changed: Vec<_> = self
+            .indicators
+            .iter_mut()
+            .map(|indicator| indicator.has_changed())
+            .collect();
+        if self.extents.len() != self.indicators.len() {
+            return Some(whole);
         }
+        let first = changed.iter().position(|changed| *changed)?;
+
+        let extents = self.layout();
+        let left = self.extents[first..]
+            .iter()
+            .chain(&extents[first..])
+            .map(|(left, _)| *left)
+            .fold(self.extents[first].1, f64::min);
+        let left = (left - INDICATOR_OVERHANG).floor().max(0.);
+        let right = (self.extents[first].1 + INDICATOR_OVERHANG)
+            .ceil()
+            .min(width);
+        Some(Rectangle {
+            x: left,
+            y: 0.,
+            width: right - left,
+            height,
+        })
     }
 
     fn draw(&mut self) {
+        let area = match self.changed_area() {
+            Some(area) => area,
+            None => return,
+        };
         let ctx = self.inner.context();
         let (width, height) = self.inner.size();
 
-        self.inner.clear();
+        // everything is drawn again, but only the changed area is cleared and copied
+        self.inner.clear_area(area);
 
         ctx.set_source_rgba(0., 0., 0., 0.3);
         ctx.rectangle(0., 0., width, height);
         ctx.fill();
 
         ctx.set_source_rgba(1., 1., 1., 1.);
-        ctx.set_font_size(12.);
-
         ctx.arc(21., height / 2., 5., 0., PI * 2.);
         ctx.fill();
 
-        let mut right_x = width - 16.;
-        for indicator in self.indicators.iter_mut() {
-            indicator.draw(ctx, &mut right_x, height);
-            right_x -= 8.;
-        }
-
+        self.extents = self.draw_indicators();
         self.inner.commit();
     }
 
@@ -0,0 +1,171 @@
+//! Switching between recent spaces by dragging the home bar sideways.
+
+use crate::server::SpaceID;
+use crate::space::SpaceTransform;
+use crate::spring::{RealTimeSpring, Spring};
+use cgmath::Vector2;
+use std::time::Instant;
+
+/// Gap between the two spaces while switching.
+const SPACE_GAP: f64 = 24.;
+
+/// How far ahead the offset is projected with its velocity when deciding whether to switch.
+const OFFSET_PROJECTION: f64 = 0.2;
+
+/// How much a drag moves the space if there is nothing to switch to.
+const RUBBER_BAND: f64 = 0.3;
+
+/// Which side of the frontmost space the neighbor is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    fn sign(self) -> f64 {
+        match self {
+            Side::Left => -1.,
+            Side::Right => 1.,
+        }
+    }
+
+    fn opposite(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+/// Slides the frontmost space to the side to reveal the previous or next one.
+///
+/// Dragging right reveals the previously used space on the left, and dragging left reveals the
+/// least recently used space on the right, so flipping back and forth between two spaces works
+/// in either direction.
+#[derive(Debug)]
+pub struct SpaceSwitcher {
+    offset: RealTimeSpring,
+    dragging: bool,
+    prev_drag_time: Instant,
+    neighbor: Option<(SpaceID, Side)>,
+}
+
+impl SpaceSwitcher {
+    pub fn new() -> SpaceSwitcher {
+        SpaceSwitcher {
+            offset: RealTimeSpring::new(Spring::new(1., 0.35)),
+            dragging: false,
+            prev_drag_time: Instant::now(),
+            neighbor: None,
+        }
+    }
+
+    /// Returns true while dragging or animating.
+    pub fn is_active(&self) -> bool {
+        self.dragging || self.offset.spring.needs_update(0.5)
+    }
+
+    /// Advances the animation; should be called once per frame.
+    pub fn update(&mut self) {
+        if self.dragging {
+            self.offset.update_time();
+        } else {
+            self.offset.update();
+            if !self.offset.spring.needs_update(0.5) {
+                self.offset.spring.finish();
+                self.neighbor = None;
+            }
+        }
+    }
+
+    /// Updates the drag offset.
+    ///
+    /// `order` lists spaces from front to back.
+    pub fn drag(&mut self, offset: f64, order: &[SpaceID]) {
+        if !self.dragging {
+            self.dragging = true;
+            self.prev_drag_time = Instant::now();
+        }
+
+        self.neighbor = if order.len() < 2 {
+            None
+        } else if offset > 0. {
+            Some((order[1], Side::Left))
+        } else {
+            Some((order[order.len() - 1], Side::Right))
+        };
+
+        let offset = if self.neighbor.is_some() {
+            offset
+        } else {
+            offset * RUBBER_BAND
+        };
+
+        self.offset.drag_to(offset, &mut self.prev_drag_time);
+    }
+
+    /// Ends the drag and returns the space that should be brought to the front, if any.
+    pub fn end(&mut self, width: f64) -> Option<SpaceID> {
+        self.dragging = false;
+        self.offset.spring.target = 0.;
+
+        let spring = self.offset.spring;
+        let projected = spring.value + spring.velocity * OFFSET_PROJECTION;
+
+        let (neighbor, side) = self.neighbor?;
+        // the neighbor only switches in if the drag is towards it
+        let towards_neighbor = projected * side.sign() < 0.;
+        if !towards_neighbor || projected.abs() < width / 2. {
+            return None;
+        }
+
+        // the neighbor becomes the frontmost space where it currently is, and the previously
+        // frontmost space becomes its neighbor on the other side
+        self.offset.spring.value += side.sign() * (width + SPACE_GAP);
+        Some(neighbor)
+    }
+
+    /// Must be called after the space returned by `end` was brought to the front.
+    pub fn switched(&mut self, previous_front: SpaceID) {
+        if let Some((_, side)) = self.neighbor {
+            self.neighbor = Some((previous_front, side.opposite()));
+        }
+    }
+
+    /// Slides the frontmost space in from the left, e.g. after the previous one was removed.
+    pub fn reveal(&mut self, width: f64) {
+        self.dragging = false;
+        self.neighbor = None;
+        self.offset.spring.value = -(width + SPACE_GAP);
+        self.offset.spring.velocity = 0.;
+        self.offset.spring.target = 0.;
+    }
+
+    /// Cancels the drag and slides back.
+    pub fn cancel(&mut self) {
+        self.dragging = false;
+        self.offset.spring.target = 0.;
+    }
+
+    /// Returns the transform for the frontmost space.
+    pub fn front_transform(&self) -> SpaceTransform {
+        SpaceTransform {
+            offset: Vector2::new(self.offset.spring.value, 0.),
+            scale: 1.,
+        }
+    }
+
+    /// Returns the neighboring space and its transform.
+    pub fn neighbor_transform(&self, width: f64) -> Option<(SpaceID, SpaceTransform)> {
+        let (neighbor, side) = self.neighbor?;
+        let x = self.offset.spring.value + side.sign() * (width + SPACE_GAP);
+        Some((
+            neighbor,
+            SpaceTransform {
+                offset: Vector2::new(x, 0.),
+                scale: 1.,
+            },
+        ))
+    }
+}
@@ -0,0 +1,304 @@
+//! Support for the tablet-unstable-v2 protocol.
+//!
+//! wlroots-rs doesn’t wrap the tablet-v2 types yet, so this uses wlroots-sys directly.
+
+use crate::event::Event;
+use crate::view::View;
+use cgmath::Vector2;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+use wlroots::wlroots_sys::wlr_tablet_tool_axes::*;
+use wlroots::wlroots_sys::*;
+
+/// How far the wheel of a tablet tool turns per click, in degrees.
+///
+/// wlroots only forwards the angle, so the number of clicks is derived from it.
+const WHEEL_CLICK_ANGLE: f64 = 15.;
+
+/// Identifies a tablet tool and the tablet it’s being used on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TabletToolId {
+    pub tablet: *mut wlr_input_device,
+    pub tool: *mut wlr_tablet_tool,
+}
+
+/// The axis values of a tablet tool event.
+///
+/// Only the axes in `updated` are set, the others are 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToolAxes {
+    /// The `wlr_tablet_tool_axes` bits of the axes that changed.
+    pub updated: u32,
+    pub pressure: f64,
+    pub distance: f64,
+    pub tilt: (f64, f64),
+    pub slider: f64,
+    pub wheel_delta: f64,
+}
+
+impl ToolAxes {
+    fn has(&self, axis: u32) -> bool {
+        (self.updated & axis) != 0
+    }
+}
+
+#[derive(Debug)]
+struct ToolState {
+    tool: *mut wlr_tablet_v2_tablet_tool,
+    focus: Option<Rc<View>>,
+    tip_down: bool,
+    /// The last tilt, since the axes can change separately.
+    tilt: (f64, f64),
+}
+
+/// Tablets, tools and pads advertised on the seat.
+#[derive(Debug)]
+pub struct TabletManager {
+    manager: *mut wlr_tablet_manager_v2,
+    seat: *mut wlr_seat,
+    tablets: HashMap<*mut wlr_input_device, *mut wlr_tablet_v2_tablet>,
+    pads: HashMap<*mut wlr_input_device, *mut wlr_tablet_v2_tablet_pad>,
+    tools: HashMap<TabletToolId, ToolState>,
+    /// The last location of each tool, in the device’s coordinates.
+    locations: HashMap<TabletToolId, Vector2<f64>>,
+}
+
+impl TabletManager {
+    /// Creates the tablet manager global for the given seat.
+    pub unsafe fn new(seat: *mut wlr_seat) -> TabletManager {
+        TabletManager {
+            manager: wlr_tablet_v2_create((*seat).display),
+            seat,
+            tablets: HashMap::new(),
+            pads: HashMap::new(),
+            tools: HashMap::new(),
+            locations: HashMap::new(),
+        }
+    }
+
+    /// Advertises a tablet.
+    pub unsafe fn add_tablet(&mut self, device: *mut wlr_input_device) {
+        let tablet = wlr_tablet_create(self.manager, self.seat, device);
+        self.tablets.insert(device, tablet);
+    }
+
+    /// Advertises a tablet pad.
+    pub unsafe fn add_pad(&mut self, device: *mut wlr_input_device) {
+        let pad = wlr_tablet_pad_create(self.manager, self.seat, device);
+        self.pads.insert(device, pad);
+    }
+
+    /// Forgets a tablet or pad and all tools used on it.
+    ///
+    /// The protocol objects are destroyed by wlroots along with the device.
+    pub fn remove_device(&mut self, device: *mut wlr_input_device) {
+        self.tablets.remove(&device);
+        self.pads.remove(&device);
+        self.tools.retain(|id, _| id.tablet != device);
+        self.locations.retain(|id, _| id.tablet != device);
+    }
+
+    /// Remembers where tools are, and fills in the coordinates that axis events didn’t update,
+    /// which wlroots leaves at 0.
+    ///
+    /// This must be called before the location is mapped to the output.
+    pub fn track_location(&mut self, event: &mut Event) {
+        match event {
+            Event::TabletAxis {
+                tool,
+                location,
+                axes,
+            } => {
+                let last = self.locations.entry(*tool).or_insert(*location);
+                if axes.has(WLR_TABLET_TOOL_AXIS_X) {
+                    last.x = location.x;
+                }
+                if axes.has(WLR_TABLET_TOOL_AXIS_Y) {
+                    last.y = location.y;
+                }
+                *location = *last;
+            }
+            Event::TabletProximityIn { tool, location }
+            | Event::TabletTipDown { tool, location }
+            | Event::TabletTipUp { tool, location } => {
+                self.locations.insert(*tool, *location);
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns the state for a tool, creating the tool object the first time it’s used.
+    fn tool(&mut self, id: TabletToolId) -> Option<&mut ToolState> {
+        if !self.tablets.contains_key(&id.tablet) {
+            return None;
+        }
+        let manager = self.manager;
+        let seat = self.seat;
+        Some(self.tools.entry(id).or_insert_with(|| ToolState {
+            tool: unsafe { wlr_tablet_tool_create(manager, seat, id.tool) },
+            focus: None,
+            tip_down: false,
+            tilt: (0., 0.),
+        }))
+    }
+
+    /// Returns true if the tool’s tip is touching the screen.
+    pub fn tip_down(&self, id: TabletToolId) -> bool {
+        self.tools.get(&id).map_or(false, |tool| tool.tip_down)
+    }
+
+    /// Returns the view the tool is in proximity of.
+    pub fn focus(&self, id: TabletToolId) -> Option<Rc<View>> {
+        self.tools.get(&id).and_then(|tool| tool.focus.clone())
+    }
+
+    /// Moves the tool into proximity of a view (or none) and sends the location.
+    pub fn set_focus(&mut self, id: TabletToolId, view: Option<Rc<View>>, location: Vector2<f64>) {
+        let tablet = match self.tablets.get(&id.tablet) {
+            Some(tablet) => *tablet,
+            None => return,
+        };
+        let pads: Vec<_> = self.pads.values().map(|pad| *pad).collect();
+        let tool = match self.tool(id) {
+            Some(tool) => tool,
+            None => return,
+        };
+
+        let changed = match (&tool.focus, &view) {
+            (Some(a), Some(b)) => !Rc::ptr_eq(a, b),
+            (None, None) => false,
+            _ => true,
+        };
+
+        if changed {
+            if tool.focus.is_some() {
+                unsafe { wlr_send_tablet_v2_tablet_tool_proximity_out(tool.tool) };
+            }
+            tool.focus = None;
+
+            if let Some(view) = view {
+                let accepted = view.with_surface(|surface| unsafe {
+                    let surface = surface.as_ptr();
+                    if !wlr_surface_accepts_tablet_v2(tablet, surface) {
+                        return false;
+                    }
+                    wlr_send_tablet_v2_tablet_tool_proximity_in(tool.tool, tablet, surface);
+                    for pad in &pads {
+                        wlr_send_tablet_v2_tablet_pad_enter(*pad, tablet, surface);
+                    }
+                    true
+                });
+                if accepted {
+                    tool.focus = Some(view);
+                }
+            }
+        }
+
+        if let Some(ref view) = tool.focus {
+            let (sx, sy) = view.map_location(location);
+            unsafe { wlr_send_tablet_v2_tablet_tool_motion(tool.tool, sx, sy) };
+        }
+    }
+
+    /// Sends the axis values that changed to the view the tool is in proximity of.
+    pub fn axis(&mut self, id: TabletToolId, axes: ToolAxes) {
+        let tool = match self.tool(id) {
+            Some(tool) => tool,
+            None => return,
+        };
+        if axes.has(WLR_TABLET_TOOL_AXIS_TILT_X) {
+            tool.tilt.0 = axes.tilt.0;
+        }
+        if axes.has(WLR_TABLET_TOOL_AXIS_TILT_Y) {
+            tool.tilt.1 = axes.tilt.1;
+        }
+        if tool.focus.is_none() {
+            return;
+        }
+
+        let tool_ptr = tool.tool;
+        unsafe {
+            if axes.has(WLR_TABLET_TOOL_AXIS_PRESSURE) {
+                wlr_send_tablet_v2_tablet_tool_pressure(tool_ptr, axes.pressure);
+            }
+            if axes.has(WLR_TABLET_TOOL_AXIS_DISTANCE) {
+                wlr_send_tablet_v2_tablet_tool_distance(tool_ptr, axes.distance);
+            }
+            if axes.has(WLR_TABLET_TOOL_AXIS_TILT_X | WLR_TABLET_TOOL_AXIS_TILT_Y) {
+                wlr_send_tablet_v2_tablet_tool_tilt(tool_ptr, tool.tilt.0, tool.tilt.1);
+            }
+            if axes.has(WLR_TABLET_TOOL_AXIS_SLIDER) {
+                wlr_send_tablet_v2_tablet_tool_slider(tool_ptr, axes.slider);
+            }
+            if axes.has(WLR_TABLET_TOOL_AXIS_WHEEL) {
+                let clicks = (axes.wheel_delta / WHEEL_CLICK_ANGLE).round() as i32;
+                wlr_send_tablet_v2_tablet_tool_wheel(tool_ptr, axes.wheel_delta, clicks);
+            }
+        }
+    }
+
+    /// Sends a tip down or up event.
+    pub fn tip(&mut self, id: TabletToolId, down: bool) {
+        let tool = match self.tool(id) {
+            Some(tool) => tool,
+            None => return,
+        };
+        tool.tip_down = down;
+        if tool.focus.is_none() {
+            return;
+        }
+        unsafe {
+            if down {
+                wlr_send_tablet_v2_tablet_tool_down(tool.tool);
+            } else {
+                wlr_send_tablet_v2_tablet_tool_up(tool.tool);
+            }
+        }
+    }
+
+    /// Sends a tool button event.
+    pub fn button(&mut self, id: TabletToolId, button: u32, pressed: bool) {
+        let tool = match self.tool(id) {
+            Some(ref tool) if tool.focus.is_some() => tool.tool,
+            _ => return,
+        };
+        let state = if pressed {
+            zwp_tablet_pad_v2_button_state::ZWP_TABLET_PAD_V2_BUTTON_STATE_PRESSED
+        } else {
+            zwp_tablet_pad_v2_button_state::ZWP_TABLET_PAD_V2_BUTTON_STATE_RELEASED
+        };
+        unsafe { wlr_send_tablet_v2_tablet_tool_button(tool, button, state) };
+    }
+
+    /// Moves the tool out of proximity.
+    pub fn proximity_out(&mut self, id: TabletToolId) {
+        if let Some(tool) = self.tools.get_mut(&id) {
+            if tool.focus.take().is_some() {
+                unsafe { wlr_send_tablet_v2_tablet_tool_proximity_out(tool.tool) };
+            }
+            tool.tip_down = false;
+        }
+    }
+
+    /// Sends a pad button event to the surface the pad entered.
+    pub fn pad_button(
+        &mut self,
+        device: *mut wlr_input_device,
+        button: u32,
+        time: Duration,
+        pressed: bool,
+    ) {
+        let pad = match self.pads.get(&device) {
+            Some(pad) => *pad,
+            None => return,
+        };
+        let state = if pressed {
+            zwp_tablet_pad_v2_button_state::ZWP_TABLET_PAD_V2_BUTTON_STATE_PRESSED
+        } else {
+            zwp_tablet_pad_v2_button_state::ZWP_TABLET_PAD_V2_BUTTON_STATE_RELEASED
+        };
+        let time_msec = time.as_secs() as u32 * 1000 + time.subsec_millis();
+        unsafe { wlr_send_tablet_v2_tablet_pad_button(pad, button as usize, time_msec, state) };
+    }
+}
@@ -0,0 +1,139 @@
+//! X11 menus and tooltips, which place themselves and float above the front space, the Slide
+//! Over panel and the on-screen keyboard.
+//!
+//! They aren’t part of any space, so they don’t take part in the overview or the space switcher
+//! and never get keyboard focus.
+
+use crate::event::Event;
+use crate::renderer::Renderer;
+use crate::server::Server;
+use crate::space::{Space, SpaceTransform};
+use crate::view::{View, ViewShell};
+use cairo::Rectangle;
+use cgmath::Matrix4;
+use std::rc::Rc;
+use wlroots::wlroots_sys::wlr_surface;
+use wlroots::TouchId;
+
+#[derive(Debug)]
+pub struct Unmanaged {
+    /// The menus and tooltips, which are only ever shown at the place they asked for.
+    space: Space,
+    /// Touch points that went down on a menu.
+    touch_ids: Vec<TouchId>,
+    /// Pointer buttons that were pressed on a menu.
+    pointer_buttons: usize,
+    pointer_inside: bool,
+}
+
+impl Unmanaged {
+    pub fn new() -> Unmanaged {
+        Unmanaged {
+            space: Space::new_floating(),
+            touch_ids: Vec::new(),
+            pointer_buttons: 0,
+            pointer_inside: false,
+        }
+    }
+
+    pub fn views(&self) -> &[Rc<View>] {
+        self.space.views()
+    }
+
+    pub fn add_view(&mut self, view: Rc<View>) {
+        self.space.add_view(view);
+    }
+
+    pub fn remove_view_for_surface(&mut self, surface: &ViewShell) -> bool {
+        self.space.remove_view_for_surface(surface)
+    }
+
+    /// Returns where a surface of a menu or tooltip is shown, in screen coordinates.
+    pub fn surface_bounds(&self, surface: *mut wlr_surface) -> Option<Rectangle> {
+        self.space
+            .views()
+            .iter()
+            .find_map(|view| view.surface_bounds(surface))
+    }
+
+    /// Returns true if the pointer is over a menu, or was pressed on one.
+    pub fn pointer_inside(&self) -> bool {
+        self.pointer_inside
+    }
+
+    /// Forgets which surface has pointer focus, so that it’s entered again on the next event.
+    pub fn reset_pointer_focus(&mut self) {
+        self.space.reset_pointer_focus();
+        self.pointer_inside = false;
+    }
+
+    pub fn render(
+        &mut self,
+        matrix: Matrix4<f32>,
+        wlr_renderer: &mut wlroots::Renderer,
+        renderer: &Renderer,
+    ) {
+        let transform = SpaceTransform::identity();
+        self.space.render(matrix, transform, wlr_renderer, renderer);
+    }
+
+    /// Handles an event that’s on a menu or tooltip, or belongs to a touch or click that
+    /// started on one, and returns true if it did.
+    pub fn handle_event(&mut self, event: Event, server: &mut Server) -> bool {
+        match event {
+            Event::TouchDown { id, location } => {
+                if !self.space.contains_point(location) {
+                    return false;
+                }
+                self.touch_ids.push(id);
+                self.space.handle_event(event, server);
+                true
+            }
+            Event::TouchMotion { id, .. } => {
+                if !self.touch_ids.contains(&id) {
+                    return false;
+                }
+                self.space.handle_event(event, server);
+                true
+            }
+            Event::TouchUp { id } | Event::TouchCancel { id } => {
+                let pos = match self.touch_ids.iter().position(|x| *x == id) {
+                    Some(pos) => pos,
+                    None => return false,
+                };
+                self.touch_ids.remove(pos);
+                self.space.handle_event(event, server);
+                true
+            }
+            Event::PointerMotion { .. }
+            | Event::PointerDown { .. }
+            | Event::PointerUp { .. }
+            | Event::PointerAxis { .. } => self.handle_pointer_event(event, server),
+            _ => false,
+        }
+    }
+
+    fn handle_pointer_event(&mut self, event: Event, server: &mut Server) -> bool {
+        let location = server.cursor_location();
+        let inside = self.pointer_buttons > 0 || self.space.contains_point(location);
+        if !inside {
+            if self.pointer_inside {
+                // the views below will send enter events once the pointer moves over them
+                self.space.reset_pointer_focus();
+                self.pointer_inside = false;
+            }
+            return false;
+        }
+        self.pointer_inside = true;
+
+        match event {
+            Event::PointerDown { .. } => self.pointer_buttons += 1,
+            Event::PointerUp { .. } => {
+                self.pointer_buttons = self.pointer_buttons.saturating_sub(1);
+            }
+            _ => (),
+        }
+        self.space.handle_event(event, server);
+        true
+    }
+}
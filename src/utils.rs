@@ -1,10 +1,18 @@
-use cairo::Context;
+use crate::server::Server;
+use cairo::{Context, Rectangle};
+use cgmath::Vector2;
 use std::f64::consts::PI;
+use std::os::raw::c_void;
+use std::ptr;
+use wlroots::wlroots_sys::{
+    wl_list, wl_listener, wl_signal, wlr_layer_surface_v1, wlr_layer_surface_v1_for_each_surface,
+    wlr_surface, wlr_surface_for_each_surface,
+};
 
 pub fn rounded_rect(ctx: &Context, x: f64, y: f64, width: f64, height: f64, radius: f64) {
     let right = x + width;
     let bottom = y + height;
-    ctx.move_to(radius, y);
+    ctx.move_to(x + radius, y);
     ctx.line_to(right - radius, y);
     ctx.arc(right - radius, y + radius, radius, -PI / 2., 0.);
     ctx.line_to(right, bottom - radius);
@@ -14,3 +22,134 @@ pub fn rounded_rect(ctx: &Context, x: f64, y: f64, width: f64, height: f64, radi
     ctx.line_to(x, y + radius);
     ctx.arc(x + radius, y + radius, radius, PI, 3. * PI / 2.);
 }
+
+/// Returns true if the point is inside the rectangle.
+pub fn contains(area: Rectangle, point: Vector2<f64>) -> bool {
+    point.x >= area.x
+        && point.y >= area.y
+        && point.x < area.x + area.width
+        && point.y < area.y + area.height
+}
+
+/// Returns true if the inner rectangle is entirely inside the outer one.
+pub fn contains_rect(outer: Rectangle, inner: Rectangle) -> bool {
+    inner.x >= outer.x
+        && inner.y >= outer.y
+        && inner.x + inner.width <= outer.x + outer.width
+        && inner.y + inner.height <= outer.y + outer.height
+}
+
+/// Returns the smallest rectangle that contains both rectangles.
+pub fn union(a: Rectangle, b: Rectangle) -> Rectangle {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    Rectangle {
+        x,
+        y,
+        width: (a.x + a.width).max(b.x + b.width) - x,
+        height: (a.y + a.height).max(b.y + b.height) - y,
+    }
+}
+
+/// Returns the part that both rectangles cover, if they overlap.
+pub fn intersection(a: Rectangle, b: Rectangle) -> Option<Rectangle> {
+    let x = a.x.max(b.x);
+    let y = a.y.max(b.y);
+    let right = (a.x + a.width).min(b.x + b.width);
+    let bottom = (a.y + a.height).min(b.y + b.height);
+    if right <= x || bottom <= y {
+        return None;
+    }
+    Some(Rectangle {
+        x,
+        y,
+        width: right - x,
+        height: bottom - y,
+    })
+}
+
+/// Runs the closure with the server, which raw signal handlers can’t get at otherwise.
+pub fn with_server<F: FnOnce(&mut Server)>(context: &str, f: F) {
+    let compositor_handle = match wlroots::compositor::handle() {
+        Some(handle) => handle,
+        None => return,
+    };
+    let res = compositor_handle.run(|compositor| {
+        let server: &mut Server = compositor.data.downcast_mut().unwrap();
+        f(server);
+    });
+    if let Err(err) = res {
+        warn!("Error in {}: {}", context, err);
+    }
+}
+
+/// Does what `wl_signal_add` does, which is an inline function and thus not in wlroots-sys.
+pub unsafe fn signal_add(signal: *mut wl_signal, listener: *mut wl_listener) {
+    let list = (*signal).listener_list.prev;
+    let link = &mut (*listener).link as *mut wl_list;
+    (*link).prev = list;
+    (*link).next = (*list).next;
+    (*(*list).next).prev = link;
+    (*list).next = link;
+}
+
+/// Does what `wl_list_remove` does.
+pub unsafe fn list_remove(link: *mut wl_list) {
+    (*(*link).prev).next = (*link).next;
+    (*(*link).next).prev = (*link).prev;
+    (*link).prev = ptr::null_mut();
+    (*link).next = ptr::null_mut();
+}
+
+/// Returns the struct that a listener is the nth field of, which is what `wl_container_of` does
+/// for the listeners at the start of a `#[repr(C)]` struct.
+pub unsafe fn container_of<T>(listener: *mut wl_listener, field: isize) -> *mut T {
+    listener.offset(-field) as *mut T
+}
+
+/// Creates a listener that isn’t added to any signal yet.
+pub fn listener(notify: unsafe extern "C" fn(*mut wl_listener, *mut c_void)) -> wl_listener {
+    wl_listener {
+        link: wl_list {
+            prev: ptr::null_mut(),
+            next: ptr::null_mut(),
+        },
+        notify,
+    }
+}
+
+/// Calls the closure for a surface and its subsurfaces from bottom to top, with their location
+/// relative to the surface.
+///
+/// This is for surfaces that wlroots-rs doesn’t wrap.
+pub unsafe fn for_each_surface(
+    surface: *mut wlr_surface,
+    f: &mut dyn FnMut(*mut wlr_surface, i32, i32),
+) {
+    if surface.is_null() {
+        return;
+    }
+    let mut f = f;
+    let data = &mut f as *mut &mut dyn FnMut(*mut wlr_surface, i32, i32) as *mut c_void;
+    wlr_surface_for_each_surface(surface, Some(surface_iterator), data);
+}
+
+/// Same as `for_each_surface`, but for a layer surface, which also includes its popups.
+pub unsafe fn for_each_layer_surface(
+    layer_surface: *mut wlr_layer_surface_v1,
+    f: &mut dyn FnMut(*mut wlr_surface, i32, i32),
+) {
+    let mut f = f;
+    let data = &mut f as *mut &mut dyn FnMut(*mut wlr_surface, i32, i32) as *mut c_void;
+    wlr_layer_surface_v1_for_each_surface(layer_surface, Some(surface_iterator), data);
+}
+
+unsafe extern "C" fn surface_iterator(
+    surface: *mut wlr_surface,
+    sx: i32,
+    sy: i32,
+    data: *mut c_void,
+) {
+    let f = &mut *(data as *mut &mut dyn FnMut(*mut wlr_surface, i32, i32));
+    f(surface, sx, sy);
+}
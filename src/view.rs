@@ -1,44 +1,396 @@
+use cairo::Rectangle;
+use crate::renderer::{Decoration, DecorationPart, TITLE_HEIGHT};
+use crate::shell::{obtain_app_id_safely_unsafely, obtain_stable_app_id_safely_unsafely};
+use crate::space::SpaceTransform;
+use crate::utils;
+use crate::xwayland;
 use cgmath::{Matrix4, Vector2};
 use gl::GLVertexArray;
+use std::cell::{Cell, RefCell};
 use wlroots::utils::current_time;
+use wlroots::wlroots_sys::wlr_edges::*;
+use wlroots::wlroots_sys::{
+    wlr_box, wlr_layer_surface_v1, wlr_layer_surface_v1_close, wlr_layer_surface_v1_configure,
+    wlr_surface, wlr_xdg_popup_unconstrain_from_box, wlr_xdg_popup_v6_unconstrain_from_box,
+    wlr_xdg_surface_send_close, wlr_xdg_surface_v6_send_close, wlr_xdg_toplevel_set_tiled,
+    wlr_xwayland_surface, wlr_xwayland_surface_activate, wlr_xwayland_surface_close,
+    wlr_xwayland_surface_configure, wlr_xwayland_surface_set_fullscreen,
+};
 use wlroots::*;
 
+/// The shell surface of a view.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViewShell {
+    XdgV6(XdgV6ShellSurfaceHandle),
+    Xdg(XdgShellSurfaceHandle),
+    /// An X11 window, which is only valid while it’s mapped.
+    XWayland(*mut wlr_xwayland_surface),
+    /// A layer-shell surface, which is only valid while it’s mapped.
+    Layer(*mut wlr_layer_surface_v1),
+}
+
+impl ViewShell {
+    /// Asks the client to close the surface.
+    ///
+    /// For popups, this dismisses the popup.
+    pub fn close(&self) {
+        let res = match self {
+            ViewShell::XdgV6(handle) => {
+                handle.run(|shell| unsafe { wlr_xdg_surface_v6_send_close(shell.as_ptr()) })
+            }
+            ViewShell::Xdg(handle) => {
+                handle.run(|shell| unsafe { wlr_xdg_surface_send_close(shell.as_ptr()) })
+            }
+            ViewShell::XWayland(surface) => {
+                unsafe { wlr_xwayland_surface_close(*surface) };
+                Ok(())
+            }
+            ViewShell::Layer(layer_surface) => {
+                unsafe { wlr_layer_surface_v1_close(*layer_surface) };
+                Ok(())
+            }
+        };
+
+        if let Err(err) = res {
+            warn!("Error in close: {}", err);
+        }
+    }
+}
+
+/// Runs the body with `$shell` bound to the shell surface, whatever its type.
+///
+/// Both shell surface types have mostly the same API, so this avoids writing everything twice.
+/// X11 windows and layer surfaces have no handle and need to be dealt with before.
+macro_rules! with_shell {
+    ($view_shell:expr, |$shell:ident| $body:expr) => {
+        match $view_shell {
+            ViewShell::XdgV6(handle) => handle.run(|$shell| $body),
+            ViewShell::Xdg(handle) => handle.run(|$shell| $body),
+            ViewShell::XWayland(_) | ViewShell::Layer(_) => Err(HandleErr::AlreadyDropped),
+        }
+    };
+}
+
 #[derive(Debug)]
 pub struct View {
-    pub shell: XdgV6ShellSurfaceHandle,
+    pub shell: ViewShell,
+    /// Top left corner of the window geometry in screen coordinates.
+    origin: Cell<Vector2<f64>>,
+    /// The area the view was configured to fill, including the title strip.
+    area: Cell<Rectangle>,
+    fullscreen: Cell<bool>,
+    /// True if sfc draws a title strip above the view.
+    decorated: Cell<bool>,
+    decoration: RefCell<Option<Decoration>>,
 }
 
 impl View {
-    pub fn new(shell: XdgV6ShellSurfaceHandle) -> View {
-        View { shell }
-    }
-
-    pub fn render(&self, _matrix: Matrix4<f32>, renderer: &mut Renderer) {
-        let res = self.shell.run(|shell| {
-            shell.for_each_surface(&mut |surface_h: SurfaceHandle, sx, sy| {
-                let _ = surface_h.run(|surface| {
-                    let (width, height) = surface.current_state().size();
-                    let render_width = width * renderer.output.scale() as i32;
-                    let render_height = height * renderer.output.scale() as i32;
-
-                    let render_box =
-                        Area::new(Origin::new(sx, sy), Size::new(render_width, render_height));
-
-                    let transform = renderer.output.get_transform().invert();
-                    let matrix = project_box(
-                        render_box,
-                        transform,
-                        0.0,
-                        renderer.output.transform_matrix(),
-                    );
-                    unsafe { GLVertexArray::unbind() };
-                    if let Some(tex) = surface.texture() {
-                        renderer.render_texture_with_matrix(&tex, matrix);
-                        surface.send_frame_done(current_time());
-                    } else {
-                        warn!("Surface has no texture");
+    pub fn new(shell: ViewShell) -> View {
+        View {
+            shell,
+            origin: Cell::new(Vector2::new(0., 0.)),
+            area: Cell::new(Rectangle {
+                x: 0.,
+                y: 0.,
+                width: 0.,
+                height: 0.,
+            }),
+            fullscreen: Cell::new(false),
+            decorated: Cell::new(false),
+            decoration: RefCell::new(None),
+        }
+    }
+
+    /// Sets whether sfc draws a title strip above the view.
+    ///
+    /// This doesn’t change the size; the view should be configured afterwards.
+    pub fn set_decorated(&self, decorated: bool) {
+        self.decorated.set(decorated);
+    }
+
+    /// Returns true if sfc draws a title strip above the view, unless it’s fullscreen.
+    pub fn is_decorated(&self) -> bool {
+        self.decorated.get()
+    }
+
+    /// Returns true if the title strip is shown, which it isn’t while the view is fullscreen.
+    fn has_decoration(&self) -> bool {
+        self.decorated.get() && !self.is_fullscreen()
+    }
+
+    /// Returns the toplevel title.
+    pub fn title(&self) -> String {
+        let res = match &self.shell {
+            ViewShell::XdgV6(handle) => handle.run(|shell| match shell.state() {
+                Some(XdgV6ShellState::TopLevel(top_level)) => {
+                    obtain_app_id_safely_unsafely(top_level).1
+                }
+                _ => String::new(),
+            }),
+            ViewShell::Xdg(handle) => handle.run(|shell| match shell.state() {
+                Some(XdgShellState::TopLevel(top_level)) => {
+                    obtain_stable_app_id_safely_unsafely(top_level).1
+                }
+                _ => String::new(),
+            }),
+            ViewShell::XWayland(surface) => Ok(unsafe { xwayland::surface_title(*surface) }),
+            ViewShell::Layer(_) => Ok(String::new()),
+        };
+        res.unwrap_or_default()
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen.get()
+    }
+
+    /// Tells the client whether it’s fullscreen.
+    ///
+    /// This doesn’t change the size; the view should be configured afterwards.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        self.fullscreen.set(fullscreen);
+
+        let res = match &self.shell {
+            ViewShell::XdgV6(handle) => handle.run(|shell| {
+                if let Some(XdgV6ShellState::TopLevel(top_level)) = shell.state() {
+                    top_level.set_fullscreen(fullscreen);
+                }
+            }),
+            ViewShell::Xdg(handle) => handle.run(|shell| {
+                if let Some(XdgShellState::TopLevel(top_level)) = shell.state() {
+                    top_level.set_fullscreen(fullscreen);
+                }
+            }),
+            ViewShell::XWayland(surface) => {
+                unsafe { wlr_xwayland_surface_set_fullscreen(*surface, fullscreen) };
+                Ok(())
+            }
+            ViewShell::Layer(_) => Ok(()),
+        };
+
+        if let Err(err) = res {
+            warn!("Error in set_fullscreen: {}", err);
+        }
+    }
+
+    /// Tells the client whether it has keyboard focus, so that it can draw itself accordingly.
+    pub fn set_activated(&self, activated: bool) {
+        let res = match &self.shell {
+            ViewShell::XdgV6(handle) => handle.run(|shell| {
+                if let Some(XdgV6ShellState::TopLevel(top_level)) = shell.state() {
+                    top_level.set_activated(activated);
+                }
+            }),
+            ViewShell::Xdg(handle) => handle.run(|shell| {
+                if let Some(XdgShellState::TopLevel(top_level)) = shell.state() {
+                    top_level.set_activated(activated);
+                }
+            }),
+            ViewShell::XWayland(surface) => {
+                unsafe { wlr_xwayland_surface_activate(*surface, activated) };
+                Ok(())
+            }
+            ViewShell::Layer(_) => Ok(()),
+        };
+
+        if let Err(err) = res {
+            warn!("Error in set_activated: {}", err);
+        }
+    }
+
+    /// Returns false for X11 menus and tooltips, which never get keyboard focus, and for layer
+    /// surfaces that don’t ask for it.
+    pub fn wants_focus(&self) -> bool {
+        match self.shell {
+            ViewShell::XWayland(surface) => unsafe { !(*surface).override_redirect },
+            ViewShell::Layer(layer_surface) => unsafe {
+                (*layer_surface).current.keyboard_interactive
+            },
+            _ => true,
+        }
+    }
+
+    /// Places the view in the given area and asks the client to fill it.
+    ///
+    /// Views are told they’re maximized and tiled so that they don’t draw shadows or resize
+    /// handles. Decorated views leave room for the title strip at the top.
+    pub fn configure(&self, area: Rectangle) {
+        self.area.set(area);
+        let area = if self.has_decoration() {
+            Rectangle {
+                y: area.y + TITLE_HEIGHT,
+                height: (area.height - TITLE_HEIGHT).max(0.),
+                ..area
+            }
+        } else {
+            area
+        };
+
+        self.origin.set(Vector2::new(area.x, area.y));
+        let width = area.width as u32;
+        let height = area.height as u32;
+
+        let res = match &self.shell {
+            ViewShell::XdgV6(handle) => handle.run(|shell| {
+                if let Some(XdgV6ShellState::TopLevel(top_level)) = shell.state() {
+                    top_level.set_maximized(true);
+                    top_level.set_size(width, height);
+                }
+            }),
+            ViewShell::Xdg(handle) => handle.run(|shell| {
+                let shell_ptr = shell.as_ptr();
+                if let Some(XdgShellState::TopLevel(top_level)) = shell.state() {
+                    top_level.set_maximized(true);
+                    top_level.set_size(width, height);
+                    unsafe {
+                        let edges = WLR_EDGE_TOP | WLR_EDGE_BOTTOM | WLR_EDGE_LEFT | WLR_EDGE_RIGHT;
+                        wlr_xdg_toplevel_set_tiled(shell_ptr, edges as u32);
                     }
-                });
+                }
+            }),
+            ViewShell::XWayland(surface) => {
+                // X11 menus and tooltips place themselves
+                unsafe {
+                    if !(**surface).override_redirect {
+                        let (x, y) = (area.x as i16, area.y as i16);
+                        wlr_xwayland_surface_configure(*surface, x, y, width as u16, height as u16);
+                    }
+                }
+                Ok(())
+            }
+            ViewShell::Layer(layer_surface) => {
+                unsafe { wlr_layer_surface_v1_configure(*layer_surface, width, height) };
+                Ok(())
+            }
+        };
+
+        if let Err(err) = res {
+            warn!("Error in configure: {}", err);
+        }
+    }
+
+    /// Configures the view again with the area it was last given.
+    pub fn reconfigure(&self) {
+        self.configure(self.area.get());
+    }
+
+    /// Returns the window geometry in screen coordinates.
+    fn geometry(&self) -> Option<Area> {
+        if let ViewShell::XWayland(surface) = self.shell {
+            let origin = self.surface_origin();
+            let size = unsafe { Size::new((*surface).width as i32, (*surface).height as i32) };
+            return Some(Area::new(
+                Origin::new(origin.x as i32, origin.y as i32),
+                size,
+            ));
+        }
+        if let ViewShell::Layer(_) = self.shell {
+            let area = self.area.get();
+            return Some(Area::new(
+                Origin::new(area.x as i32, area.y as i32),
+                Size::new(area.width as i32, area.height as i32),
+            ));
+        }
+
+        let origin = self.origin.get();
+        with_shell!(&self.shell, |shell| shell.geometry())
+            .ok()
+            .map(|geometry| {
+                Area::new(
+                    Origin::new(origin.x as i32, origin.y as i32),
+                    geometry.size,
+                )
+            })
+    }
+
+    /// Returns the location of the main surface in screen coordinates.
+    fn surface_origin(&self) -> Vector2<f64> {
+        if let ViewShell::XWayland(surface) = self.shell {
+            return unsafe {
+                if (*surface).override_redirect {
+                    Vector2::new((*surface).x as f64, (*surface).y as f64)
+                } else {
+                    self.origin.get()
+                }
+            };
+        }
+        if let ViewShell::Layer(_) = self.shell {
+            return self.origin.get();
+        }
+
+        let origin = self.origin.get();
+        match with_shell!(&self.shell, |shell| shell.geometry().origin) {
+            Ok(geometry_origin) => Vector2::new(
+                origin.x - geometry_origin.x as f64,
+                origin.y - geometry_origin.y as f64,
+            ),
+            Err(_) => origin,
+        }
+    }
+
+    /// Calls the closure for every surface of the view from bottom to top, with their location
+    /// relative to the main surface.
+    fn for_each_surface(&self, f: &mut dyn FnMut(SurfaceHandle, i32, i32)) -> HandleResult<()> {
+        let mut raw_f = |surface: *mut wlr_surface, sx: i32, sy: i32| {
+            f(unsafe { SurfaceHandle::from_ptr(surface) }, sx, sy)
+        };
+        match self.shell {
+            ViewShell::Layer(layer_surface) => {
+                unsafe { utils::for_each_layer_surface(layer_surface, &mut raw_f) };
+                Ok(())
+            }
+            ViewShell::XWayland(surface) => {
+                unsafe { utils::for_each_surface((*surface).surface, &mut raw_f) };
+                Ok(())
+            }
+            _ => with_shell!(&self.shell, |shell| shell.for_each_surface(f)),
+        }
+    }
+
+    /// Returns the main surface of X11 windows and layer surfaces, which wlroots-rs doesn’t
+    /// wrap.
+    fn raw_surface(&self) -> Option<*mut wlr_surface> {
+        match self.shell {
+            ViewShell::XWayland(surface) => Some(unsafe { (*surface).surface }),
+            ViewShell::Layer(layer_surface) => Some(unsafe { (*layer_surface).surface }),
+            _ => None,
+        }
+    }
+
+    /// Renders the view with the given space transform, and the projection matrix and resolution
+    /// of the output, which mirrors take from the output they mirror.
+    pub fn render(
+        &self,
+        space_transform: SpaceTransform,
+        projection: [f32; 9],
+        resolution: f64,
+        renderer: &mut Renderer,
+    ) {
+        let origin = self.surface_origin();
+        let res = self.for_each_surface(&mut |surface_h: SurfaceHandle, sx, sy| {
+            let _ = surface_h.run(|surface| {
+                let output_scale = resolution;
+                let scale = output_scale * space_transform.scale;
+                let (width, height) = surface.current_state().size();
+                let position = space_transform.apply(origin + Vector2::new(sx as f64, sy as f64));
+                let render_width = (width as f64 * scale) as i32;
+                let render_height = (height as f64 * scale) as i32;
+                let render_x = (position.x * output_scale) as i32;
+                let render_y = (position.y * output_scale) as i32;
+
+                let render_box = Area::new(
+                    Origin::new(render_x, render_y),
+                    Size::new(render_width, render_height),
+                );
+
+                let transform = renderer.output.get_transform().invert();
+                let matrix = project_box(render_box, transform, 0.0, projection);
+                unsafe { GLVertexArray::unbind() };
+                if let Some(tex) = surface.texture() {
+                    renderer.render_texture_with_matrix(&tex, matrix);
+                    surface.send_frame_done(current_time());
+                } else {
+                    warn!("Surface has no texture");
+                }
             });
         });
 
@@ -47,27 +399,200 @@ impl View {
         }
     }
 
-    #[wlroots_dehandle(shell_surface, surface)]
+    /// Renders the title strip with the given space transform, if the view has one.
+    pub fn render_decoration(
+        &self,
+        matrix: Matrix4<f32>,
+        space_transform: SpaceTransform,
+        renderer: &crate::renderer::Renderer,
+        fullscreen_button: bool,
+    ) {
+        if !self.has_decoration() {
+            return;
+        }
+
+        let area = self.area.get();
+        let title = self.title();
+        let mut decoration = self.decoration.borrow_mut();
+        let resolution = renderer.resolution();
+        let current = decoration.as_ref().map_or(false, |d| {
+            d.is_current(area.width, resolution, &title, fullscreen_button)
+        });
+        if !current {
+            *decoration = Some(Decoration::new(
+                area.width,
+                resolution,
+                &title,
+                fullscreen_button,
+            ));
+        }
+
+        let origin = space_transform.apply(Vector2::new(area.x, area.y));
+        decoration
+            .as_ref()
+            .unwrap()
+            .render(matrix, origin.x, origin.y, space_transform.scale);
+    }
+
+    /// Returns the part of the title strip at the given point.
+    pub fn decoration_part_at(
+        &self,
+        point: Vector2<f64>,
+        fullscreen_button: bool,
+    ) -> Option<DecorationPart> {
+        if !self.has_decoration() {
+            return None;
+        }
+        let area = self.area.get();
+        let local = point - Vector2::new(area.x, area.y);
+        if local.x >= 0. && local.y >= 0. && local.x < area.width && local.y < TITLE_HEIGHT {
+            Some(Decoration::part_at(area.width, local, fullscreen_button))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the main surface, or `None` if the shell surface is already gone.
+    pub fn surface_ptr(&self) -> Option<*mut wlr_surface> {
+        if let Some(surface) = self.raw_surface() {
+            return Some(surface).filter(|surface| !surface.is_null());
+        }
+        with_shell!(&self.shell, |shell| shell.surface().run(|surface| surface.as_ptr()))
+            .and_then(|res| res)
+            .ok()
+    }
+
     pub fn with_surface<T, F: FnOnce(&mut Surface) -> T>(&self, f: F) -> T {
-        let shell = &self.shell;
-        use shell as shell_surface;
-        let shell_surface = shell_surface.surface();
-        use shell_surface as surface;
-        f(surface)
+        if let Some(surface) = self.raw_surface() {
+            return unsafe { SurfaceHandle::from_ptr(surface) }.run(f).unwrap();
+        }
+        with_shell!(&self.shell, |shell| shell.surface().run(f).unwrap()).unwrap()
+    }
+
+    /// Returns the topmost surface at the given point along with its origin in screen
+    /// coordinates.
+    ///
+    /// This includes popups and subsurfaces.
+    pub fn surface_at(&self, point: Vector2<f64>) -> Option<(SurfaceHandle, Vector2<f64>)> {
+        let origin = self.surface_origin();
+        let mut found = None;
+        // surfaces are iterated from bottom to top, so the last match is the topmost one
+        let _ = self.for_each_surface(&mut |surface_h: SurfaceHandle, sx, sy| {
+            let surface_origin = origin + Vector2::new(sx as f64, sy as f64);
+            let local = point - surface_origin;
+            let contains = surface_h.run(|surface| {
+                let (width, height) = surface.current_state().size();
+                local.x >= 0. && local.y >= 0. && local.x < width as f64 && local.y < height as f64
+            });
+            if let Ok(true) = contains {
+                found = Some((surface_h, surface_origin));
+            }
+        });
+        found
+    }
+
+    /// Returns true if the window and its title strip cover the whole area, which hides
+    /// everything below them if they’re opaque.
+    pub fn covers(&self, area: Rectangle) -> bool {
+        let geometry = match self.geometry() {
+            Some(geometry) => geometry,
+            None => return false,
+        };
+        let (x, y) = (geometry.origin.x as f64, geometry.origin.y as f64);
+        let right = x + geometry.size.width as f64;
+        let bottom = y + geometry.size.height as f64;
+
+        // the title strip fills the top of the configured area, right above the window
+        let strip = self.area.get();
+        let top = if self.has_decoration()
+            && strip.x <= area.x
+            && strip.x + strip.width >= area.x + area.width
+            && y <= strip.y + TITLE_HEIGHT
+        {
+            strip.y.min(y)
+        } else {
+            y
+        };
+        x <= area.x
+            && top <= area.y
+            && right >= area.x + area.width
+            && bottom >= area.y + area.height
+    }
+
+    /// Returns the area that one of the view’s surfaces covers in screen coordinates, which for
+    /// the main surface includes the area the view was configured to fill, or `None` if the
+    /// surface isn’t the view’s.
+    ///
+    /// This includes popups and subsurfaces.
+    pub fn surface_bounds(&self, surface: *mut wlr_surface) -> Option<Rectangle> {
+        let origin = self.surface_origin();
+        let mut found = None;
+        let _ = self.for_each_surface(&mut |surface_h: SurfaceHandle, sx, sy| {
+            let size = surface_h.run(|candidate| {
+                if candidate.as_ptr() == surface {
+                    Some(candidate.current_state().size())
+                } else {
+                    None
+                }
+            });
+            if let Ok(Some((width, height))) = size {
+                found = Some(Rectangle {
+                    x: origin.x + sx as f64,
+                    y: origin.y + sy as f64,
+                    width: width as f64,
+                    height: height as f64,
+                });
+            }
+        });
+        let bounds = found?;
+        if self.with_surface(|main| main.as_ptr()) == surface {
+            Some(utils::union(bounds, self.area.get()))
+        } else {
+            Some(bounds)
+        }
     }
 
-    #[wlroots_dehandle(shell)]
     pub fn contains_point(&self, point: Vector2<f64>) -> bool {
-        let shell_h = &self.shell;
-        use shell_h as shell;
-        shell.geometry().contains_point(point.x, point.y)
+        let in_geometry = self
+            .geometry()
+            .map_or(false, |geometry| geometry.contains_point(point.x, point.y));
+        in_geometry || self.surface_at(point).is_some()
+    }
+
+    /// Moves a popup of this view so that it stays inside the given area.
+    pub fn unconstrain_popup(&self, popup: &ViewShell, area: Rectangle) {
+        // the box is relative to the toplevel surface
+        let origin = self.surface_origin();
+        let mut bounds = wlr_box {
+            x: (area.x - origin.x) as i32,
+            y: (area.y - origin.y) as i32,
+            width: area.width as i32,
+            height: area.height as i32,
+        };
+
+        let res = match popup {
+            ViewShell::XdgV6(handle) => handle.run(|shell| {
+                if let Some(XdgV6ShellState::Popup(popup)) = shell.state() {
+                    unsafe { wlr_xdg_popup_v6_unconstrain_from_box(popup.as_ptr(), &mut bounds) };
+                }
+            }),
+            ViewShell::Xdg(handle) => handle.run(|shell| {
+                if let Some(XdgShellState::Popup(popup)) = shell.state() {
+                    unsafe { wlr_xdg_popup_unconstrain_from_box(popup.as_ptr(), &mut bounds) };
+                }
+            }),
+            // X11 menus are placed by the client
+            ViewShell::XWayland(_) | ViewShell::Layer(_) => Ok(()),
+        };
+
+        if let Err(err) = res {
+            warn!("Error in unconstrain_popup: {}", err);
+        }
     }
 
-    #[wlroots_dehandle(shell)]
+    /// Maps a point in screen coordinates to surface-local coordinates.
     pub fn map_location(&self, point: Vector2<f64>) -> (f64, f64) {
-        let shell_h = &self.shell;
-        use shell_h as shell;
-        let origin = shell.geometry().origin;
-        (point.x - origin.x as f64, point.y - origin.y as f64)
+        let origin = self.surface_origin();
+        (point.x - origin.x, point.y - origin.y)
     }
 }
@@ -0,0 +1,115 @@
+//! Support for the xdg-decoration protocol.
+//!
+//! wlroots-rs doesn’t wrap xdg-decoration yet, so this uses wlroots-sys and listens to the
+//! signals directly. Clients leave decorations to the server for the windows that sfc draws a
+//! title strip above, and draw their own for the others.
+//!
+//! Before a window is mapped, the rules decide whether it will have a title strip, and the mode
+//! is set again once it’s mapped.
+
+use crate::utils::{container_of, list_remove, listener, signal_add, with_server};
+use std::fmt;
+use std::os::raw::c_void;
+use wlroots::wlroots_sys::wlr_xdg_toplevel_decoration_v1_mode::*;
+use wlroots::wlroots_sys::*;
+
+/// The decoration manager global.
+///
+/// This must stay at the same address, so it’s always boxed.
+#[repr(C)]
+pub struct XdgDecorationManager {
+    // must be the first field, see new_decoration_notify
+    new_decoration: wl_listener,
+    manager: *mut wlr_xdg_decoration_manager_v1,
+}
+
+impl fmt::Debug for XdgDecorationManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "XdgDecorationManager({:?})", self.manager)
+    }
+}
+
+impl XdgDecorationManager {
+    /// Creates the decoration manager global on the given display.
+    pub unsafe fn new(display: *mut wl_display) -> Box<XdgDecorationManager> {
+        let manager = wlr_xdg_decoration_manager_v1_create(display);
+        let mut this = Box::new(XdgDecorationManager {
+            new_decoration: listener(new_decoration_notify),
+            manager,
+        });
+        signal_add(
+            &mut (*manager).events.new_toplevel_decoration,
+            &mut this.new_decoration,
+        );
+        this
+    }
+}
+
+/// Listeners for a single toplevel decoration, freed when the decoration is destroyed.
+#[repr(C)]
+struct ToplevelDecoration {
+    // the order of these fields is used to get back to the struct from the listeners
+    request_mode: wl_listener,
+    destroy: wl_listener,
+    map: wl_listener,
+    decoration: *mut wlr_xdg_toplevel_decoration_v1,
+}
+
+unsafe extern "C" fn new_decoration_notify(_: *mut wl_listener, data: *mut c_void) {
+    let decoration = data as *mut wlr_xdg_toplevel_decoration_v1;
+    let listeners = Box::into_raw(Box::new(ToplevelDecoration {
+        request_mode: listener(request_mode_notify),
+        destroy: listener(destroy_notify),
+        map: listener(map_notify),
+        decoration,
+    }));
+    signal_add(
+        &mut (*decoration).events.request_mode,
+        &mut (*listeners).request_mode,
+    );
+    signal_add(&mut (*decoration).events.destroy, &mut (*listeners).destroy);
+    // this is added after the shell’s own listener, so the view exists by the time it’s called
+    signal_add(
+        &mut (*(*decoration).surface).events.map,
+        &mut (*listeners).map,
+    );
+
+    set_mode(decoration);
+}
+
+/// Tells the client who draws the decorations, regardless of what it prefers.
+unsafe fn set_mode(decoration: *mut wlr_xdg_toplevel_decoration_v1) {
+    let surface = (*decoration).surface;
+    let mut decorated = false;
+    with_server("decoration", |server| {
+        decorated = server.decorates_toplevel(surface)
+    });
+    let mode = if decorated {
+        WLR_XDG_TOPLEVEL_DECORATION_V1_MODE_SERVER_SIDE
+    } else {
+        WLR_XDG_TOPLEVEL_DECORATION_V1_MODE_CLIENT_SIDE
+    };
+    wlr_xdg_toplevel_decoration_v1_set_mode(decoration, mode);
+}
+
+unsafe extern "C" fn request_mode_notify(listener: *mut wl_listener, _: *mut c_void) {
+    // request_mode is the first field
+    let listeners = container_of::<ToplevelDecoration>(listener, 0);
+    set_mode((*listeners).decoration);
+}
+
+unsafe extern "C" fn map_notify(listener: *mut wl_listener, _: *mut c_void) {
+    // map is the third field
+    let listeners = container_of::<ToplevelDecoration>(listener, 2);
+    set_mode((*listeners).decoration);
+}
+
+unsafe extern "C" fn destroy_notify(listener: *mut wl_listener, _: *mut c_void) {
+    // destroy directly follows request_mode, which is the first field
+    let listeners = container_of::<ToplevelDecoration>(listener, 1);
+
+    list_remove(&mut (*listeners).request_mode.link);
+    list_remove(&mut (*listeners).destroy.link);
+    list_remove(&mut (*listeners).map.link);
+    drop(Box::from_raw(listeners));
+}
@@ -0,0 +1,180 @@
+//! XWayland support for X11 apps.
+//!
+//! wlroots-rs always starts XWayland along with the compositor, so this creates it with
+//! wlroots-sys in lazy mode instead, where the X server is only started once the first X11
+//! client connects, and listens to the signals directly.
+
+use crate::config::UNKNOWN_APP_ID;
+use crate::server::Server;
+use crate::shell::string_from_ptr;
+use crate::utils::{container_of, list_remove, listener, signal_add, with_server};
+use crate::view::{View, ViewShell};
+use std::env;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_void;
+use std::rc::Rc;
+use wlroots::wlroots_sys::*;
+
+/// Returns the title of an X11 window.
+pub unsafe fn surface_title(surface: *mut wlr_xwayland_surface) -> String {
+    string_from_ptr((*surface).title).unwrap_or_default()
+}
+
+/// Returns the WM_CLASS of an X11 window, which is used like an app id.
+pub unsafe fn surface_class(surface: *mut wlr_xwayland_surface) -> String {
+    string_from_ptr((*surface).class).unwrap_or_else(|| String::from(UNKNOWN_APP_ID))
+}
+
+/// The XWayland server.
+///
+/// This must stay at the same address, so it’s always boxed.
+#[repr(C)]
+pub struct XWayland {
+    // must be the first field, see new_surface_notify
+    new_surface: wl_listener,
+    xwayland: *mut wlr_xwayland,
+}
+
+impl fmt::Debug for XWayland {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "XWayland({:?})", self.xwayland)
+    }
+}
+
+impl XWayland {
+    /// Sets up XWayland and sets `DISPLAY` so that X11 clients started from sfc find it.
+    pub unsafe fn new(
+        display: *mut wl_display,
+        compositor: *mut wlr_compositor,
+        seat: *mut wlr_seat,
+    ) -> Box<XWayland> {
+        let xwayland = wlr_xwayland_create(display, compositor, true);
+        let mut this = Box::new(XWayland {
+            new_surface: listener(new_surface_notify),
+            xwayland,
+        });
+        signal_add(&mut (*xwayland).events.new_surface, &mut this.new_surface);
+        wlr_xwayland_set_seat(xwayland, seat);
+
+        let display_name = CStr::from_ptr((*xwayland).display_name.as_ptr());
+        if let Ok(display_name) = display_name.to_str() {
+            info!("XWayland is on DISPLAY={}", display_name);
+            env::set_var("DISPLAY", display_name);
+        }
+
+        this
+    }
+}
+
+impl Drop for XWayland {
+    fn drop(&mut self) {
+        unsafe {
+            list_remove(&mut self.new_surface.link);
+            wlr_xwayland_destroy(self.xwayland);
+        }
+    }
+}
+
+/// Listeners for a single X11 window, freed when the window is destroyed.
+#[repr(C)]
+struct SurfaceListeners {
+    // the order of these fields is used to get back to the struct from the listeners
+    map: wl_listener,
+    unmap: wl_listener,
+    destroy: wl_listener,
+    request_configure: wl_listener,
+    request_fullscreen: wl_listener,
+    surface: *mut wlr_xwayland_surface,
+}
+
+unsafe extern "C" fn new_surface_notify(_: *mut wl_listener, data: *mut c_void) {
+    let surface = data as *mut wlr_xwayland_surface;
+    let listeners = Box::into_raw(Box::new(SurfaceListeners {
+        map: listener(map_notify),
+        unmap: listener(unmap_notify),
+        destroy: listener(destroy_notify),
+        request_configure: listener(request_configure_notify),
+        request_fullscreen: listener(request_fullscreen_notify),
+        surface,
+    }));
+    let events = &mut (*surface).events;
+    signal_add(&mut events.map, &mut (*listeners).map);
+    signal_add(&mut events.unmap, &mut (*listeners).unmap);
+    signal_add(&mut events.destroy, &mut (*listeners).destroy);
+    signal_add(
+        &mut events.request_configure,
+        &mut (*listeners).request_configure,
+    );
+    signal_add(
+        &mut events.request_fullscreen,
+        &mut (*listeners).request_fullscreen,
+    );
+}
+
+unsafe extern "C" fn map_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let surface = (*container_of::<SurfaceListeners>(listener, 0)).surface;
+    let view = Rc::new(View::new(ViewShell::XWayland(surface)));
+
+    with_server("map", |server| {
+        if (*surface).override_redirect {
+            // menus and tooltips are placed by the client and float above everything
+            server.add_unmanaged_view(view);
+        } else {
+            // the client may have asked to be fullscreen before it was mapped
+            if (*surface).fullscreen {
+                view.set_fullscreen(true);
+            }
+            server.add_view(surface_class(surface), &surface_title(surface), view);
+        }
+    });
+}
+
+unsafe extern "C" fn unmap_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let surface = (*container_of::<SurfaceListeners>(listener, 1)).surface;
+    let shell = ViewShell::XWayland(surface);
+    with_server("unmap", |server| server.remove_view_for_surface(&shell));
+}
+
+unsafe extern "C" fn destroy_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let listeners = container_of::<SurfaceListeners>(listener, 2);
+    list_remove(&mut (*listeners).map.link);
+    list_remove(&mut (*listeners).unmap.link);
+    list_remove(&mut (*listeners).destroy.link);
+    list_remove(&mut (*listeners).request_configure.link);
+    list_remove(&mut (*listeners).request_fullscreen.link);
+    drop(Box::from_raw(listeners));
+}
+
+unsafe extern "C" fn request_configure_notify(listener: *mut wl_listener, data: *mut c_void) {
+    let surface = (*container_of::<SurfaceListeners>(listener, 3)).surface;
+    let event = data as *mut wlr_xwayland_surface_configure_event;
+
+    let mut handled = false;
+    with_server("request_configure", |server| {
+        if let Some(view) = server.find_view(&ViewShell::XWayland(surface)) {
+            // mapped windows stay where they were put
+            view.reconfigure();
+            handled = true;
+        }
+    });
+
+    if !handled {
+        wlr_xwayland_surface_configure(
+            surface,
+            (*event).x,
+            (*event).y,
+            (*event).width,
+            (*event).height,
+        );
+    }
+}
+
+unsafe extern "C" fn request_fullscreen_notify(listener: *mut wl_listener, _: *mut c_void) {
+    let surface = (*container_of::<SurfaceListeners>(listener, 4)).surface;
+    let fullscreen = (*surface).fullscreen;
+    let shell = ViewShell::XWayland(surface);
+    with_server("request_fullscreen", |server| {
+        server.set_fullscreen(&shell, fullscreen)
+    });
+}